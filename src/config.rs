@@ -2,9 +2,11 @@
 
 use std::{fs, io};
 use std::collections::HashMap;
+use std::hash::Hasher;
 use std::path::{Path, PathBuf};
 use femtomap::render::Color;
 use serde::Deserialize;
+use crate::railway::class::Railway;
 
 //------------ MapConfig -----------------------------------------------------
 
@@ -17,9 +19,52 @@ pub struct MapConfig {
     /// The regions of the map.
     pub regions: HashMap<String, Region>,
 
+    /// Named groups of regions.
+    ///
+    /// Members can be regions or other groups.
+    #[serde(default)]
+    pub groups: HashMap<String, Vec<String>>,
+
     /// Overide of the default colors of the map.
     #[serde(default)]
     pub colors: HashMap<String, Color>,
+
+    /// A directory with additional markers as SVG files.
+    #[serde(default)]
+    pub markers: Option<PathBuf>,
+
+    /// Suppress labels that overlap labels of higher priority?
+    #[serde(default)]
+    pub label_collisions: bool,
+
+    /// The colors of railway operators for the `op` layers.
+    ///
+    /// Keys are the operator codes given to features via `:operatorN`
+    /// symbols. Operators missing here are drawn in grey.
+    #[serde(default)]
+    pub operators: HashMap<String, Color>,
+
+    /// Overide of the default measures of the map.
+    #[serde(default)]
+    pub measures: MeasuresConfig,
+
+    /// The detail levels where the rendering of track changes.
+    #[serde(default)]
+    pub track_detail: TrackDetailConfig,
+
+    /// The minimum width of track lines in device pixels.
+    ///
+    /// Thinner track is drawn with this width so it stays visible at low
+    /// zoom levels. A value of zero, the default, disables the minimum.
+    #[serde(default)]
+    pub min_line_width: f64,
+
+    /// Draw a scale bar and north arrow onto each tile?
+    ///
+    /// This is meant for standalone exports of the map. If the section is
+    /// missing, nothing is drawn.
+    #[serde(default)]
+    pub scale_bar: Option<ScaleBarConfig>,
 }
 
 impl MapConfig {
@@ -28,16 +73,141 @@ impl MapConfig {
         let mut data: Self = toml::from_str(&data).map_err(|err| {
             io::Error::new(io::ErrorKind::Other, err)
         })?;
+        data.measures.check().map_err(|err| {
+            io::Error::new(io::ErrorKind::InvalidData, err)
+        })?;
+        for code in data.operators.keys() {
+            if !matches!(
+                code.parse::<u8>(),
+                Ok(code) if code <= Railway::MAX_OPERATOR
+            ) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid operator code '{}'", code)
+                ))
+            }
+        }
+        data.track_detail.check().map_err(|err| {
+            io::Error::new(io::ErrorKind::InvalidData, err)
+        })?;
+        if !data.min_line_width.is_finite() || data.min_line_width < 0. {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid minimum line width {}", data.min_line_width)
+            ))
+        }
         if let Some(path) = path.as_ref().parent() {
             data.prepare(path);
         }
         Ok(data)
     }
 
+    /// Merges the regions and groups of another map configuration.
+    ///
+    /// Regions and groups of `other` replace those of the same name. The
+    /// kind and name of each of them are returned in sorted order so the
+    /// caller can warn about them. All other settings of `other` are
+    /// ignored.
+    pub fn merge(&mut self, other: MapConfig) -> Vec<(&'static str, String)> {
+        let mut replaced = Vec::new();
+        for (name, region) in other.regions {
+            if self.regions.contains_key(&name) {
+                replaced.push(("region", name.clone()));
+            }
+            self.regions.insert(name, region);
+        }
+        for (name, members) in other.groups {
+            if self.groups.contains_key(&name) {
+                replaced.push(("group", name.clone()));
+            }
+            self.groups.insert(name, members);
+        }
+        replaced.sort();
+        replaced
+    }
+
+    /// Expands a list of region and group names into region names.
+    ///
+    /// Groups are expanded transitively. If a name is both a region and a
+    /// group, the region wins. The result is sorted and free of
+    /// duplicates. Returns an error for unknown names and for groups that
+    /// contain themselves.
+    pub fn expand_regions(
+        &self, names: &[String]
+    ) -> Result<Vec<String>, String> {
+        let mut res = Vec::new();
+        let mut path = Vec::new();
+        for name in names {
+            self.expand_name(name, &mut path, &mut res)?;
+        }
+        res.sort();
+        res.dedup();
+        Ok(res)
+    }
+
+    /// Adds the regions for a single name to `res`.
+    ///
+    /// The `path` contains the groups currently being expanded.
+    fn expand_name<'a>(
+        &'a self,
+        name: &'a str,
+        path: &mut Vec<&'a str>,
+        res: &mut Vec<String>,
+    ) -> Result<(), String> {
+        if self.regions.contains_key(name) {
+            res.push(name.into());
+            return Ok(())
+        }
+        let members = match self.groups.get(name) {
+            Some(members) => members,
+            None => return Err(format!("Unknown region '{}'.", name)),
+        };
+        if path.contains(&name) {
+            return Err(format!(
+                "Region group '{}' contains itself via {}.",
+                name, path.join(" -> ")
+            ))
+        }
+        path.push(name);
+        for member in members {
+            self.expand_name(member, path, res)?;
+        }
+        path.pop();
+        Ok(())
+    }
+
     pub fn prepare(&mut self, base_dir: &Path) {
         self.regions.values_mut().for_each(|region| {
             region.prepare(base_dir)
         });
+        if let Some(markers) = self.markers.as_mut() {
+            *markers = base_dir.join(&markers);
+        }
+    }
+
+    /// Returns a version of the map’s source data.
+    ///
+    /// The version is derived from the content of the map configuration
+    /// files, all files of the given regions, and the marker files. It only
+    /// changes if any of these change, including across restarts.
+    pub fn version<'a>(
+        &self,
+        paths: &[PathBuf],
+        regions: impl IntoIterator<Item = &'a Region>,
+    ) -> u64 {
+        let mut hasher = StableHasher::default();
+        hasher.write(env!("CARGO_PKG_VERSION").as_bytes());
+        for path in paths {
+            hash_path(path, &mut hasher);
+        }
+        for region in regions {
+            hash_path(&region.paths, &mut hasher);
+            hash_path(&region.rules, &mut hasher);
+        }
+        if let Some(markers) = self.markers.as_ref() {
+            hash_path(markers, &mut hasher);
+        }
+        hasher.finish()
     }
 }
 
@@ -73,3 +243,344 @@ impl Region {
     }
 }
 
+
+//------------ MeasuresConfig ------------------------------------------------
+
+/// Overides of the default measures.
+///
+/// All values but those of the casing are given in bp and replace the
+/// default value for all detail levels. Since the defaults differ between
+/// detail levels, an override flattens them into a single value, e.g.,
+/// giving `main_double` makes double track the same width at every zoom.
+/// Values that are not given keep their default. See the
+/// `railway::measures` module for the meaning of the individual measures.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MeasuresConfig {
+    pub dt: Option<f64>,
+    pub main_track: Option<f64>,
+    pub main_double: Option<f64>,
+    pub main_skip: Option<f64>,
+    pub light_track: Option<f64>,
+    pub light_double: Option<f64>,
+    pub light_skip: Option<f64>,
+    pub guide_width: Option<f64>,
+    pub border_width: Option<f64>,
+    pub seg: Option<f64>,
+    pub station_width: Option<f64>,
+    pub station_height: Option<f64>,
+
+    /// The widths of track casings.
+    pub casing: CasingConfig,
+
+    /// The scaling of label sizes by class.
+    pub label_scale: LabelScaleConfig,
+}
+
+impl MeasuresConfig {
+    /// Checks that all given values are usable at all.
+    ///
+    /// Skips may be zero, everything else needs to be positive.
+    pub fn check(&self) -> Result<(), String> {
+        let values = [
+            ("dt", self.dt, false),
+            ("main_track", self.main_track, false),
+            ("main_double", self.main_double, false),
+            ("main_skip", self.main_skip, true),
+            ("light_track", self.light_track, false),
+            ("light_double", self.light_double, false),
+            ("light_skip", self.light_skip, true),
+            ("guide_width", self.guide_width, false),
+            ("border_width", self.border_width, false),
+            ("seg", self.seg, false),
+            ("station_width", self.station_width, false),
+            ("station_height", self.station_height, false),
+        ];
+        for (name, value, zero) in values {
+            if let Some(value) = value {
+                if !value.is_finite() || value < 0. || (!zero && value == 0.) {
+                    return Err(format!(
+                        "invalid value {} for measure '{}'", value, name
+                    ))
+                }
+            }
+        }
+        self.casing.check()?;
+        self.label_scale.check()
+    }
+}
+
+
+//------------ CasingConfig --------------------------------------------------
+
+/// The widths of the casings drawn around track.
+///
+/// The defaults are the widths used by the standard map.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CasingConfig {
+    /// The factor applied to the class skip on either side of a casing.
+    ///
+    /// Classes can override this via a `:casingskipN` symbol with `N` in
+    /// percent.
+    pub skip: f64,
+
+    /// The width of the casing of single track in _dt._
+    pub single: f64,
+
+    /// The width of the casing of double track in _dt._
+    pub double: f64,
+}
+
+impl CasingConfig {
+    /// Checks that all values are usable.
+    ///
+    /// The skip may be zero, the widths need to be positive.
+    pub fn check(&self) -> Result<(), String> {
+        let values = [
+            ("skip", self.skip, true),
+            ("single", self.single, false),
+            ("double", self.double, false),
+        ];
+        for (name, value, zero) in values {
+            if !value.is_finite() || value < 0. || (!zero && value == 0.) {
+                return Err(format!(
+                    "invalid value {} for casing '{}'", value, name
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for CasingConfig {
+    fn default() -> Self {
+        CasingConfig { skip: 1., single: 1.2, double: 2.2 }
+    }
+}
+
+
+//------------ LabelScaleConfig ----------------------------------------------
+
+/// The factors to scale label sizes with by the importance of their class.
+///
+/// Major labels are those of first and second class railways with full
+/// passenger service. Minor labels are those of trams, private railways,
+/// sidings, and anything without passenger service. The size of all other
+/// labels is never scaled. The defaults keep all labels at their size and
+/// weight.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LabelScaleConfig {
+    /// The factor for the size of major labels.
+    pub major: f64,
+
+    /// The factor for the size of minor labels.
+    pub minor: f64,
+
+    /// Should major labels be set in bold unless given another weight?
+    pub major_bold: bool,
+}
+
+impl LabelScaleConfig {
+    /// Checks that both factors are positive.
+    pub fn check(&self) -> Result<(), String> {
+        for (name, value) in [("major", self.major), ("minor", self.minor)] {
+            if !value.is_finite() || value <= 0. {
+                return Err(format!(
+                    "invalid value {} for label scale '{}'", value, name
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for LabelScaleConfig {
+    fn default() -> Self {
+        LabelScaleConfig { major: 1., minor: 1., major_bold: false }
+    }
+}
+
+
+//------------ TrackDetailConfig ---------------------------------------------
+
+/// The detail levels where the rendering of track changes.
+///
+/// Each value is the first detail level where track is rendered in the
+/// respective way. See the `railway::feature::track` module for what is
+/// drawn at each of them. The defaults are the detail levels used by the
+/// standard map.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrackDetailConfig {
+    /// Track is drawn with class-dependent widths and pax dashes.
+    pub medium: u8,
+
+    /// Bridges are drawn.
+    pub bridges: u8,
+
+    /// Separate tracks and all decorations are drawn.
+    pub full: u8,
+}
+
+impl TrackDetailConfig {
+    /// Checks that the levels are in increasing order.
+    pub fn check(&self) -> Result<(), String> {
+        if self.medium > self.bridges || self.bridges > self.full {
+            return Err(format!(
+                "track detail levels must not decrease: \
+                 medium {}, bridges {}, full {}",
+                self.medium, self.bridges, self.full
+            ))
+        }
+        Ok(())
+    }
+}
+
+impl Default for TrackDetailConfig {
+    fn default() -> Self {
+        TrackDetailConfig { medium: 2, bridges: 3, full: 4 }
+    }
+}
+
+
+//------------ ScaleBarConfig ------------------------------------------------
+
+/// The placement of the scale bar and north arrow.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScaleBarConfig {
+    /// The corner to draw them in.
+    pub corner: Corner,
+
+    /// The units the scale bar uses.
+    pub units: ScaleUnits,
+}
+
+
+//------------ Corner --------------------------------------------------------
+
+/// A corner of the output.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    #[default]
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    /// Returns whether the corner is on the left side.
+    pub fn is_left(self) -> bool {
+        matches!(self, Corner::TopLeft | Corner::BottomLeft)
+    }
+
+    /// Returns whether the corner is at the top.
+    pub fn is_top(self) -> bool {
+        matches!(self, Corner::TopLeft | Corner::TopRight)
+    }
+}
+
+
+//------------ ScaleUnits ----------------------------------------------------
+
+/// The units of a scale bar.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScaleUnits {
+    /// Kilometres and, for short distances, metres.
+    #[default]
+    Km,
+
+    /// Statute miles.
+    Mi,
+}
+
+impl ScaleUnits {
+    /// Returns the length of the unit in metres.
+    pub fn metres(self) -> f64 {
+        match self {
+            ScaleUnits::Km => 1000.,
+            ScaleUnits::Mi => 1609.344,
+        }
+    }
+
+    /// Returns the label for a distance given in these units.
+    pub fn label(self, value: f64) -> String {
+        match self {
+            ScaleUnits::Km if value < 1. => {
+                format!("{} m", (value * 1000.).round())
+            }
+            ScaleUnits::Km => format!("{} km", value),
+            ScaleUnits::Mi => format!("{} mi", value),
+        }
+    }
+}
+
+
+//------------ StableHasher --------------------------------------------------
+
+/// A hasher that produces the same hash every time.
+///
+/// This uses 64 bit FNV-1a which is fast and, unlike the standard library’s
+/// default hasher, guaranteed to stay the same.
+#[derive(Clone, Copy, Debug)]
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Adds the content of a file or directory to a hasher.
+///
+/// Directories are traversed recursively in a stable order. Files and
+/// directories starting with a dot are skipped. Errors are ignored – the
+/// loader will complain about them.
+fn hash_path(path: &Path, hasher: &mut StableHasher) {
+    if path.is_dir() {
+        let mut entries = match fs::read_dir(path) {
+            Ok(entries) => {
+                entries.filter_map(|entry| {
+                    entry.ok().map(|entry| entry.path())
+                }).filter(|path| {
+                    !path.file_name().map(|name| {
+                        name.to_string_lossy().starts_with('.')
+                    }).unwrap_or(false)
+                }).collect::<Vec<_>>()
+            }
+            Err(_) => return,
+        };
+        entries.sort();
+        for entry in entries {
+            hash_path(&entry, hasher)
+        }
+    }
+    else if let Ok(data) = fs::read(path) {
+        if let Some(name) = path.file_name() {
+            hasher.write(name.to_string_lossy().as_bytes());
+        }
+        hasher.write_u64(data.len() as u64);
+        hasher.write(&data);
+    }
+}
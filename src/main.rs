@@ -1,29 +1,76 @@
-use std::{fs, io};
+use std::{fs, io, process, thread};
 use std::net::SocketAddr;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
-use clap::Parser;
+use clap::{ArgAction, Parser, Subcommand};
 use femtomap::import::eval::Failed;
 use femtomap::import::watch::WatchSet;
+use log::{debug, error, info, warn, LevelFilter};
 use notify::Watcher;
 use railmap::MapConfig;
 use railmap::railway;
 use railmap::railway::import::load::LoadFeatures;
-use railmap::server::{Server, ServerControl};
+use railmap::server::{Server, ServerConfig, ServerControl};
+use railmap::tile;
 use tokio::sync::{mpsc, oneshot};
 
 const DEFAULT_CONFIG_PATH: &str = "/etc/railmap.conf";
 
+/// The default time in milliseconds to wait for the map files to settle.
+const DEFAULT_DEBOUNCE: u64 = 100;
+
+/// The default patterns of file names whose changes don’t trigger a reload.
+const DEFAULT_WATCH_IGNORE: &[&str] = &[".*"];
+
 //------------ ConfigFile ----------------------------------------------------
 
 #[derive(serde::Deserialize)]
 struct ConfigFile {
-    map: Option<PathBuf>,
+    map: Option<MapFiles>,
     regions: Option<Vec<String>>,
     listen: Option<SocketAddr>,
+    tile_size: Option<u32>,
+    max_age: Option<u32>,
+    webp_quality: Option<u8>,
+    jpeg_quality: Option<u8>,
+    debounce: Option<u64>,
+    watch_ignore: Option<Vec<String>>,
+    metatile: Option<u32>,
+    background: Option<tile::Background>,
+    cors: Option<CorsFile>,
+}
+
+/// The `cors` section of the config file.
+#[derive(serde::Deserialize)]
+struct CorsFile {
+    /// The origins allowed to access the server from a browser.
+    #[serde(default)]
+    origins: Vec<String>,
+}
+
+/// The map configuration files given in the config file.
+///
+/// This can be a single path or a list of paths.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum MapFiles {
+    One(PathBuf),
+    Many(Vec<PathBuf>),
+}
+
+impl From<MapFiles> for Vec<PathBuf> {
+    fn from(files: MapFiles) -> Self {
+        match files {
+            MapFiles::One(path) => vec![path],
+            MapFiles::Many(paths) => paths,
+        }
+    }
 }
 
+
 //------------ Args ----------------------------------------------------------
 
 #[derive(Parser)]
@@ -34,13 +81,23 @@ struct Args {
     config: Option<PathBuf>,
 
     /// The map configuration file.
+    ///
+    /// Can be given multiple times. The regions of later files are added
+    /// to those of earlier files, replacing regions of the same name.
     #[arg(short, long, value_name = "FILE")]
-    map: Option<PathBuf>,
+    map: Vec<PathBuf>,
 
     /// Select regions to render.
     #[arg(value_name = "NAME")]
     region: Vec<String>,
 
+    /// Only load features within an area.
+    ///
+    /// The area is given as west,south,east,north in degrees. Features
+    /// that are only partly inside are kept.
+    #[arg(long, value_name = "BBOX", value_parser = parse_bbox)]
+    bbox: Option<kurbo::Rect>,
+
     /// The addr to listen on.
     #[arg(short, long, value_name = "ADDR")]
     listen: Option<SocketAddr>,
@@ -52,27 +109,98 @@ struct Args {
     /// Enable proof mode.
     #[arg(short, long)]
     proof: bool,
+
+    /// Log more. Can be given twice for even more.
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Log less. Can be given up to three times for no logging at all.
+    #[arg(short, long, action = ArgAction::Count, conflicts_with = "verbose")]
+    quiet: u8,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+
+//------------ Command -------------------------------------------------------
+
+#[derive(Subcommand)]
+enum Command {
+    /// Load the map and report all errors without serving it.
+    ///
+    /// Exits with a non-zero status if there were any errors.
+    Check,
+
+    /// Load the map and print a JSON description of all features.
+    ///
+    /// Nothing is rendered. The output lists class, category, and bounds
+    /// of each feature as well as the number of features per set.
+    Dump {
+        /// Select regions to dump instead of the configured ones.
+        #[arg(long, value_name = "NAME")]
+        region: Vec<String>,
+    },
+
+    /// Render all tiles of a range of zoom levels into a directory.
+    ///
+    /// The tiles are written as PNG files to `{out}/{z}/{x}/{y}.png`.
+    /// Tiles without any features are skipped.
+    RenderTiles {
+        /// Select regions to render instead of the configured ones.
+        #[arg(long, value_name = "NAME")]
+        region: Vec<String>,
+
+        /// The zoom levels to render, e.g., `0..12` including both ends.
+        #[arg(long, value_name = "RANGE", value_parser = parse_zoom_range)]
+        zoom: (u8, u8),
+
+        /// The railway layer to render.
+        #[arg(
+            long, value_name = "LAYER", default_value = "el",
+            value_parser = parse_layer
+        )]
+        layer: railway::LayerId,
+
+        /// The directory to write the tiles to.
+        #[arg(long, value_name = "DIR")]
+        out: PathBuf,
+    },
 }
 
 
 //------------ Config --------------------------------------------------------
 
 struct Config {
-    map: PathBuf,
+    map: Vec<PathBuf>,
     regions: Option<Vec<String>>,
+    bbox: Option<kurbo::Rect>,
     listen: SocketAddr,
     watch: bool,
-    proof: bool,
+    debounce: Duration,
+    watch_ignore: Vec<String>,
+    check: bool,
+    dump: bool,
+    render_tiles: Option<RenderTiles>,
+    server: ServerConfig,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
-            map: PathBuf::new(),
+            map: Vec::new(),
             regions: None,
+            bbox: None,
             listen: SocketAddr::from_str("127.0.0.1:8080").unwrap(),
             watch: false,
-            proof: false,
+            debounce: Duration::from_millis(DEFAULT_DEBOUNCE),
+            watch_ignore: DEFAULT_WATCH_IGNORE.iter().map(|pattern| {
+                String::from(*pattern)
+            }).collect(),
+            check: false,
+            dump: false,
+            render_tiles: None,
+            server: ServerConfig::default(),
         }
     }
 }
@@ -80,6 +208,7 @@ impl Default for Config {
 impl Config {
     pub fn get() -> Result<Self, Failed> {
         let args = Args::parse();
+        Logger::init(args.verbose, args.quiet);
 
         let (config_path, insist) = match args.config.as_ref() {
             Some(path) => (path.clone(), true),
@@ -93,21 +222,21 @@ impl Config {
                 let value = match toml::from_str(&content) {
                     Ok(value) => value,
                     Err(err) => {
-                        eprintln!(
+                        error!(
                             "Failed to parse config file: {}: {}",
                             config_path.display(), err
                         );
                         return Err(Failed)
                     }
                 };
-                config.apply_toml(value);
+                config.apply_toml(value)?;
             }
             Err(err) => {
                 if
                     !matches!(err.kind(), io::ErrorKind::NotFound)
                     || insist
                 {
-                    eprintln!(
+                    error!(
                         "Failed to read config file {}: {}",
                         config_path.display(), err
                     );
@@ -118,8 +247,11 @@ impl Config {
 
         config.apply_args(args);
 
-        if !config.map.is_file() {
-            eprintln!("Map configuration not provided or does not exist.");
+        if
+            config.map.is_empty()
+            || !config.map.iter().all(|map| map.is_file())
+        {
+            error!("Map configuration not provided or does not exist.");
             return Err(Failed)
         }
 
@@ -127,8 +259,8 @@ impl Config {
     }
 
     fn apply_args(&mut self, args: Args) {
-        if let Some(map) = args.map {
-            self.map = map;
+        if !args.map.is_empty() {
+            self.map = args.map;
         }
         if !args.region.is_empty() {
             self.regions = Some(args.region);
@@ -136,13 +268,30 @@ impl Config {
         if let Some(addr) = args.listen {
             self.listen = addr;
         }
+        self.bbox = args.bbox;
         self.watch = args.watch;
-        self.proof = args.proof;
+        match args.command {
+            Some(Command::Check) => self.check = true,
+            Some(Command::Dump { region }) => {
+                if !region.is_empty() {
+                    self.regions = Some(region);
+                }
+                self.dump = true;
+            }
+            Some(Command::RenderTiles { region, zoom, layer, out }) => {
+                if !region.is_empty() {
+                    self.regions = Some(region);
+                }
+                self.render_tiles = Some(RenderTiles { zoom, layer, out });
+            }
+            None => { }
+        }
+        self.server.proof = args.proof;
     }
 
-    fn apply_toml(&mut self, toml: ConfigFile) {
+    fn apply_toml(&mut self, toml: ConfigFile) -> Result<(), Failed> {
         if let Some(map) = toml.map {
-            self.map = map
+            self.map = map.into()
         }
         if let Some(regions) = toml.regions {
             self.regions = Some(regions);
@@ -150,6 +299,42 @@ impl Config {
         if let Some(listen) = toml.listen {
             self.listen = listen;
         }
+        if let Some(tile_size) = toml.tile_size {
+            if let Err(err) = tile::check_tile_size(tile_size) {
+                error!("Failed to parse config file: {}", err);
+                return Err(Failed)
+            }
+            self.server.tile_size = tile_size;
+        }
+        if let Some(max_age) = toml.max_age {
+            self.server.max_age = max_age;
+        }
+        if let Some(quality) = toml.webp_quality {
+            self.server.webp_quality = tile::WebpQuality::from_config(quality);
+        }
+        if let Some(quality) = toml.jpeg_quality {
+            self.server.jpeg_quality = Some(quality.clamp(1, 100));
+        }
+        if let Some(metatile) = toml.metatile {
+            if let Err(err) = tile::check_metatile(metatile) {
+                error!("Failed to parse config file: {}", err);
+                return Err(Failed)
+            }
+            self.server.metatile = metatile;
+        }
+        if let Some(background) = toml.background {
+            self.server.background = background;
+        }
+        if let Some(cors) = toml.cors {
+            self.server.cors_origins = cors.origins;
+        }
+        if let Some(debounce) = toml.debounce {
+            self.debounce = Duration::from_millis(debounce);
+        }
+        if let Some(ignore) = toml.watch_ignore {
+            self.watch_ignore = ignore;
+        }
+        Ok(())
     }
 
     pub async fn run(mut self) {
@@ -168,14 +353,113 @@ impl Config {
             None => return,
         };
 
-        let (server, ctrl) = Server::new(map, self.proof);
+        let (server, ctrl) = Server::new(map, self.server.clone());
         let listen = self.listen;
 
         if self.watch {
             tokio::spawn(self.watch(ctrl, watch));
         }
 
-        let _ = server.run(listen).await;
+        if let Err(err) = server.run(listen).await {
+            error!("Failed to listen on {}: {}", listen, err);
+        }
+    }
+
+    /// Loads the map and reports whether that was successful.
+    ///
+    /// All errors are printed to stderr by `load_railway`.
+    fn check(mut self) -> bool {
+        if let Some(regions) = self.regions.as_mut() {
+            regions.sort();
+            regions.dedup();
+        }
+        if self.load_railway(&mut WatchSet::default()).is_some() {
+            info!("No errors found.");
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    /// Loads the map and prints a JSON description of its features.
+    ///
+    /// Reports whether loading was successful.
+    fn dump(mut self) -> bool {
+        if let Some(regions) = self.regions.as_mut() {
+            regions.sort();
+            regions.dedup();
+        }
+        let map = match self.load_railway(&mut WatchSet::default()) {
+            Some(map) => map,
+            None => return false,
+        };
+        let stdout = io::stdout().lock();
+        if let Err(err) = serde_json::to_writer_pretty(stdout, &map.dump()) {
+            error!("Failed to write dump: {}", err);
+            return false
+        }
+        println!();
+        true
+    }
+
+    /// Loads the map and renders all its tiles into a directory.
+    ///
+    /// Reports whether loading and rendering was successful.
+    fn render_tiles(mut self, args: RenderTiles) -> bool {
+        if let Some(regions) = self.regions.as_mut() {
+            regions.sort();
+            regions.dedup();
+        }
+        let map = match self.load_railway(&mut WatchSet::default()) {
+            Some(map) => map,
+            None => return false,
+        };
+        let start = Instant::now();
+        let tiles = match args.tiles(&map, &self.server) {
+            Ok(tiles) => tiles,
+            Err(err) => {
+                error!("Cannot render tiles: {}.", err);
+                return false
+            }
+        };
+        info!("Rendering {} tiles.", tiles.len());
+
+        // Tiles are handed out to the threads one by one so that the slow
+        // tiles with many features are spread evenly.
+        let next = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let threads = thread::available_parallelism().map(
+            NonZeroUsize::get
+        ).unwrap_or(1);
+        thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| {
+                    while let Some(tile) = tiles.get(
+                        next.fetch_add(1, Ordering::Relaxed)
+                    ) {
+                        // Stop early if another thread failed already.
+                        if failed.load(Ordering::Relaxed) {
+                            return
+                        }
+                        if let Err(err) = args.write_tile(
+                            *tile, &map, self.server.webp_quality
+                        ) {
+                            error!("Failed to write tile {}: {}", tile, err);
+                            failed.store(true, Ordering::Relaxed);
+                            return
+                        }
+                    }
+                });
+            }
+        });
+        if failed.load(Ordering::Relaxed) {
+            return false
+        }
+        info!(
+            "Rendered tiles in {:.03}s.", start.elapsed().as_secs_f32()
+        );
+        true
     }
 
     async fn watch(self, ctrl: ServerControl, mut watch: WatchSet) {
@@ -192,6 +476,7 @@ impl Config {
     ) -> Result<WatchSet, Failed> {
         let (ev_tx, mut ev_rx) = mpsc::channel(10);
         let (done_tx, done_rx) = oneshot::channel();
+        let ignore = self.watch_ignore.clone();
 
         tokio::task::spawn_blocking(move || {
             let mut watcher = match notify::recommended_watcher(
@@ -201,25 +486,34 @@ impl Config {
                             let mut skip = true;
                             for path in &ev.paths {
                                 if let Some(name) = path.file_name() {
-                                    if name.as_encoded_bytes().first()
-                                        != Some(&b'.')
-                                    {
+                                    if !is_ignored(
+                                        &name.to_string_lossy(), &ignore
+                                    ) {
                                         skip = false;
                                         break;
                                     }
                                 }
                             }
                             if skip {
+                                debug!(
+                                    "Ignoring change to files {:?}.",
+                                    ev.paths
+                                );
                                 return
                             }
                             let _ = ev_tx.blocking_send(());
                         }
-                        _ => { } // XXX Ignore errors for now.
+                        Err(err) => {
+                            warn!("Failed to watch map files: {}", err);
+                        }
                     }
                 }
             ) {
                 Ok(watcher) => watcher,
-                Err(_) => return,
+                Err(err) => {
+                    error!("Failed to watch map files: {}", err);
+                    return
+                }
             };
             for item in watch.iter() {
                 let _ = watcher.watch(
@@ -229,8 +523,13 @@ impl Config {
             let _ = done_rx.blocking_recv();
         });
 
+        // Wait for the first event and then until there haven’t been any
+        // new events for the debounce time. This way, a burst of changes
+        // only triggers one reload once all files have been written.
         let _ = ev_rx.recv().await;
-        tokio::time::sleep(Duration::from_millis(100)).await;
+        while let Ok(Some(())) = tokio::time::timeout(
+            self.debounce, ev_rx.recv()
+        ).await { }
         let _ = done_tx.send(());
 
         let mut watch = WatchSet::default();
@@ -242,39 +541,95 @@ impl Config {
         Ok(watch)
     }
 
+    /// Loads all map configuration files and merges them.
+    ///
+    /// Settings other than the regions are taken from the first file.
+    fn load_map_config(&self) -> Option<MapConfig> {
+        let mut res: Option<MapConfig> = None;
+        for path in &self.map {
+            let map = match MapConfig::load(path) {
+                Ok(map) => map,
+                Err(err) => {
+                    error!(
+                        "Failed to load map config {}: {}",
+                        path.display(), err
+                    );
+                    return None
+                }
+            };
+            match res.as_mut() {
+                Some(res) => {
+                    for (kind, name) in res.merge(map) {
+                        warn!(
+                            "{} '{}' replaced by {}.",
+                            kind, name, path.display()
+                        );
+                    }
+                }
+                None => res = Some(map),
+            }
+        }
+        res
+    }
+
     fn load_railway(
         &self, watch: &mut WatchSet,
     ) -> Option<railway::Map> {
-        let map = match MapConfig::load(&self.map) {
-            Ok(map) => map,
-            Err(err) => {
-                eprintln!(
-                    "Failed to load map config {}: {}",
-                    self.map.display(), err
-                );
-                return None
+        let map = self.load_map_config()?;
+        for warning in railway::measures::Measures::config_warnings(
+            &map.measures
+        ) {
+            warn!("measures: {}", warning);
+        }
+        let regions = match self.regions.as_ref() {
+            Some(names) => {
+                match map.expand_regions(names) {
+                    Ok(regions) => Some(regions),
+                    Err(err) => {
+                        error!("{}", err);
+                        return None
+                    }
+                }
             }
+            None => None,
         };
 
         let start = Instant::now();
+        let version = match regions.as_ref() {
+            Some(values) => {
+                map.version(
+                    &self.map,
+                    values.iter().filter_map(|value| map.regions.get(value))
+                )
+            }
+            None => {
+                // The order needs to be stable for the version to be.
+                let mut names = map.regions.keys().collect::<Vec<_>>();
+                names.sort();
+                map.version(
+                    &self.map,
+                    names.into_iter().map(|name| &map.regions[name])
+                )
+            }
+        };
         let mut features = LoadFeatures::new();
-        match self.regions.as_ref() {
+        features.set_measures(&map.measures);
+        if let Some(bbox) = self.bbox {
+            features.set_limit(bbox);
+        }
+        if let Some(markers) = map.markers.as_ref() {
+            features.load_markers(markers, watch);
+        }
+        match regions.as_ref() {
             Some(values) => {
+                // All names have been checked by expand_regions.
                 for value in values {
-                    match map.regions.get(value) {
-                        Some(region) => {
-                            features.load_region(region, watch);
-                        }
-                        None => {
-                            eprintln!("Unknown region '{}'.", value);
-                            return None;
-                        }
-                    }
+                    features.load_region(value, &map.regions[value], watch);
                 }
             }
             None => {
-                for region in map.regions.values() {
-                    features.load_region(region, watch)
+                for (name, region) in &map.regions {
+                    features.load_region(name, region, watch)
                 }
             }
         }
@@ -282,13 +637,13 @@ impl Config {
         let features = match features.finalize() {
             Ok(features) => features,
             Err(err) => {
-                eprintln!("{}", err);
+                error!("{}", err);
                 return None;
             }
         };
 
-        eprintln!("Loaded map in {:.03}s.", start.elapsed().as_secs_f32());
-        eprintln!(
+        info!("Loaded map in {:.03}s.", start.elapsed().as_secs_f32());
+        info!(
             "Features:\n  \
                railway: {}\n  \
                line labels: {}\n  \
@@ -300,16 +655,322 @@ impl Config {
             features.borders.len(),
         );
 
-        Some(railway::Map::new(features))
+        Some(railway::Map::new(features, &map, version))
+    }
+}
+
+//------------ RenderTiles ---------------------------------------------------
+
+/// The arguments of the `render-tiles` command.
+struct RenderTiles {
+    /// The smallest and largest zoom level to render.
+    zoom: (u8, u8),
+
+    /// The railway layer to render.
+    layer: railway::LayerId,
+
+    /// The directory to write the tiles to.
+    out: PathBuf,
+}
+
+impl RenderTiles {
+    /// Returns all tiles with features.
+    ///
+    /// Only tiles within the bounds of the loaded regions are considered.
+    /// Size, background, and format of the tiles are taken from the server
+    /// config so the tiles look the same as those served.
+    ///
+    /// Returns an error message if the tiles can’t be determined.
+    fn tiles(
+        &self, map: &railway::Map, config: &ServerConfig,
+    ) -> Result<Vec<tile::TileId>, String> {
+        let mut res = Vec::new();
+        for region in map.regions() {
+            let [west, south, east, north] = match region.bounds {
+                Some(bounds) => bounds,
+                None => continue,
+            };
+            for zoom in self.zoom.0..=self.zoom.1 {
+                let nw = self.tile(zoom, west, north, config)?;
+                let se = self.tile(zoom, east, south, config)?;
+                for x in nw.x..=se.x {
+                    for y in nw.y..=se.y {
+                        let tile = tile::TileId { x, y, ..nw };
+                        let features = map.has_features(tile).map_err(|_| {
+                            format!("tile {} is not supported", tile)
+                        })?;
+                        if features {
+                            res.push(tile)
+                        }
+                    }
+                }
+            }
+        }
+
+        // Regions may overlap.
+        res.sort_by_key(|tile| (tile.zoom, tile.x, tile.y));
+        res.dedup();
+        Ok(res)
+    }
+
+    /// Returns the tile containing a point given in degrees.
+    fn tile(
+        &self, zoom: u8, lon: f64, lat: f64, config: &ServerConfig,
+    ) -> Result<tile::TileId, String> {
+        // Points right at the edge of the world belong to no tile.
+        let point = tile::storage_point(lon, lat);
+        let point = kurbo::Point::new(
+            point.x.clamp(0., 1. - f64::EPSILON),
+            point.y.clamp(0., 1. - f64::EPSILON),
+        );
+        let mut res = tile::TileId::containing(
+            tile::LayerId::Railway(self.layer), zoom, point, false,
+            config.tile_size
+        ).map_err(|_| format!("zoom level {} is not supported", zoom))?;
+        res.background = config.background;
+        res.format = res.format.for_background(
+            config.background, config.jpeg_quality
+        );
+        Ok(res)
+    }
+
+    /// Renders a tile and writes it to its file.
+    fn write_tile(
+        &self, tile: tile::TileId, map: &railway::Map,
+        webp: tile::WebpQuality,
+    ) -> Result<(), io::Error> {
+        let dir = self.out.join(
+            tile.zoom.to_string()
+        ).join(tile.x.to_string());
+        fs::create_dir_all(&dir)?;
+        fs::write(
+            dir.join(format!("{}.{}", tile.y, tile.format.extension())),
+            tile.render(map, webp)
+        )
     }
 }
 
+
+//------------ Logger --------------------------------------------------------
+
+/// A logger writing all messages to stderr.
+struct Logger;
+
+static LOGGER: Logger = Logger;
+
+impl Logger {
+    /// Installs the logger.
+    ///
+    /// Messages with level info and up are logged by default. Each
+    /// `verbose` adds a level, each `quiet` removes one.
+    fn init(verbose: u8, quiet: u8) {
+        let level = match i16::from(verbose) - i16::from(quiet) {
+            i16::MIN..=-3 => LevelFilter::Off,
+            -2 => LevelFilter::Error,
+            -1 => LevelFilter::Warn,
+            0 => LevelFilter::Info,
+            1 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        };
+        if log::set_logger(&LOGGER).is_ok() {
+            log::set_max_level(level);
+        }
+    }
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{}] {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) { }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Parses the value of the `--bbox` option.
+///
+/// Returns the area in storage coordinates.
+fn parse_bbox(s: &str) -> Result<kurbo::Rect, String> {
+    let values = s.split(',').map(|item| {
+        f64::from_str(item.trim()).map_err(|err| {
+            format!("invalid coordinate '{}': {}", item, err)
+        })
+    }).collect::<Result<Vec<_>, _>>()?;
+    let (west, south, east, north) = match *values.as_slice() {
+        [west, south, east, north] => (west, south, east, north),
+        _ => return Err("expected west,south,east,north".into())
+    };
+    if west >= east || south >= north {
+        return Err(
+            "west must be less than east and south less than north".into()
+        )
+    }
+    let nw = tile::storage_point(west, north);
+    let se = tile::storage_point(east, south);
+    Ok(kurbo::Rect::new(nw.x, nw.y, se.x, se.y))
+}
+
+/// Parses the value of the `--layer` option of `render-tiles`.
+///
+/// Only railway layers can be rendered into a directory.
+fn parse_layer(s: &str) -> Result<railway::LayerId, String> {
+    railway::LayerId::from_str(s).map_err(|_| {
+        format!("unknown railway layer '{}'", s)
+    })
+}
+
+/// Parses the value of the `--zoom` option of `render-tiles`.
+///
+/// This is either a single zoom level or two levels separated by `..`.
+/// Both ends are included.
+fn parse_zoom_range(s: &str) -> Result<(u8, u8), String> {
+    let (start, end) = s.split_once("..").unwrap_or((s, s));
+    let parse = |item: &str| {
+        u8::from_str(item.trim()).map_err(|err| {
+            format!("invalid zoom level '{}': {}", item, err)
+        })
+    };
+    let (start, end) = (parse(start)?, parse(end)?);
+    if start > end {
+        return Err("the first zoom level must not be larger".into())
+    }
+    Ok((start, end))
+}
+
+/// Returns whether a file name matches any of the ignore patterns.
+fn is_ignored(name: &str, patterns: &[String]) -> bool {
+    let name: Vec<_> = name.chars().collect();
+    patterns.iter().any(|pattern| {
+        glob_match(&pattern.chars().collect::<Vec<_>>(), &name)
+    })
+}
+
+/// Matches a name against a glob pattern.
+///
+/// In the pattern, `*` matches any number of characters and `?` matches
+/// exactly one character. Everything else matches itself. An empty pattern
+/// only matches an empty name.
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    // Where to continue if the last star needs to match more.
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+                continue
+            }
+            Some(&ch) if ch == '?' || ch == name[n] => {
+                p += 1;
+                n += 1;
+                continue
+            }
+            _ => { }
+        }
+        match star {
+            Some((star_p, star_n)) => {
+                star = Some((star_p, star_n + 1));
+                p = star_p + 1;
+                n = star_n + 1;
+            }
+            None => return false
+        }
+    }
+    pattern[p..].iter().all(|&ch| ch == '*')
+}
+
 #[tokio::main]
 async fn main() {
-    let config = match Config::get() {
+    let mut config = match Config::get() {
         Ok(config) => config,
-        Err(_) =>  return,
+        Err(_) =>  process::exit(1),
     };
 
+    if config.check {
+        if !config.check() {
+            process::exit(1)
+        }
+        return
+    }
+    if config.dump {
+        if !config.dump() {
+            process::exit(1)
+        }
+        return
+    }
+    if let Some(args) = config.render_tiles.take() {
+        if !config.render_tiles(args) {
+            process::exit(1)
+        }
+        return
+    }
+
     config.run().await
 }
+
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ignored(name: &str, patterns: &[&str]) -> bool {
+        let patterns: Vec<_> = patterns.iter().map(|item| {
+            String::from(*item)
+        }).collect();
+        is_ignored(name, &patterns)
+    }
+
+    #[test]
+    fn hidden_files() {
+        assert!(ignored(".map.swp", &[".*"]));
+        assert!(ignored(".", &[".*"]));
+        assert!(!ignored("map.rail", &[".*"]));
+        assert!(!ignored("map.", &[".*"]));
+    }
+
+    #[test]
+    fn editor_files() {
+        let patterns = [".*", "*~", "*.swp", "4913"];
+        assert!(ignored("map.rail~", &patterns));
+        assert!(ignored("map.rail.swp", &patterns));
+        assert!(ignored("4913", &patterns));
+        assert!(!ignored("49130", &patterns));
+        assert!(!ignored("map.rail", &patterns));
+        assert!(!ignored("map.swpx", &patterns));
+        assert!(!ignored("~map.rail", &patterns));
+    }
+
+    #[test]
+    fn empty_pattern() {
+        assert!(ignored("", &[""]));
+        assert!(!ignored("map.rail", &[""]));
+        assert!(!ignored("map.rail", &[]));
+    }
+
+    #[test]
+    fn question_mark() {
+        assert!(ignored("a.rail", &["?.rail"]));
+        assert!(!ignored("ab.rail", &["?.rail"]));
+        assert!(!ignored(".rail", &["?.rail"]));
+    }
+
+    #[test]
+    fn non_ascii() {
+        assert!(ignored("ä.rail", &["?.rail"]));
+        assert!(ignored("Görlitz.rail", &["G?rlitz.*"]));
+        assert!(!ignored("Görlitz.rail", &["G??rlitz.*"]));
+        assert!(ignored("Köln~", &["*~"]));
+        assert!(ignored("Straße", &["*ß?"]));
+    }
+}
@@ -7,7 +7,10 @@
 //! help with defining the railway class.
 #![allow(dead_code)]
 
+use std::ops::RangeInclusive;
+use std::str::FromStr;
 use femtomap::import::eval::{EvalErrors, Failed, SymbolSet};
+use femtomap::render::Color;
 use super::import::eval::{Expression, Scope, ScopeExt};
 
 
@@ -26,6 +29,29 @@ pub struct Railway {
     gauge: Option<Gauge>,
     double: Option<bool>,
     station: Option<bool>,
+    opened: Option<u16>,
+    closed: Option<u16>,
+
+    /// A color overriding the style’s color rules.
+    color: Option<Color>,
+
+    /// The layer overriding the layer given by the render params.
+    layer: Option<i16>,
+
+    /// The smallest zoom level the feature is shown at.
+    min_zoom: Option<u8>,
+
+    /// The largest zoom level the feature is shown at.
+    max_zoom: Option<u8>,
+
+    /// The casing skip factor in percent overriding the configured one.
+    casing_skip: Option<u16>,
+
+    /// The code of the operator of the feature.
+    operator: Option<u8>,
+
+    /// The order of the feature’s shapes within a render stage.
+    z_bias: Option<i16>,
 }
 
 impl Railway {
@@ -109,8 +135,91 @@ impl Railway {
         else if symbols.take("nostation") {
             self.station = Some(false)
         }
+
+        if let Some(year) = Self::number_from_symbols(
+            symbols, "opened", Self::FIRST_YEAR..=Self::LAST_YEAR
+        ) {
+            self.opened = Some(year)
+        }
+        if let Some(year) = Self::number_from_symbols(
+            symbols, "closed", Self::FIRST_YEAR..=Self::LAST_YEAR
+        ) {
+            self.closed = Some(year)
+        }
+        if let Some(layer) = Self::number_from_symbols(
+            symbols, "layer", 0..=Self::MAX_LAYER
+        ) {
+            self.layer = Some(layer)
+        }
+        if let Some(zoom) = Self::number_from_symbols(
+            symbols, "minzoom", 0..=Self::MAX_ZOOM
+        ) {
+            self.min_zoom = Some(zoom)
+        }
+        if let Some(zoom) = Self::number_from_symbols(
+            symbols, "maxzoom", 0..=Self::MAX_ZOOM
+        ) {
+            self.max_zoom = Some(zoom)
+        }
+        if let Some(skip) = Self::number_from_symbols(
+            symbols, "casingskip", 0..=Self::MAX_CASING_SKIP
+        ) {
+            self.casing_skip = Some(skip)
+        }
+        if let Some(operator) = Self::number_from_symbols(
+            symbols, "operator", 0..=Self::MAX_OPERATOR
+        ) {
+            self.operator = Some(operator)
+        }
+        if let Some(z_bias) = Self::number_from_symbols(
+            symbols, "zbias", 0..=Self::MAX_Z_BIAS
+        ) {
+            self.z_bias = Some(z_bias)
+        }
     }
 
+    /// Takes a symbol of a prefix and a number, e.g., `:opened1873`.
+    ///
+    /// The symbols are scanned once for the prefix. Only numbers in `range`
+    /// written without a sign or leading zeros are accepted. Other symbols
+    /// with the prefix are left in place and reported as unexpected later.
+    pub fn number_from_symbols<T: FromStr + PartialOrd>(
+        symbols: &mut SymbolSet, prefix: &str, range: RangeInclusive<T>
+    ) -> Option<T> {
+        let (symbol, value) = symbols.iter().find_map(|symbol| {
+            let symbol: &str = symbol.as_ref();
+            let digits = symbol.strip_prefix(prefix)?;
+            if digits.is_empty()
+                || !digits.bytes().all(|ch| ch.is_ascii_digit())
+                || (digits.len() > 1 && digits.starts_with('0'))
+            {
+                return None
+            }
+            let value = T::from_str(digits).ok()?;
+            range.contains(&value).then(|| (String::from(symbol), value))
+        })?;
+        symbols.take(&symbol);
+        Some(value)
+    }
+
+    const FIRST_YEAR: u16 = 1800;
+    const LAST_YEAR: u16 = 2100;
+
+    /// The largest layer that can be given via a `:layerN` symbol.
+    const MAX_LAYER: i16 = 9;
+
+    /// The largest zoom level that can be given via zoom symbols.
+    const MAX_ZOOM: u8 = 19;
+
+    /// The largest percentage that can be given via `:casingskipN`.
+    const MAX_CASING_SKIP: u16 = 300;
+
+    /// The largest operator code that can be given via `:operatorN`.
+    pub const MAX_OPERATOR: u8 = 99;
+
+    /// The largest z-bias that can be given via `:zbiasN`.
+    const MAX_Z_BIAS: i16 = 9;
+
     pub fn update(&mut self, class: &Self) {
         if self.category.is_none() {
             self.category = class.category
@@ -145,12 +254,63 @@ impl Railway {
         if self.station.is_none() {
             self.station = class.station
         }
+        if self.opened.is_none() {
+            self.opened = class.opened
+        }
+        if self.closed.is_none() {
+            self.closed = class.closed
+        }
+        if self.color.is_none() {
+            self.color = class.color
+        }
+        if self.layer.is_none() {
+            self.layer = class.layer
+        }
+        if self.min_zoom.is_none() {
+            self.min_zoom = class.min_zoom
+        }
+        if self.max_zoom.is_none() {
+            self.max_zoom = class.max_zoom
+        }
+        if self.casing_skip.is_none() {
+            self.casing_skip = class.casing_skip
+        }
+        if self.operator.is_none() {
+            self.operator = class.operator
+        }
+        if self.z_bias.is_none() {
+            self.z_bias = class.z_bias
+        }
+    }
+
+    /// Returns the year the feature was opened if known.
+    pub fn opened(&self) -> Option<u16> {
+        self.opened
+    }
+
+    /// Returns the year the feature was closed if known.
+    pub fn closed(&self) -> Option<u16> {
+        self.closed
+    }
+
+    /// Returns whether the feature existed in the given year.
+    ///
+    /// A feature exists from the year it was opened up to but not including
+    /// the year it was closed. If either is unknown, the feature is assumed
+    /// to exist on that side.
+    pub fn exists_in(&self, year: u16) -> bool {
+        self.opened.map(|opened| opened <= year).unwrap_or(true)
+            && self.closed.map(|closed| year < closed).unwrap_or(true)
     }
 
     pub fn category(&self) -> Category {
         self.category.unwrap_or_default()
     }
 
+    pub fn set_category(&mut self, category: Category) {
+        self.category = Some(category);
+    }
+
     pub fn status(&self) -> Status {
         self.status.unwrap_or_default()
     }
@@ -159,6 +319,61 @@ impl Railway {
         self.status = Some(status);
     }
 
+    /// Returns the color override if there is one.
+    ///
+    /// If present, it is used instead of the color determined by the style.
+    pub fn color(&self) -> Option<Color> {
+        self.color
+    }
+
+    pub fn set_color(&mut self, color: Color) {
+        self.color = Some(color);
+    }
+
+    /// Returns the layer override if there is one.
+    ///
+    /// If present, it is used instead of the layer given via the `layer`
+    /// render param.
+    pub fn layer(&self) -> Option<i16> {
+        self.layer
+    }
+
+    /// Returns the zoom levels the feature is limited to.
+    ///
+    /// Either end is `None` if the feature isn’t limited on that side. The
+    /// zoom levels are those of tiles of the default size. Tiles of other
+    /// sizes use the zoom level they are rendered like, e.g., a tile twice
+    /// the size counts as the next zoom level.
+    pub fn zoom_range(&self) -> (Option<u8>, Option<u8>) {
+        (self.min_zoom, self.max_zoom)
+    }
+
+    /// Returns the code of the operator if known.
+    pub fn operator(&self) -> Option<u8> {
+        self.operator
+    }
+
+    pub fn set_operator(&mut self, operator: u8) {
+        self.operator = Some(operator);
+    }
+
+    /// Returns the z-bias override if there is one.
+    ///
+    /// If present, the shapes of the feature use it as their
+    /// [z-bias][crate::railway::feature::Shape::z_bias], i.e., they are
+    /// rendered after shapes with a lower bias in every stage.
+    pub fn z_bias(&self) -> Option<i16> {
+        self.z_bias
+    }
+
+    /// Returns the casing skip factor override if there is one.
+    ///
+    /// If present, it is used instead of the configured factor applied to
+    /// the class skip when determining the width of a track casing.
+    pub fn casing_skip(&self) -> Option<f64> {
+        self.casing_skip.map(|skip| f64::from(skip) / 100.)
+    }
+
     pub fn is_open(&self) -> bool {
         matches!(self.status(), Status::Open)
     }
@@ -175,6 +390,10 @@ impl Railway {
         self.cat
     }
 
+    pub fn set_cat(&mut self, cat: ElectricCat) {
+        self.cat = Some(cat);
+    }
+
     pub fn has_active_cat(&self) -> bool {
         if let Some(cat) = self.cat {
             matches!(cat.status, ElectricStatus::Open)
@@ -224,6 +443,10 @@ impl Railway {
         self.pax.unwrap_or_default()
     }
 
+    pub fn set_pax(&mut self, pax: Pax) {
+        self.pax = Some(pax);
+    }
+
     pub fn opt_pax(&self) -> Option<Pax> {
         self.pax
     }
@@ -249,7 +472,7 @@ impl Railway {
 //------------ Category ------------------------------------------------------
 
 /// The category of railway this feature is for.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum Category {
     /// First-class public railway.
     First,
@@ -271,6 +494,12 @@ pub enum Category {
 }
 
 impl Category {
+    /// All categories from the most to the least important.
+    pub const ALL: [Category; 6] = [
+        Category::First, Category::Second, Category::Third,
+        Category::Tram, Category::Private, Category::Siding,
+    ];
+
     fn from_symbols(symbols: &mut SymbolSet) -> Option<Self> {
         if symbols.take("first") {
             Some(Category::First)
@@ -329,7 +558,7 @@ impl Default for Category {
 ///
 /// The variants are ordered so that features with these statuses are drawn
 /// in a  sensible order way atop each other.
-#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum Status {
     /// The feature has been removed a long time ago.
     Gone = 0,
@@ -356,6 +585,12 @@ pub enum Status {
 }
 
 impl Status {
+    /// All statuses from open to long gone.
+    pub const ALL: [Status; 7] = [
+        Status::Open, Status::Planned, Status::Suspended, Status::Closed,
+        Status::Removed, Status::Explanned, Status::Gone,
+    ];
+
     fn from_symbols(symbols: &mut SymbolSet) -> Option<Self> {
         let res = if symbols.take("exproject") {
             Some(Status::Explanned)
@@ -461,6 +696,10 @@ impl Surface {
         }
     }
 
+    pub fn is_bridge(self) -> bool {
+        matches!(self, Surface::Bridge)
+    }
+
     pub fn is_tunnel(self) -> bool {
         matches!(self, Surface::Tunnel)
     }
@@ -720,7 +959,7 @@ impl Default for Speed {
 
 //------------ Pax -----------------------------------------------------------
 
-#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum Pax {
     /// There is no passenger service.
     None = 0,
@@ -736,6 +975,11 @@ pub enum Pax {
 }
 
 impl Pax {
+    /// All levels of passenger service from full to none.
+    pub const ALL: [Pax; 4] = [
+        Pax::Full, Pax::Seasonal, Pax::Heritage, Pax::None,
+    ];
+
     fn from_symbols(symbols: &mut SymbolSet) -> Option<Self> {
         if symbols.take("nopax") {
             Some(Pax::None)
@@ -938,3 +1182,157 @@ impl GaugeGroup {
     }
 }
 
+
+//------------ ClassFilter ---------------------------------------------------
+
+/// A filter limiting the features shown to some railway classes.
+///
+/// The filter is given as a comma separated list of class symbols with or
+/// without the leading colon, e.g., `narrow,open`. Symbols for the same
+/// property are alternatives while the properties all have to match. The
+/// supported symbols are those for category, status, passenger service,
+/// overhead and third rail electrification, main gauge, and the `narrow`
+/// and `broad` gauge groups.
+///
+/// Features without a class are shown or hidden depending on a separate
+/// flag. By default, they are shown.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ClassFilter {
+    /// The bit mask of categories or 0 for all.
+    categories: u8,
+
+    /// The bit mask of statuses or 0 for all.
+    statuses: u8,
+
+    /// The bit mask of passenger service levels or 0 for all.
+    pax: u8,
+
+    /// The bit mask of indexes into `Gauge::MAIN_GAUGES` or 0 for all.
+    gauges: u16,
+
+    /// The bit mask of gauge groups or 0 for all.
+    gauge_groups: u8,
+
+    /// Whether there must or must not be open overhead electrification.
+    cat: Option<bool>,
+
+    /// Whether there must or must not be an open power rail.
+    rail: Option<bool>,
+
+    /// Are features without a class shown?
+    unclassed: bool,
+}
+
+impl ClassFilter {
+    /// Returns whether features without a class are shown.
+    pub fn unclassed(&self) -> bool {
+        self.unclassed
+    }
+
+    /// Sets whether features without a class are shown.
+    pub fn set_unclassed(&mut self, unclassed: bool) {
+        self.unclassed = unclassed
+    }
+
+    /// Returns whether a feature with the given class is shown.
+    pub fn matches(&self, class: Option<&Railway>) -> bool {
+        let class = match class {
+            Some(class) => class,
+            None => return self.unclassed,
+        };
+        Self::test(self.categories, class.category() as u8)
+            && Self::test(self.statuses, class.status() as u8)
+            && Self::test(self.pax, class.pax() as u8)
+            && Self::test(self.gauge_groups, class.gauge_group() as u8)
+            && self.matches_gauge(class.gauge().main())
+            && self.cat.map(|cat| {
+                cat == class.has_active_cat()
+            }).unwrap_or(true)
+            && self.rail.map(|rail| {
+                rail == class.has_active_rail()
+            }).unwrap_or(true)
+    }
+
+    fn test(mask: u8, value: u8) -> bool {
+        mask == 0 || mask & (1 << value) != 0
+    }
+
+    fn matches_gauge(&self, main: u16) -> bool {
+        self.gauges == 0 || Gauge::MAIN_GAUGES.iter().enumerate().any(
+            |(idx, &(_, gauge))| {
+                self.gauges & (1 << idx) != 0 && gauge == main
+            }
+        )
+    }
+
+    /// Adds a single class symbol to the filter.
+    fn add(mut self, symbol: &str) -> Result<Self, ClassFilterError> {
+        match symbol {
+            "first" => self.categories |= 1 << Category::First as u8,
+            "second" => self.categories |= 1 << Category::Second as u8,
+            "third" => self.categories |= 1 << Category::Third as u8,
+            "tram" => self.categories |= 1 << Category::Tram as u8,
+            "private" => self.categories |= 1 << Category::Private as u8,
+            "side" => self.categories |= 1 << Category::Siding as u8,
+            "open" => self.statuses |= 1 << Status::Open as u8,
+            "project" => self.statuses |= 1 << Status::Planned as u8,
+            "exproject" => self.statuses |= 1 << Status::Explanned as u8,
+            "suspended" => self.statuses |= 1 << Status::Suspended as u8,
+            "closed" => self.statuses |= 1 << Status::Closed as u8,
+            "removed" => self.statuses |= 1 << Status::Removed as u8,
+            "gone" => self.statuses |= 1 << Status::Gone as u8,
+            "pax" => self.pax |= 1 << Pax::Full as u8,
+            "seasonal" => self.pax |= 1 << Pax::Seasonal as u8,
+            "heritage" => self.pax |= 1 << Pax::Heritage as u8,
+            "nopax" => self.pax |= 1 << Pax::None as u8,
+            "narrow" => {
+                self.gauge_groups |= 1 << GaugeGroup::Narrow as u8
+            }
+            "broad" => self.gauge_groups |= 1 << GaugeGroup::Broad as u8,
+            "cat" => self.cat = Some(true),
+            "nocat" => self.cat = Some(false),
+            "rail" => self.rail = Some(true),
+            "norail" => self.rail = Some(false),
+            _ => {
+                let idx = Gauge::MAIN_GAUGES.iter().position(|item| {
+                    item.0 == symbol
+                }).ok_or(ClassFilterError)?;
+                self.gauges |= 1 << idx;
+            }
+        }
+        Ok(self)
+    }
+}
+
+impl Default for ClassFilter {
+    fn default() -> Self {
+        ClassFilter {
+            categories: 0,
+            statuses: 0,
+            pax: 0,
+            gauges: 0,
+            gauge_groups: 0,
+            cat: None,
+            rail: None,
+            unclassed: true,
+        }
+    }
+}
+
+impl FromStr for ClassFilter {
+    type Err = ClassFilterError;
+
+    /// Parses a comma separated list of class symbols.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',').try_fold(Self::default(), |filter, item| {
+            filter.add(item.strip_prefix(':').unwrap_or(item))
+        })
+    }
+}
+
+
+//------------ ClassFilterError ----------------------------------------------
+
+/// An unsupported class symbol was encountered in a class filter.
+#[derive(Clone, Copy, Debug)]
+pub struct ClassFilterError;
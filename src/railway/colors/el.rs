@@ -182,6 +182,46 @@ impl Colors {
     }
 }
 
+impl Colors {
+    /// Returns a palette that remains distinguishable with deuteranopia.
+    ///
+    /// The colors are largely taken from the Okabe-Ito palette.
+    pub fn accessible() -> Self {
+        Self {
+            none: Color::hex(ACC_NONE).unwrap(),
+            ac_high: Color::hex(ACC_AC_HIGH).unwrap(),
+            ac_low: Color::hex(ACC_AC_LOW).unwrap(),
+            dc_high: Color::hex(ACC_DC_HIGH).unwrap(),
+            dc_low: Color::hex(ACC_DC_LOW).unwrap(),
+            rail: Color::hex(ACC_RAIL).unwrap(),
+            four: Color::hex(ACC_FOUR).unwrap(),
+            tram: Color::hex(ACC_TRAM).unwrap(),
+            .. Default::default()
+        }
+    }
+
+    /// Returns a palette for use on a dark background.
+    pub fn dark() -> Self {
+        Self {
+            none: Color::hex(DARK_NONE).unwrap(),
+            ac_high: Color::hex(DARK_AC_HIGH).unwrap(),
+            ac_low: Color::hex(DARK_AC_LOW).unwrap(),
+            dc_high: Color::hex(DARK_DC_HIGH).unwrap(),
+            dc_low: Color::hex(DARK_DC_LOW).unwrap(),
+            rail: Color::hex(DARK_RAIL).unwrap(),
+            four: Color::hex(DARK_FOUR).unwrap(),
+            tram: Color::hex(DARK_TRAM).unwrap(),
+            closed:  Color::grey(0.450),
+            removed: Color::grey(0.350),
+            gone:    Color::grey(0.250),
+            closed_label:  Color::grey(0.650),
+            removed_label: Color::grey(0.600),
+            gone_label:    Color::grey(0.550),
+            toxic:   Color::rgb(0.824, 0.824, 0.0),
+        }
+    }
+}
+
 impl Default for Colors {
     fn default() -> Self {
         Self {
@@ -216,3 +256,105 @@ const RAIL: &str = "007e40ff";
 const FOUR: &str = "53633bff";
 const TRAM: &str = "005387ff";
 
+const DARK_NONE: &str = "c8a27cff";
+const DARK_AC_HIGH: &str  = "b57edcff";
+const DARK_AC_LOW: &str = "d36fa8ff";
+const DARK_DC_HIGH: &str = "e5605cff";
+const DARK_DC_LOW: &str = "f0954aff";
+const DARK_RAIL: &str = "3fbf7fff";
+const DARK_FOUR: &str = "a3b86cff";
+const DARK_TRAM: &str = "4fa3e0ff";
+
+const ACC_NONE: &str = "000000ff";
+const ACC_AC_HIGH: &str  = "0072b2ff";
+const ACC_AC_LOW: &str = "56b4e9ff";
+const ACC_DC_HIGH: &str = "d55e00ff";
+const ACC_DC_LOW: &str = "f0c000ff";
+const ACC_RAIL: &str = "117733ff";
+const ACC_FOUR: &str = "44aa99ff";
+const ACC_TRAM: &str = "882255ff";
+
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Converts a hex color into linear RGB.
+    fn linear(hex: &str) -> [f64; 3] {
+        let channel = |idx: usize| {
+            let value = u8::from_str_radix(
+                &hex[idx..idx + 2], 16
+            ).unwrap() as f64 / 255.;
+            if value <= 0.04045 {
+                value / 12.92
+            }
+            else {
+                ((value + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        [channel(0), channel(2), channel(4)]
+    }
+
+    /// Simulates deuteranopia on a linear RGB color.
+    ///
+    /// This uses the matrix by Machado et al. for full severity.
+    fn deuteranopia([r, g, b]: [f64; 3]) -> [f64; 3] {
+        [
+            (0.367322 * r + 0.860646 * g - 0.227968 * b).clamp(0., 1.),
+            (0.280085 * r + 0.672501 * g + 0.047413 * b).clamp(0., 1.),
+            (-0.011820 * r + 0.042940 * g + 0.968881 * b).clamp(0., 1.),
+        ]
+    }
+
+    /// Converts a linear RGB color into CIE L*a*b*.
+    fn lab([r, g, b]: [f64; 3]) -> [f64; 3] {
+        let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+        let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+        let f = |t: f64| {
+            if t > 0.008856 { t.cbrt() } else { 7.787 * t + 16. / 116. }
+        };
+        [
+            116. * f(y) - 16.,
+            500. * (f(x) - f(y)),
+            200. * (f(y) - f(z)),
+        ]
+    }
+
+    fn distance(left: [f64; 3], right: [f64; 3]) -> f64 {
+        let left = lab(left);
+        let right = lab(right);
+        (
+            (left[0] - right[0]).powi(2)
+            + (left[1] - right[1]).powi(2)
+            + (left[2] - right[2]).powi(2)
+        ).sqrt()
+    }
+
+    #[test]
+    fn accessible_distance() {
+        const MIN_DISTANCE: f64 = 20.;
+
+        let palette = [
+            ACC_NONE, ACC_AC_HIGH, ACC_AC_LOW, ACC_DC_HIGH, ACC_DC_LOW,
+            ACC_RAIL, ACC_FOUR, ACC_TRAM,
+        ];
+        for (idx, left) in palette.iter().enumerate() {
+            for right in &palette[idx + 1..] {
+                let (l, r) = (linear(left), linear(right));
+                assert!(
+                    distance(l, r) > MIN_DISTANCE,
+                    "{} and {} too close", left, right
+                );
+                assert!(
+                    distance(deuteranopia(l), deuteranopia(r))
+                        > MIN_DISTANCE,
+                    "{} and {} too close with deuteranopia", left, right
+                );
+            }
+        }
+    }
+}
+
@@ -1,11 +1,13 @@
 //! Coloring rules.
 
+use std::collections::HashMap;
 use femtomap::render::Color;
 use super::class;
 
 //------------ Colors for individual styles ----------------------------------
 
 pub mod el;
+pub mod operator;
 pub mod pax;
 
 
@@ -15,6 +17,8 @@ pub mod pax;
 pub enum Colors {
     El(el::Colors),
     Pax(pax::Colors),
+    Dark(el::Colors),
+    Operator(operator::Colors),
 }
 
 impl Colors {
@@ -26,10 +30,38 @@ impl Colors {
         Colors::Pax(Default::default())
     }
 
+    /// Returns the electrification colors for a dark background.
+    pub fn dark() -> Self {
+        Colors::Dark(el::Colors::dark())
+    }
+
+    /// Returns the operator colors for the given operator table.
+    pub fn operator(operators: &HashMap<String, Color>) -> Self {
+        Colors::Operator(operator::Colors::from_config(operators))
+    }
+
+    /// Returns the electrification colors safe for deuteranopia.
+    pub fn accessible() -> Self {
+        Colors::El(el::Colors::accessible())
+    }
+
+    /// Returns the colors for the given reference year.
+    ///
+    /// Only the passenger colors use the year to tint recently opened
+    /// lines.
+    pub fn with_year(self, year: Option<u16>) -> Self {
+        match self {
+            Colors::Pax(colors) => Colors::Pax(colors.with_year(year)),
+            _ => self,
+        }
+    }
+
     pub fn track_color(&self, class: &class::Railway) -> Color {
         match self {
             Colors::El(colors) => colors.track_color(class),
             Colors::Pax(colors) => colors.track_color(class),
+            Colors::Dark(colors) => colors.track_color(class),
+            Colors::Operator(colors) => colors.track_color(class),
         }
     }
 
@@ -37,6 +69,8 @@ impl Colors {
         match self {
             Colors::El(colors) => colors.cat_color(class),
             Colors::Pax(colors) => colors.cat_color(class),
+            Colors::Dark(colors) => colors.cat_color(class),
+            Colors::Operator(colors) => colors.cat_color(class),
         }
     }
 
@@ -44,6 +78,8 @@ impl Colors {
         match self {
             Colors::El(colors) => colors.rail_color(class),
             Colors::Pax(colors) => colors.rail_color(class),
+            Colors::Dark(colors) => colors.rail_color(class),
+            Colors::Operator(colors) => colors.rail_color(class),
         }
     }
 
@@ -51,6 +87,8 @@ impl Colors {
         match self {
             Colors::El(colors) => colors.label_color(class),
             Colors::Pax(colors) => colors.label_color(class),
+            Colors::Dark(colors) => colors.label_color(class),
+            Colors::Operator(colors) => colors.label_color(class),
         }
     }
 
@@ -58,11 +96,49 @@ impl Colors {
         match self {
             Colors::El(colors) => colors.primary_marker_color(class),
             Colors::Pax(colors) => colors.primary_marker_color(class),
+            Colors::Dark(colors) => colors.primary_marker_color(class),
+            Colors::Operator(colors) => colors.primary_marker_color(class),
         }
     }
 
+    /// Returns the color for casings around markers and track.
     pub fn casing_color(&self) -> Color {
-        Color::rgba(1., 1., 1., 0.7)
+        match self {
+            Colors::Dark(_) => Color::rgba(0.1, 0.1, 0.1, 0.7),
+            _ => Color::rgba(1., 1., 1., 0.7),
+        }
+    }
+
+    /// Returns the color for the casing of track contours.
+    ///
+    /// This is also used to knock out the base of dashed track. It is
+    /// slightly more opaque than the general casing color.
+    pub fn track_casing_color(&self) -> Color {
+        match self {
+            Colors::Dark(_) => Color::rgba(0.1, 0.1, 0.1, 0.8),
+            _ => Color::rgba(1., 1., 1., 0.8),
+        }
+    }
+
+    /// Returns the color of the map background.
+    ///
+    /// This is used where something needs to be knocked out, such as the
+    /// halo around labels.
+    pub fn background_color(&self) -> Color {
+        match self {
+            Colors::Dark(_) => Color::grey(0.1),
+            _ => Color::WHITE,
+        }
+    }
+
+    /// Returns the color for platforms.
+    ///
+    /// This is a neutral color that doesn’t compete with the tracks.
+    pub fn platform_color(&self) -> Color {
+        match self {
+            Colors::Dark(_) => Color::grey(0.3),
+            _ => Color::grey(0.8),
+        }
     }
 }
 
@@ -73,6 +149,19 @@ impl Colors {
 pub struct ColorSet {
     pub el: Colors,
     pub pax: Colors,
+    pub accessible: Colors,
+    pub dark: Colors,
+    pub operator: Colors,
+}
+
+impl ColorSet {
+    /// Creates the color set using the given operator table.
+    pub fn with_operators(operators: &HashMap<String, Color>) -> Self {
+        ColorSet {
+            operator: Colors::operator(operators),
+            ..Default::default()
+        }
+    }
 }
 
 impl Default for ColorSet {
@@ -80,6 +169,9 @@ impl Default for ColorSet {
         ColorSet {
             el: Colors::el(),
             pax: Colors::pax(),
+            accessible: Colors::accessible(),
+            dark: Colors::dark(),
+            operator: Colors::operator(&HashMap::new()),
         }
     }
 }
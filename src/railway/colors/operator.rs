@@ -0,0 +1,131 @@
+//! The coloring rules for the operator map.
+
+use std::collections::HashMap;
+use femtomap::render::Color;
+use crate::railway::class;
+
+//------------ Configuration -------------------------------------------------
+
+/// The number of operator codes.
+const OPERATOR_COUNT: usize = class::Railway::MAX_OPERATOR as usize + 1;
+
+
+//------------ Colors --------------------------------------------------------
+
+#[derive(Clone, Copy, Debug)]
+pub struct Colors {
+    /// The colors of the operators by their code.
+    operators: [Option<Color>; OPERATOR_COUNT],
+
+    /// The color for open lines of unknown operators.
+    unknown: Color,
+
+    /// The color for closed lines.
+    closed: Color,
+}
+
+impl Colors {
+    /// Creates the colors from the operator table of the map config.
+    ///
+    /// Keys that aren’t valid operator codes are ignored. They are rejected
+    /// when loading the config already.
+    pub fn from_config(operators: &HashMap<String, Color>) -> Self {
+        let mut res = Self::default();
+        for (code, color) in operators {
+            if let Ok(code) = code.parse::<usize>() {
+                if let Some(item) = res.operators.get_mut(code) {
+                    *item = Some(*color)
+                }
+            }
+        }
+        res
+    }
+
+    /// Returns the color for a piece of track.
+    pub fn track_color(&self, class: &class::Railway) -> Color {
+        if !class.is_open() {
+            self.closed
+        }
+        else {
+            class.operator().and_then(|code| {
+                self.operators[usize::from(code)]
+            }).unwrap_or(self.unknown)
+        }
+    }
+
+    /// Returns the color for cat markings if they should be drawn.
+    pub fn cat_color(&self, class: &class::Railway) -> Option<Color> {
+        class.cat().and_then(|cat| {
+            match cat.status {
+                class::ElectricStatus::None => None,
+                class::ElectricStatus::Open => Some(self.track_color(class)),
+                class::ElectricStatus::Removed => Some(self.closed),
+            }
+        })
+    }
+
+    /// Returns the color for third rail markings if they should be drawn.
+    pub fn rail_color(&self, class: &class::Railway) -> Option<Color> {
+        class.rail().and_then(|rail| {
+            match rail.status {
+                class::ElectricStatus::None => None,
+                class::ElectricStatus::Open => Some(self.track_color(class)),
+                class::ElectricStatus::Removed => Some(self.closed),
+            }
+        })
+    }
+
+    /// Returns the color for a station label.
+    pub fn label_color(&self, class: &class::Railway) -> Color {
+        self.track_color(class)
+    }
+
+    /// Returns the primary color for a marker.
+    pub fn primary_marker_color(&self, class: &class::Railway) -> Color {
+        self.track_color(class)
+    }
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Self {
+            operators: [None; OPERATOR_COUNT],
+            unknown: Color::grey(0.5),
+            closed: Color::grey(0.9),
+        }
+    }
+}
+
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn from_config() {
+        let colors = Colors::from_config(&HashMap::from([
+            (String::from("1"), Color::rgb(0.8, 0., 0.)),
+            (String::from("100"), Color::grey(0.)),
+            (String::from("db"), Color::grey(0.)),
+        ]));
+        assert!(colors.operators[1].is_some());
+        assert_eq!(colors.operators.iter().flatten().count(), 1);
+    }
+
+    #[test]
+    fn unknown_operator() {
+        let colors = Colors::from_config(&HashMap::from([
+            (String::from("1"), Color::rgb(0.8, 0., 0.)),
+        ]));
+        let grey = format!("{:?}", Color::grey(0.5));
+        let mut class = class::Railway::default();
+        class.set_status(class::Status::Open);
+        assert_eq!(format!("{:?}", colors.track_color(&class)), grey);
+        class.set_operator(2);
+        assert_eq!(format!("{:?}", colors.track_color(&class)), grey);
+        class.set_operator(1);
+        assert_ne!(format!("{:?}", colors.track_color(&class)), grey);
+    }
+}
@@ -3,6 +3,15 @@
 use femtomap::render::Color;
 use crate::railway::class;
 
+//------------ Configuration -------------------------------------------------
+
+/// The number of years a line is considered recently opened.
+///
+/// Over this period, the color of a line fades from the recent color to
+/// the regular color.
+const RECENT_YEARS: u16 = 25;
+
+
 //------------ Colors --------------------------------------------------------
 
 #[derive(Clone, Copy, Debug)]
@@ -11,9 +20,25 @@ pub struct Colors {
     //ltd: Color,
     none: Color,
     closed: Color,
+
+    /// The RGB components of the color for lines just opened.
+    recent: [f64; 3],
+
+    /// The RGB components of the color for established lines.
+    ///
+    /// This should be the same as `full`.
+    established: [f64; 3],
+
+    /// The reference year for tinting recently opened lines.
+    year: Option<u16>,
 }
 
 impl Colors {
+    /// Returns the colors with the given reference year.
+    pub fn with_year(self, year: Option<u16>) -> Self {
+        Colors { year, ..self }
+    }
+
     /// Returns the color for a piece of track.
     pub fn track_color(&self, class: &class::Railway) -> Color {
         if !class.is_open() {
@@ -22,11 +47,33 @@ impl Colors {
         else {
             match class.pax() {
                 class::Pax::None => self.none,
-                _ => self.full,
+                _ => self.open_color(class),
             }
         }
     }
 
+    /// Returns the color for an open line with passenger service.
+    ///
+    /// If there is a reference year and the line was opened less than
+    /// `RECENT_YEARS` before it, the color is interpolated between the
+    /// recent and the established color.
+    fn open_color(&self, class: &class::Railway) -> Color {
+        let (year, opened) = match (self.year, class.opened()) {
+            (Some(year), Some(opened)) => (year, opened),
+            _ => return self.full,
+        };
+        let age = match year.checked_sub(opened) {
+            Some(age) if age < RECENT_YEARS => age,
+            _ => return self.full,
+        };
+        let frac = f64::from(age) / f64::from(RECENT_YEARS);
+        let mix = |idx: usize| {
+            let (from, to) = (self.recent[idx], self.established[idx]);
+            from + frac * (to - from)
+        };
+        Color::rgb(mix(0), mix(1), mix(2))
+    }
+
     /// Returns the color for cat markings if they should be drawn.
     pub fn cat_color(&self, class: &class::Railway) -> Option<Color> {
         class.cat().and_then(|cat| {
@@ -67,6 +114,9 @@ impl Default for Colors {
             //ltd: Color::grey(0.3),
             none: Color::grey(0.7),
             closed: Color::grey(0.9),
+            recent: [0.8, 0.2, 0.1],
+            established: [0.1, 0.1, 0.1],
+            year: None,
         }
     }
 }
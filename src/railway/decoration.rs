@@ -0,0 +1,126 @@
+//! Decorations drawn on top of the map.
+//!
+//! Currently, this is a scale bar and north arrow for standalone exports.
+//! They are drawn into one corner of each tile if enabled in the map
+//! configuration.
+
+use femtomap::render::{Canvas, LineWidth};
+use kurbo::{PathEl, Point};
+use crate::config::ScaleBarConfig;
+use crate::tile::{self, TileId};
+use super::class::Railway;
+use super::feature::label;
+use super::style::Style;
+
+
+//------------ Configurable Constants ----------------------------------------
+
+/// The margin between the decorations and the edge in dt.
+const MARGIN: f64 = 2.;
+
+/// The space between the north arrow and the scale bar in dt.
+const GAP: f64 = 2.;
+
+/// The largest length of the scale bar as a share of the canvas size.
+const MAX_LENGTH: f64 = 0.25;
+
+
+//------------ render --------------------------------------------------------
+
+/// Renders the scale bar and north arrow onto a tile.
+///
+/// The length of the scale bar is the largest round distance that fits and
+/// is correct for the latitude of the center of the tile.
+pub fn render(
+    config: &ScaleBarConfig, tile_id: &TileId, style: &Style,
+    canvas: &mut Canvas,
+) {
+    let size = tile_id.canvas_size();
+    let nw = tile_id.nw();
+    let half = 0.5 / tile_id.n();
+    let (_, lat) = tile::lonlat(Point::new(nw.x + half, nw.y + half));
+    let per_unit = config.units.metres() * style.canvas_per_metre(lat);
+    let value = round_distance(MAX_LENGTH * size / per_unit);
+    let length = value * per_unit;
+
+    let dt = style.measures().dt();
+    let sh = style.measures().sh();
+    let arrow_width = 0.6 * sh;
+    let width = arrow_width + GAP * dt + length;
+    let height = 2. * sh;
+    let left = if config.corner.is_left() {
+        MARGIN * dt
+    }
+    else {
+        size - MARGIN * dt - width
+    };
+    let top = if config.corner.is_top() {
+        MARGIN * dt
+    }
+    else {
+        size - MARGIN * dt - height
+    };
+    let color = style.label_color(&Railway::default());
+
+    // The north arrow.
+    canvas.sketch()
+        .apply(color)
+        .apply([
+            PathEl::MoveTo(Point::new(left + 0.5 * arrow_width, top)),
+            PathEl::LineTo(Point::new(left + arrow_width, top + height)),
+            PathEl::LineTo(
+                Point::new(left + 0.5 * arrow_width, top + 0.75 * height)
+            ),
+            PathEl::LineTo(Point::new(left, top + height)),
+            PathEl::ClosePath,
+        ])
+        .fill();
+
+    // The scale bar with its label above.
+    let bar = left + arrow_width + GAP * dt;
+    let bottom = top + height;
+    canvas.sketch()
+        .apply(color)
+        .apply(LineWidth(style.canvas_bp()))
+        .apply([
+            PathEl::MoveTo(Point::new(bar, bottom - 0.5 * sh)),
+            PathEl::LineTo(Point::new(bar, bottom)),
+            PathEl::LineTo(Point::new(bar + length, bottom)),
+            PathEl::LineTo(Point::new(bar + length, bottom - 0.5 * sh)),
+        ])
+        .stroke();
+    label::render_legend(
+        &config.units.label(value), Point::new(bar, top + 0.5 * sh),
+        style, canvas
+    );
+}
+
+/// Returns the largest round distance not larger than `max`.
+///
+/// Round distances are one, two, or five times a power of ten.
+fn round_distance(max: f64) -> f64 {
+    let base = 10f64.powi(max.log10().floor() as i32);
+    for factor in [5., 2.] {
+        if factor * base <= max {
+            return factor * base
+        }
+    }
+    base
+}
+
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_distances() {
+        assert_eq!(round_distance(1.), 1.);
+        assert_eq!(round_distance(4.9), 2.);
+        assert_eq!(round_distance(7.), 5.);
+        assert_eq!(round_distance(19.), 10.);
+        assert_eq!(round_distance(0.3), 0.2);
+    }
+}
@@ -1,23 +1,38 @@
 //! Rendering an area.
 
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
 use femtomap::world;
-use femtomap::path::Trace;
-use femtomap::render::Canvas;
+use femtomap::import::eval::SymbolSet;
+use femtomap::path::{Trace, Transform};
+use femtomap::render::{Canvas, Color, LineCap, LineWidth, Outline};
+use kurbo::{Affine, PathEl, Point};
 use crate::railway::class::Railway;
-use crate::railway::style::Style;
-use super::{AnyShape, Category, Group, Feature};
+use crate::config::MeasuresConfig;
+use crate::railway::style::{self, Style};
+use super::{AnyShape, Category, Group, Feature, TraceExt};
 
 //------------ AreaContour ---------------------------------------------------
 
 /// A contour drawing an area.
 pub struct AreaContour {
     class: Railway,
+    fill: AreaFill,
     trace: Trace,
 }
 
 impl AreaContour {
-    pub fn new(class: Railway, trace: Trace) -> Self {
-        AreaContour { class, trace }
+    pub fn new(class: Railway, fill: AreaFill, trace: Trace) -> Self {
+        AreaContour { class, fill, trace }
+    }
+
+    /// Returns whether a point lies inside the area.
+    ///
+    /// The point is given in storage coordinates. The outline is
+    /// approximated by a polygon which is closed implicitly if necessary.
+    /// The even-odd rule decides for self-intersecting outlines. Outlines
+    /// too short to form a polygon contain no points.
+    pub fn contains(&self, point: Point, style: &Style) -> bool {
+        trace_contains(&self.trace, style.transform() * point, style)
     }
 }
 
@@ -26,52 +41,323 @@ impl Feature for AreaContour {
         self.trace.storage_bounds()
     }
 
+    fn class(&self) -> Option<&Railway> {
+        Some(&self.class)
+    }
+
     fn group(&self) -> Group {
         Group::with_railway(Category::Back, &self.class)
     }
 
+    fn hit(&self, point: Point, _radius: f64, style: &Style) -> bool {
+        self.contains(point, style)
+    }
+
     fn shape(
         &self, style: &Style, _canvas: &Canvas
     ) -> AnyShape {
         let color = style.track_color(&self.class);
         let outline = self.trace.outline(style);
+        let fill = self.fill;
 
-        AnyShape::single_stage(move |_style: &Style, canvas: &mut Canvas| {
-            let mut canvas = canvas.sketch();
-            canvas.apply(&outline);
-            canvas.apply(color);
-            canvas.fill();
+        AnyShape::single_stage(move |style: &Style, canvas: &mut Canvas| {
+            if matches!(fill, AreaFill::Solid) {
+                let mut canvas = canvas.sketch();
+                canvas.apply(&outline);
+                canvas.apply(color);
+                canvas.fill();
+            }
+            else {
+                fill.render_hatch(&outline, color, style, canvas)
+            }
         })
     }
 }
 
 
+//------------ AreaFill ------------------------------------------------------
+
+/// How an area is filled.
+///
+/// The angles of hatch lines are in canvas coordinates, i.e., clockwise
+/// from the x axis, and their spacing is in _dt_.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum AreaFill {
+    /// The area is filled with solid color.
+    #[default]
+    Solid,
+
+    /// The area is filled with parallel lines.
+    Hatch { angle: f64, spacing: f64 },
+
+    /// The area is filled with lines crossing at right angles.
+    Crosshatch { angle: f64, spacing: f64 },
+}
+
+impl AreaFill {
+    /// Takes the fill from the symbols of a class.
+    ///
+    /// Hatching is selected via `:hatch` or `:crosshatch`. Either can be
+    /// followed by an angle in degrees counterclockwise from horizontal,
+    /// e.g., `:hatch30` or `:crosshatch0`. Without an angle, lines run
+    /// from top left to bottom right, i.e., `:hatch` is the same as
+    /// `:hatch135`. The lines are one _dt_ apart unless a different
+    /// spacing is given via `:hatchspacingN` with `N` in _dt_.
+    pub fn from_symbols(symbols: &mut SymbolSet) -> Self {
+        let res = if symbols.take("hatch") {
+            AreaFill::Hatch { angle: FRAC_PI_4, spacing: 1. }
+        }
+        else if let Some(angle) = Self::angle_from_symbols(symbols, "hatch") {
+            AreaFill::Hatch { angle, spacing: 1. }
+        }
+        else if symbols.take("crosshatch") {
+            AreaFill::Crosshatch { angle: FRAC_PI_4, spacing: 1. }
+        }
+        else if let Some(angle) = Self::angle_from_symbols(
+            symbols, "crosshatch"
+        ) {
+            AreaFill::Crosshatch { angle, spacing: 1. }
+        }
+        else {
+            return AreaFill::Solid
+        };
+        match Railway::number_from_symbols(
+            symbols, "hatchspacing", 1..=Self::MAX_SPACING
+        ) {
+            Some(value) => res.with_spacing(f64::from(value)),
+            None => res,
+        }
+    }
+
+    /// The largest spacing in _dt_ that can be given via `:hatchspacingN`.
+    const MAX_SPACING: u8 = 10;
+
+    /// Takes an angle symbol and converts it into canvas radians.
+    fn angle_from_symbols(
+        symbols: &mut SymbolSet, prefix: &str
+    ) -> Option<f64> {
+        Railway::number_from_symbols(
+            symbols, prefix, 0..=179u16
+        ).map(|degrees| -f64::from(degrees).to_radians())
+    }
+
+    /// Returns the fill with the spacing of the hatch lines replaced.
+    fn with_spacing(self, spacing: f64) -> Self {
+        match self {
+            AreaFill::Solid => AreaFill::Solid,
+            AreaFill::Hatch { angle, .. } => {
+                AreaFill::Hatch { angle, spacing }
+            }
+            AreaFill::Crosshatch { angle, .. } => {
+                AreaFill::Crosshatch { angle, spacing }
+            }
+        }
+    }
+
+    /// Renders the hatching of an area.
+    ///
+    /// Rather than relying on clipping, the contour is approximated by a
+    /// polygon and each line is cut to the parts inside of it, so nothing
+    /// leaks outside. The lines are aligned to storage coordinates so they
+    /// continue across tiles.
+    fn render_hatch(
+        self, outline: &Outline, color: Color, style: &Style,
+        canvas: &mut Canvas,
+    ) {
+        let (angles, spacing) = match self {
+            AreaFill::Solid => return,
+            AreaFill::Hatch { angle, spacing } => {
+                (vec![angle], spacing)
+            }
+            AreaFill::Crosshatch { angle, spacing } => {
+                (vec![angle, angle + FRAC_PI_2], spacing)
+            }
+        };
+        let spacing = spacing * style.measures().dt();
+        let points = outline.iter_positions(
+            0.25 * style.measures().dt(), Some(0.)
+        ).map(|(pos, _)| pos).collect::<Vec<_>>();
+        if points.len() < 3 {
+            return
+        }
+
+        let mut sketch = canvas.sketch();
+        sketch.apply(color);
+        sketch.apply(LineWidth(style.measures().guide_width()));
+        sketch.apply(LineCap::Butt);
+
+        for angle in angles {
+            // Rotate everything so that the hatch lines are horizontal.
+            let rotate = Affine::rotate(-angle);
+            let points = points.iter().map(|pos| {
+                rotate * *pos
+            }).collect::<Vec<_>>();
+            let shift = (
+                rotate * style.transform().translation.to_point()
+            ).y;
+            let (min_y, max_y) = points.iter().fold(
+                (f64::INFINITY, f64::NEG_INFINITY),
+                |(min, max), pos| (min.min(pos.y), max.max(pos.y))
+            );
+
+            let mut y = ((min_y - shift) / spacing).ceil() * spacing + shift;
+            while y <= max_y {
+                for (x0, x1) in hatch_line(&points, y) {
+                    sketch.apply([
+                        PathEl::MoveTo(rotate.inverse() * Point::new(x0, y)),
+                        PathEl::LineTo(rotate.inverse() * Point::new(x1, y)),
+                    ]);
+                    sketch.stroke();
+                }
+                y += spacing;
+            }
+        }
+    }
+}
+
+/// Returns whether a point in canvas coordinates lies inside a trace.
+///
+/// The outline is approximated by a polygon and checked with
+/// [`polygon_contains`].
+fn trace_contains(trace: &Trace, point: Point, style: &Style) -> bool {
+    let points = trace.outline(style).iter_positions(
+        0.25 * style.measures().dt(), Some(0.)
+    ).map(|(pos, _)| pos).collect::<Vec<_>>();
+    polygon_contains(&points, point)
+}
+
+/// Returns whether a point lies inside a polygon.
+///
+/// The polygon is closed implicitly if necessary. The even-odd rule decides
+/// for self-intersecting polygons. Polygons with less than three points
+/// contain no points.
+fn polygon_contains(points: &[Point], point: Point) -> bool {
+    if points.len() < 3 {
+        return false
+    }
+    hatch_line(&points, point.y).any(|(x0, x1)| {
+        x0 <= point.x && point.x <= x1
+    })
+}
+
+/// Returns the parts of the horizontal line at `y` inside a polygon.
+///
+/// Uses the even-odd rule. The polygon is closed implicitly.
+fn hatch_line(
+    points: &[Point], y: f64
+) -> impl Iterator<Item = (f64, f64)> {
+    let mut xs = Vec::new();
+    let mut prev = points[points.len() - 1];
+    for &pos in points {
+        if (prev.y <= y) != (pos.y <= y) {
+            xs.push(
+                prev.x + (y - prev.y) * (pos.x - prev.x) / (pos.y - prev.y)
+            )
+        }
+        prev = pos;
+    }
+    xs.sort_by(|left, right| left.total_cmp(right));
+    let mut xs = xs.into_iter();
+    std::iter::from_fn(move || Some((xs.next()?, xs.next()?)))
+}
+
+
 //------------ PlatformContour -----------------------------------------------
 
-/// A contour drawing an area.
+/// The smallest detail level platforms with a width are drawn at.
+const WIDTH_DETAIL: u8 = 4;
+
+/// A contour drawing a platform.
+///
+/// If a width is given, the platform is drawn as a band of this width in
+/// _dt_ along the trace at detail level 4 and up. Otherwise the trace is
+/// filled like an area.
 pub struct PlatformContour {
     class: Railway,
+
+    /// The width of the band in _dt_ and its half width in storage units.
+    ///
+    /// The latter is for the largest _dt_ the band is drawn with.
+    width: Option<(f64, f64)>,
+
     trace: Trace,
 }
 
 impl PlatformContour {
-    pub fn new(class: Railway, trace: Trace) -> Self {
-        PlatformContour { class, trace }
+    /// Creates a new platform.
+    ///
+    /// The measures are needed to determine how far a band with a width
+    /// reaches beyond the trace.
+    pub fn new(
+        class: Railway, width: Option<f64>, trace: Trace,
+        measures: &MeasuresConfig,
+    ) -> Self {
+        let width = width.map(|width| {
+            (
+                width,
+                0.5 * width * style::max_storage_dt(WIDTH_DETAIL, measures)
+            )
+        });
+        PlatformContour { class, width, trace }
     }
 }
 
 impl Feature for PlatformContour {
     fn storage_bounds(&self) -> world::Rect {
-        self.trace.storage_bounds()
+        match self.width {
+            Some((_, margin)) => {
+                // Cover the whole width, not just the center line.
+                kurbo::Rect::from(
+                    self.trace.storage_bounds()
+                ).inflate(margin, margin).into()
+            }
+            None => self.trace.storage_bounds()
+        }
+    }
+
+    fn class(&self) -> Option<&Railway> {
+        Some(&self.class)
     }
 
     fn group(&self) -> Group {
         Group::with_railway(Category::Back, &self.class)
     }
 
+    fn hit(&self, point: Point, radius: f64, style: &Style) -> bool {
+        let point = style.transform() * point;
+        match self.width {
+            Some((width, _)) => {
+                self.trace.is_near(
+                    point, radius + 0.5 * width * style.measures().dt(),
+                    style
+                )
+            }
+            None => trace_contains(&self.trace, point, style)
+        }
+    }
+
     fn shape(
         &self, style: &Style, _canvas: &Canvas
     ) -> AnyShape {
+        if let Some((width, _)) = self.width {
+            if style.detail() < f64::from(WIDTH_DETAIL) {
+                return AnyShape::empty()
+            }
+            let color = style.platform_color();
+            let width = width * style.measures().dt();
+            let outline = self.trace.outline(style);
+            return AnyShape::single_stage(
+                move |_style: &Style, canvas: &mut Canvas| {
+                    canvas.sketch()
+                        .apply(color)
+                        .apply(LineWidth(width))
+                        .apply(LineCap::Butt)
+                        .apply(&outline)
+                        .stroke();
+                }
+            )
+        }
+
         let color = style.track_color(&self.class);
         let outline = self.trace.outline(style);
 
@@ -84,3 +370,61 @@ impl Feature for PlatformContour {
     }
 }
 
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use std::f64::consts::PI;
+    use super::*;
+
+    fn points(coords: &[(f64, f64)]) -> Vec<Point> {
+        coords.iter().map(|&(x, y)| Point::new(x, y)).collect()
+    }
+
+    #[test]
+    fn contains_closed() {
+        let square = points(&[
+            (0., 0.), (10., 0.), (10., 10.), (0., 10.), (0., 0.)
+        ]);
+        assert!(polygon_contains(&square, Point::new(5., 5.)));
+        assert!(polygon_contains(&square, Point::new(0.5, 9.5)));
+        assert!(!polygon_contains(&square, Point::new(15., 5.)));
+        assert!(!polygon_contains(&square, Point::new(5., -1.)));
+        assert!(!polygon_contains(&square, Point::new(-5., 5.)));
+    }
+
+    #[test]
+    fn contains_open() {
+        // The last edge back to the start is added implicitly.
+        let square = points(&[(0., 0.), (10., 0.), (10., 10.), (0., 10.)]);
+        assert!(polygon_contains(&square, Point::new(5., 5.)));
+        assert!(polygon_contains(&square, Point::new(0.5, 5.)));
+        assert!(!polygon_contains(&square, Point::new(-0.5, 5.)));
+
+        // Too short to enclose anything.
+        let line = points(&[(0., 0.), (10., 10.)]);
+        assert!(!polygon_contains(&line, Point::new(5., 5.)));
+        assert!(!polygon_contains(&[], Point::new(5., 5.)));
+    }
+
+    #[test]
+    fn contains_self_intersecting() {
+        // A bow tie: both loops are inside, the outside isn’t.
+        let bowtie = points(&[(0., 0.), (10., 10.), (10., 0.), (0., 10.)]);
+        assert!(polygon_contains(&bowtie, Point::new(1., 5.)));
+        assert!(polygon_contains(&bowtie, Point::new(9., 5.)));
+        assert!(!polygon_contains(&bowtie, Point::new(5., 1.)));
+        assert!(!polygon_contains(&bowtie, Point::new(5., 9.)));
+
+        // A pentagram: the points are inside, the center is covered twice
+        // and thus outside under the even-odd rule.
+        let star = (0..5).map(|i: u32| {
+            let angle = FRAC_PI_2 + f64::from(2 * i) * 0.4 * PI;
+            Point::new(10. * angle.cos(), -10. * angle.sin())
+        }).collect::<Vec<_>>();
+        assert!(polygon_contains(&star, Point::new(0., -8.)));
+        assert!(!polygon_contains(&star, Point::new(0., 0.)));
+        assert!(!polygon_contains(&star, Point::new(0., -12.)));
+    }
+}
@@ -1,13 +1,20 @@
 //! Rendering of borders.
+//!
+//! The border class needs one of `:national` or `:state` for the category
+//! of the border. `:former` marks a border that doesn’t exist anymore.
+//! `:provisional` and `:disputed` draw the border dashed or dotted,
+//! respectively, in all detail levels.
 
 use femtomap::world;
 use femtomap::import::eval::{EvalErrors, Failed, SymbolSet};
 use femtomap::path::Trace;
-use femtomap::render::{Canvas, DashPattern, Color, Outline, LineWidth};
+use femtomap::render::{
+    Canvas, DashPattern, Color, LineCap, Outline, LineWidth,
+};
 use crate::railway::class;
 use crate::railway::import::eval::Expression;
 use crate::railway::style::Style;
-use super::{AnyShape, Category, Group, Feature};
+use super::{AnyShape, Category, Group, Feature, TraceExt};
 
 //------------ Configuration -------------------------------------------------
 
@@ -65,6 +72,9 @@ pub struct BorderContour {
     /// Whether this is a former border.
     former: bool,
 
+    /// How the border line is drawn.
+    line: BorderLine,
+
     /// The trace of the border.
     trace: Trace,
 }
@@ -78,8 +88,9 @@ impl BorderContour {
         let mut symbols = arg.eval(err)?;
         let category = BorderCategory::from_symbols(&mut symbols, err)?;
         let former = symbols.take("former");
+        let line = BorderLine::from_symbols(&mut symbols);
         symbols.check_exhausted(err)?;
-        Ok(BorderContour { category, former, trace })
+        Ok(BorderContour { category, former, line, trace })
     }
 }
 
@@ -99,11 +110,15 @@ impl Feature for BorderContour {
         )
     }
 
+    fn hit(&self, point: kurbo::Point, radius: f64, style: &Style) -> bool {
+        self.trace.is_near(style.transform() * point, radius, style)
+    }
+
     fn shape(
         &self, style: &Style, _canvas: &Canvas
     ) -> AnyShape {
         let outline = self.trace.outline(style);
-        if style.detail() < 3 {
+        if style.detail() < 3. {
             AnyShape::single_stage(move |style: &Style, canvas: &mut Canvas| {
                 self.render_low(&outline, style, canvas)
             })
@@ -132,6 +147,10 @@ impl BorderContour {
             }
         );
         canvas.apply(LineWidth(LOW_BORDER_WIDTH * style.canvas_bp()));
+        if let Some(dash) = self.line.dash(style) {
+            canvas.apply(dash);
+            canvas.apply(self.line.cap());
+        }
         canvas.stroke();
     }
 
@@ -159,7 +178,15 @@ impl BorderContour {
             }
         );
         sketch.apply(LineWidth(BORDER_WIDTH * style.canvas_bp()));
-        sketch.apply(self.category.dash_high(style));
+        match self.line.dash(style) {
+            Some(dash) => {
+                sketch.apply(dash);
+                sketch.apply(self.line.cap());
+            }
+            None => {
+                sketch.apply(self.category.dash_high(style));
+            }
+        }
         sketch.stroke();
     }
 }
@@ -229,3 +256,54 @@ impl BorderCategory {
     }
 }
 
+
+//------------ BorderLine ----------------------------------------------------
+
+/// How the line of a border is drawn.
+#[derive(Clone, Copy, Debug)]
+enum BorderLine {
+    /// The regular line depending on the category.
+    Regular,
+
+    /// A dashed line for provisional borders.
+    Provisional,
+
+    /// A dotted line for disputed borders.
+    Disputed,
+}
+
+impl BorderLine {
+    fn from_symbols(symbols: &mut SymbolSet) -> Self {
+        if symbols.take("provisional") {
+            Self::Provisional
+        }
+        else if symbols.take("disputed") {
+            Self::Disputed
+        }
+        else {
+            Self::Regular
+        }
+    }
+
+    /// Returns the dash pattern or `None` for the regular line.
+    fn dash(self, style: &Style) -> Option<DashPattern<2>> {
+        let base = DASH_BASE * style.canvas_bp();
+        match self {
+            Self::Regular => None,
+            Self::Provisional => {
+                Some(DashPattern::new([0.6 * base, 0.4 * base], 0.))
+            }
+            Self::Disputed => {
+                // With round caps, the short strokes become dots.
+                Some(DashPattern::new([0.01 * base, 0.3 * base], 0.))
+            }
+        }
+    }
+
+    fn cap(self) -> LineCap {
+        match self {
+            Self::Disputed => LineCap::Round,
+            _ => LineCap::Butt,
+        }
+    }
+}
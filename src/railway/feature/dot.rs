@@ -3,7 +3,7 @@
 use femtomap::world;
 use femtomap::import::eval::{EvalErrors, Failed, SymbolSet};
 use femtomap::path::Position;
-use femtomap::render::{Canvas, Color, LineWidth};
+use femtomap::render::{Canvas, LineWidth};
 use kurbo::{Circle, Point};
 use crate::railway::class::Railway;
 use crate::railway::import::eval::{Scope, ScopeExt};
@@ -115,10 +115,18 @@ impl Feature for DotMarker {
         self.position.storage_bounds()
     }
 
+    fn class(&self) -> Option<&Railway> {
+        Some(&self.class)
+    }
+
     fn group(&self) -> Group {
         Group::with_railway(Category::Marker, &self.class)
     }
 
+    fn anchor(&self, style: &Style) -> Option<Point> {
+        Some(self.position.resolve(style).0)
+    }
+
     fn shape(
         &self, style: &Style, _canvas: &Canvas
     ) -> AnyShape {
@@ -153,7 +161,7 @@ impl<'a> Shape<'a> for DotShape<'a> {
                         )
                     );
                     //sketch.apply(Operator::DestinationOut);
-                    sketch.apply(Color::WHITE);
+                    sketch.apply(style.background_color());
                     sketch.fill();
                 }
             }
@@ -1,6 +1,10 @@
 //! Rendering of guides.
 //!
 //! Guides are thin lines attaching a label to something.
+//!
+//! The class of a guide can contain `:casing` to add a casing, `:wide` or
+//! `:xwide` to draw it two or three times as wide, and `:trackcolor` to draw
+//! it in the color of the track rather than that of labels.
 
 use femtomap::world;
 use femtomap::import::eval::{EvalErrors, Failed, SymbolSet};
@@ -9,7 +13,9 @@ use femtomap::render::{Canvas, LineWidth, Outline};
 use crate::railway::class::Railway;
 use crate::railway::import::eval::Scope;
 use crate::railway::style::Style;
-use super::{AnyShape, Category, Group, Feature, Shape, Stage, StageSet};
+use super::{
+    AnyShape, Category, Group, Feature, Shape, Stage, StageSet, TraceExt,
+};
 
 //------------ GuideContour --------------------------------------------------
 
@@ -21,6 +27,12 @@ pub struct GuideContour {
     /// Should the guide have a casing?
     casing: bool,
 
+    /// The width of the guide in multiples of the guide width.
+    width: f64,
+
+    /// Which color to draw the guide in.
+    color: GuideColor,
+
     trace: Trace,
 }
 
@@ -28,7 +40,12 @@ impl GuideContour {
     pub fn new(
         class: Railway, casing: bool, trace: Trace,
     ) -> Self {
-        GuideContour { class, casing, trace }
+        GuideContour {
+            class, casing,
+            width: 1.,
+            color: GuideColor::Label,
+            trace
+        }
     }
 
     pub fn from_symbols(
@@ -39,8 +56,32 @@ impl GuideContour {
     ) -> Result<Self, Failed> {
         let railway = Railway::from_symbols(&mut class, scope);
         let casing = class.take("casing");
+        let (width, color) = Self::look(|name| class.take(name));
         class.check_exhausted(err)?;
-        Ok(GuideContour { class: railway, casing, trace })
+        Ok(GuideContour { class: railway, casing, width, color, trace })
+    }
+
+    /// Determines width and color of a guide from its class symbols.
+    ///
+    /// The closure is called with a symbol name and should take the symbol
+    /// from the class and return whether it was present.
+    fn look(mut take: impl FnMut(&str) -> bool) -> (f64, GuideColor) {
+        let width = if take("wide") {
+            2.
+        }
+        else if take("xwide") {
+            3.
+        }
+        else {
+            1.
+        };
+        let color = if take("trackcolor") {
+            GuideColor::Track
+        }
+        else {
+            GuideColor::Label
+        };
+        (width, color)
     }
 }
 
@@ -49,10 +90,18 @@ impl Feature for GuideContour {
         self.trace.storage_bounds()
     }
 
+    fn class(&self) -> Option<&Railway> {
+        Some(&self.class)
+    }
+
     fn group(&self) -> Group {
         Group::with_category(Category::Label)
     }
 
+    fn hit(&self, point: kurbo::Point, radius: f64, style: &Style) -> bool {
+        self.trace.is_near(style.transform() * point, radius, style)
+    }
+
     fn shape(
         &self, style: &Style, _canvas: &Canvas
     ) -> AnyShape {
@@ -78,7 +127,8 @@ impl<'a> Shape<'a> for GuideShape<'a> {
                     let mut sketch = canvas.sketch();
                     sketch.apply(&self.trace);
                     sketch.apply(LineWidth(
-                        3. * style.measures().guide_width()
+                        (self.contour.width + 2.)
+                            * style.measures().guide_width()
                     ));
                     sketch.apply(style.casing_color());
                     sketch.stroke();
@@ -87,8 +137,17 @@ impl<'a> Shape<'a> for GuideShape<'a> {
             Stage::Base => {
                 let mut sketch = canvas.sketch();
                 sketch.apply(&self.trace);
-                sketch.apply(LineWidth(style.measures().guide_width()));
-                sketch.apply(style.label_color(&self.contour.class));
+                sketch.apply(LineWidth(
+                    self.contour.width * style.measures().guide_width()
+                ));
+                sketch.apply(match self.contour.color {
+                    GuideColor::Label => {
+                        style.label_color(&self.contour.class)
+                    }
+                    GuideColor::Track => {
+                        style.track_color(&self.contour.class)
+                    }
+                });
                 sketch.stroke();
             }
             _ => { }
@@ -106,3 +165,40 @@ impl<'a> Shape<'a> for GuideShape<'a> {
     }
 }
 
+
+//------------ GuideColor ----------------------------------------------------
+
+/// The color a guide is drawn in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum GuideColor {
+    /// The color of labels of the class.
+    Label,
+
+    /// The color of track of the class.
+    Track,
+}
+
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+    use super::*;
+
+    fn look(symbols: &[&str]) -> (f64, GuideColor) {
+        let mut symbols = symbols.iter().copied().collect::<HashSet<_>>();
+        let res = GuideContour::look(|name| symbols.remove(name));
+        assert!(symbols.is_empty(), "symbols left: {:?}", symbols);
+        res
+    }
+
+    #[test]
+    fn explicit_width() {
+        assert_eq!(look(&[]), (1., GuideColor::Label));
+        assert_eq!(look(&["wide"]), (2., GuideColor::Label));
+        assert_eq!(look(&["xwide"]), (3., GuideColor::Label));
+        assert_eq!(look(&["trackcolor"]), (1., GuideColor::Track));
+        assert_eq!(look(&["xwide", "trackcolor"]), (3., GuideColor::Track));
+    }
+}
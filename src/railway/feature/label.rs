@@ -1,19 +1,26 @@
 //! Making and rendering label features.
 
+use std::f64::consts::FRAC_PI_2;
 use femtomap::{layout, world};
 use femtomap::import::eval::{EvalErrors, Failed, SymbolSet};
 use femtomap::layout::{Align, Base, Margins, ShapedBlock, ShapedLayout};
-use femtomap::path::Position;
+use femtomap::path::{Position, Trace};
 use femtomap::render::{
-    Canvas, Color, Font, FontBuilder, FontFamily, FontFeatures, FontStretch,
+    Canvas, Font, FontBuilder, FontFamily, FontFeatures, FontStretch,
     FontStyle, FontWeight, LineCap, LineJoin, LineWidth, Matrix,
     Operator, TextDecoration, Sketch,
 };
+use kurbo::{Affine, Rect};
+use crate::config::MeasuresConfig;
 use crate::railway::import::eval;
 use crate::railway::import::eval::{Custom, Expression, Scope};
 use crate::railway::class::Railway;
+use crate::railway::style;
 use crate::railway::style::Style;
-use super::{AnyShape, Category, Group, Feature, Shape, Stage, StageSet};
+use super::{
+    AnyShape, Category, FeatureInfo, Group, Feature, Shape, Stage, StageSet,
+    TraceExt,
+};
 
 
 //------------ Configuration -------------------------------------------------
@@ -43,6 +50,62 @@ pub struct Label {
 
     /// The block to render
     block: Layout,
+
+    /// The text of the label if known.
+    ///
+    /// This is only used for identifying the label when picking features.
+    text: Option<String>,
+
+    /// The marker the label is placed next to, if any.
+    marker: Option<MarkerOffset>,
+}
+
+/// The marker a label is placed next to.
+#[derive(Clone, Copy, Debug)]
+struct MarkerOffset {
+    /// The orientation of the marker relative to the position.
+    orientation: f64,
+
+    /// The scale of the marker.
+    scale: f64,
+
+    /// Should the label’s box be fitted beyond the marker?
+    ///
+    /// If this is `false`, the label’s anchor is moved beyond the marker
+    /// instead.
+    fit: bool,
+}
+
+impl MarkerOffset {
+    /// Returns the direction the marker points to in canvas coordinates.
+    ///
+    /// Markers are drawn along the local y axis which is rotated by the
+    /// path’s direction plus the marker’s orientation.
+    fn direction(self, path_angle: f64) -> f64 {
+        path_angle + self.orientation + FRAC_PI_2
+    }
+
+    /// Returns the distance between the position and the label.
+    fn distance(self, style: &Style) -> f64 {
+        self.scale * style.measures().sh() + style.measures().dt()
+    }
+
+    /// Returns how far to move a label with the given box.
+    ///
+    /// The box is given relative to the label’s anchor in the label’s own
+    /// coordinates and `direction` is the marker direction in the same
+    /// coordinates. The label is moved along the direction so that the
+    /// closest corner of the box ends up `distance` beyond the position.
+    fn fit_distance(outer: Rect, direction: f64, distance: f64) -> f64 {
+        let dir = kurbo::Vec2::from_angle(direction);
+        let near = [
+            (outer.x0, outer.y0), (outer.x1, outer.y0),
+            (outer.x0, outer.y1), (outer.x1, outer.y1),
+        ].into_iter().map(|(x, y)| {
+            kurbo::Vec2::new(x, y).dot(dir)
+        }).fold(f64::INFINITY, f64::min);
+        distance - near
+    }
 }
 
 impl Label {
@@ -56,9 +119,57 @@ impl Label {
         block.update_properties(&base, |me, parent| me.update(parent));
 
         Self {
-            position, on_path, block
+            position, on_path, block,
+            text: None,
+            marker: None,
         }
     }
+
+    /// Moves the label next to a marker at the same position.
+    ///
+    /// The label is moved away from the position in the direction the
+    /// marker points to by the marker’s height plus a _dt._ The
+    /// `orientation` and `scale` are those of the marker. If `fit` is
+    /// `true`, the label’s box rather than its anchor is moved there, so
+    /// the label ends up on the side the marker points to whatever the
+    /// direction of the path.
+    pub fn with_marker(
+        mut self, orientation: f64, scale: f64, fit: bool
+    ) -> Self {
+        self.marker = Some(MarkerOffset { orientation, scale, fit });
+        self
+    }
+
+    /// Returns the point and angle of the label in canvas coordinates.
+    ///
+    /// If the label is placed next to a marker and `outer` is given, it is
+    /// used as the box of the label for fitting it beyond the marker.
+    fn resolve(
+        &self, style: &Style, outer: Option<Rect>
+    ) -> (kurbo::Point, f64) {
+        let (point, angle) = self.position.resolve_label(style, self.on_path);
+        let marker = match self.marker {
+            Some(marker) => marker,
+            None => return (point, angle),
+        };
+        let (_, path_angle) = self.position.resolve(style);
+        let direction = marker.direction(path_angle);
+        let distance = match outer {
+            Some(outer) if marker.fit => {
+                MarkerOffset::fit_distance(
+                    outer, direction - angle, marker.distance(style)
+                )
+            }
+            _ => marker.distance(style)
+        };
+        (point + kurbo::Vec2::from_angle(direction) * distance, angle)
+    }
+
+    /// Sets the text identifying the label.
+    pub fn with_text(mut self, text: Option<String>) -> Self {
+        self.text = text;
+        self
+    }
 }
 
 impl Feature for Label {
@@ -70,13 +181,30 @@ impl Feature for Label {
         Group::with_category(Category::Label)
     }
 
+    fn class(&self) -> Option<&Railway> {
+        Some(self.block.properties().class())
+    }
+
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo::new(self.group(), self.class()).with_text(
+            self.text.clone()
+        )
+    }
+
+    fn anchor(&self, style: &Style) -> Option<kurbo::Point> {
+        Some(self.resolve(style, None).0)
+    }
+
     fn shape(
         &self, style: &Style, canvas: &Canvas
     ) -> AnyShape {
-        let (point, angle) = self.position.resolve_label(style, self.on_path);
-        let matrix = Matrix::identity().translate(point).rotate(angle);
         let layout = self.block.shape(Default::default(), style, canvas);
-        AnyShape::from(LabelShape { matrix, layout })
+        let (point, angle) = self.resolve(style, Some(layout.outer()));
+        let matrix = Matrix::identity().translate(point).rotate(angle);
+        let bounds = (
+            Affine::translate(point.to_vec2()) * Affine::rotate(angle)
+        ).transform_rect_bbox(layout.outer());
+        AnyShape::from(LabelShape { matrix, layout, bounds })
     }
 }
 
@@ -86,6 +214,9 @@ impl Feature for Label {
 struct LabelShape<'a> {
     matrix: Matrix,
     layout: ShapedLayout<'a, BlockProperties>,
+
+    /// The bounding box of the rendered label in canvas coordinates.
+    bounds: Rect,
 }
 
 const LABEL_STAGES: StageSet = StageSet::from_slice(&[
@@ -100,10 +231,172 @@ impl<'a> Shape<'a> for LabelShape<'a> {
     fn stages(&self) -> StageSet {
         LABEL_STAGES
     }
+
+    fn collision_box(&self) -> Option<Rect> {
+        Some(self.bounds)
+    }
 }
     
 
 
+//------------ TickLabels ----------------------------------------------------
+
+/// The labels for ticks along a trace.
+///
+/// Each tick placed by [`Ticks`][super::marker::Ticks] with the same trace
+/// and distance gets a label with its distance from the start of the trace
+/// in kilometres. The labels are placed to the left of the trace beyond
+/// the tick.
+pub struct TickLabels {
+    /// The trace the ticks are placed along.
+    trace: Trace,
+
+    /// The distance between ticks in metres.
+    every: f64,
+
+    /// The class of the labels.
+    class: Railway,
+
+    /// How far the labels reach beyond the trace in storage coordinates.
+    margin: f64,
+}
+
+impl TickLabels {
+    /// Creates the labels with the distance between ticks in metres.
+    ///
+    /// The measures are needed to determine how far the labels reach
+    /// beyond the trace.
+    pub fn new(
+        class: Railway, trace: Trace, every: f64,
+        measures: &MeasuresConfig,
+    ) -> Self {
+        // Leave room for labels of up to about six characters.
+        let margin = style::max_storage_size(0, measures, |units| {
+            0.5 * units.sh() + units.dt() + 4. * units.xsmall_font()
+        });
+        TickLabels { trace, every, class, margin }
+    }
+
+    /// Returns the text of the label for the tick with the given index.
+    ///
+    /// The first tick has index 0.
+    fn text(&self, idx: usize) -> String {
+        // Round to whole metres to get rid of floating point artefacts.
+        let metres = ((idx + 1) as f64 * self.every).round();
+        format!("{}", metres / 1000.)
+    }
+}
+
+impl Feature for TickLabels {
+    fn storage_bounds(&self) -> world::Rect {
+        kurbo::Rect::from(
+            self.trace.storage_bounds()
+        ).inflate(self.margin, self.margin).into()
+    }
+
+    fn group(&self) -> Group {
+        Group::with_category(Category::Label)
+    }
+
+    fn hit(
+        &self, point: kurbo::Point, radius: f64, style: &Style
+    ) -> bool {
+        // The labels sit beside the trace, so widen the radius by their
+        // distance from it.
+        let distance = 0.5 * style.measures().sh() + style.measures().dt();
+        self.trace.is_near(
+            style.transform() * point, radius + distance, style
+        )
+    }
+
+    fn class(&self) -> Option<&Railway> {
+        Some(&self.class)
+    }
+
+    fn shape(
+        &self, style: &Style, _canvas: &Canvas
+    ) -> AnyShape {
+        let distance = 0.5 * style.measures().sh() + style.measures().dt();
+        let mut base = BlockProperties::with_class(self.class.clone());
+        base.update(&BlockProperties::base());
+        let labels = self.trace.positions_every(
+            self.trace.canvas_distance(self.every, style), style
+        ).enumerate().map(|(idx, (point, dir))| {
+            let point = point + kurbo::Vec2::from_angle(
+                dir - std::f64::consts::FRAC_PI_2
+            ) * distance;
+            let mut block = Layout::hbox(
+                Base::Center, Align::End,
+                BlockProperties::with_size(FontSize::Xsmall),
+                vec![Block::span(self.text(idx).into(), Default::default())],
+            );
+            block.update_properties(&base, |me, parent| me.update(parent));
+            (Matrix::identity().translate(point), block)
+        }).collect();
+        AnyShape::from(TickLabelsShape { labels })
+    }
+}
+
+
+//------------ TickLabelsShape -----------------------------------------------
+
+struct TickLabelsShape {
+    /// The translation and layout of each label.
+    labels: Vec<(Matrix, Layout)>,
+}
+
+impl<'a> Shape<'a> for TickLabelsShape {
+    fn render(&self, stage: Stage, style: &Style, canvas: &mut Canvas) {
+        for (matrix, block) in &self.labels {
+            let layout = block.shape(Default::default(), style, canvas);
+            layout.render(style, &stage, canvas.sketch().apply(*matrix))
+        }
+    }
+
+    fn stages(&self) -> StageSet {
+        LABEL_STAGES
+    }
+}
+
+
+//------------ Legend --------------------------------------------------------
+
+/// Renders a line of small text for the legend.
+///
+/// The text starts at `point` in canvas coordinates and is vertically
+/// centered on it.
+pub fn render_legend(
+    text: &str, point: kurbo::Point, style: &Style, canvas: &mut Canvas
+) {
+    let block = legend_block(text);
+    let layout = block.shape(Default::default(), style, canvas);
+    let matrix = Matrix::identity().translate(point);
+    for stage in LABEL_STAGES.iter() {
+        layout.render(style, &stage, canvas.sketch().apply(matrix))
+    }
+}
+
+/// Returns the width of a line of legend text in canvas coordinates.
+pub fn legend_width(text: &str, style: &Style, canvas: &Canvas) -> f64 {
+    let block = legend_block(text);
+    let layout = block.shape(Default::default(), style, canvas);
+    layout.outer().width()
+}
+
+/// Creates the layout for a line of legend text.
+fn legend_block(text: &str) -> Layout {
+    let mut block = Layout::hbox(
+        Base::Start, Align::Center,
+        BlockProperties::with_size(FontSize::Small),
+        vec![Block::span(String::from(text).into(), Default::default())],
+    );
+    block.update_properties(
+        &BlockProperties::base(), |me, parent| me.update(parent)
+    );
+    block
+}
+
+
 //------------ Layout ---------------------------------------------------------
 
 pub type Layout = layout::Layout<BlockProperties>;
@@ -125,6 +418,15 @@ pub fn block_from_expr(
 }
 
 
+/// Returns the text of an expression if it is plain text.
+pub fn text_from_expr(expr: &eval::Expression) -> Option<String> {
+    match expr.value {
+        eval::Value::Text(ref val) => Some(val.clone()),
+        _ => None
+    }
+}
+
+
 //------------ Block --------------------------------------------------------
 
 pub type Block = layout::Block<BlockProperties>;
@@ -255,6 +557,12 @@ pub struct BlockProperties {
     /// Is this a packed layout?
     packed: Option<bool>,
 
+    /// The halo drawn around text.
+    halo: Option<Halo>,
+
+    /// Should size and weight stay independent of the class?
+    fixed_size: Option<bool>,
+
     /// What kind of layout to we have?
     layout_type: BlockType,
 
@@ -316,6 +624,8 @@ impl BlockProperties {
             font: Self::font_from_symbols(symbols),
             size: FontSize::from_symbols(symbols),
             packed: None,
+            halo: Halo::from_symbols(symbols),
+            fixed_size: symbols.take("fixedsize").then_some(true),
             layout_type: BlockType::Normal,
             class: Railway::from_symbols(symbols, scope),
         }
@@ -328,6 +638,8 @@ impl BlockProperties {
             font: Self::font_from_symbols(symbols),
             size: FontSize::from_symbols(symbols),
             packed: None,
+            halo: Halo::from_symbols(symbols),
+            fixed_size: symbols.take("fixedsize").then_some(true),
             layout_type: BlockType::Normal,
             class: Railway::from_symbols_only(symbols),
         }
@@ -414,12 +726,33 @@ impl BlockProperties {
         if self.packed.is_none() {
             self.packed = base.packed
         }
+        if self.halo.is_none() {
+            self.halo = base.halo
+        }
+        if self.fixed_size.is_none() {
+            self.fixed_size = base.fixed_size
+        }
         self.class.update(&base.class)
     }
 
     fn size(&self) -> FontSize {
         self.size.unwrap_or_default()
     }
+
+    fn fixed_size(&self) -> bool {
+        matches!(self.fixed_size, Some(true))
+    }
+
+    /// Returns the font size scaled for the class unless it is fixed.
+    fn font_size(&self, style: &Style) -> f64 {
+        let size = self.size().size(style);
+        if self.fixed_size() {
+            size
+        }
+        else {
+            size * style.label_size_factor(&self.class)
+        }
+    }
 }
 
 
@@ -444,9 +777,11 @@ impl layout::Properties for BlockProperties {
     }
 
     fn font(&self, style: &Self::Style) -> Font {
-        self.font.clone().size(
-            self.size().size(style)
-        ).finalize()
+        let mut font = self.font.clone();
+        if !self.fixed_size() && style.label_bold(&self.class) {
+            font.update(&FontBuilder::new().weight(FontWeight::Bold));
+        }
+        font.size(self.font_size(style)).finalize()
     }
 
     fn frame(&self, style: &Self::Style) -> Option<Margins> {
@@ -472,14 +807,14 @@ impl layout::Properties for BlockProperties {
             }
             BlockType::Framed => {
                 Margins::vh(
-                    self.size().size(style) * 0.15,
-                    self.size().size(style) * 0.2,
+                    self.font_size(style) * 0.15,
+                    self.font_size(style) * 0.2,
                 )
             }
             BlockType::ReverseBadge => {
                 Margins::vh(
-                    self.size().size(style) * 0.1,
-                    self.size().size(style) * 0.2,
+                    self.font_size(style) * 0.1,
+                    self.font_size(style) * 0.2,
                 )
             }
             _ => Margins::default()
@@ -500,10 +835,14 @@ impl layout::Properties for BlockProperties {
                 {
                     return
                 }
+                let factor = match self.halo.unwrap_or_default().factor() {
+                    Some(factor) => factor,
+                    None => return,
+                };
                 canvas.apply(LineCap::Butt);
                 canvas.apply(LineJoin::Bevel);
-                canvas.apply(Color::WHITE);
-                canvas.apply(LineWidth(self.size().size(style) * 0.3));
+                canvas.apply(style.background_color());
+                canvas.apply(LineWidth(self.font_size(style) * factor));
                 layout.stroke_text(canvas);
             }
             Stage::MarkerBase => {
@@ -516,7 +855,7 @@ impl layout::Properties for BlockProperties {
                     }
                     BlockType::TextFrame => {
                         canvas.apply(layout.outer());
-                        canvas.apply(Color::WHITE);
+                        canvas.apply(style.background_color());
                         canvas.fill();
                     }
                     BlockType::ReverseBadge => {
@@ -530,7 +869,7 @@ impl layout::Properties for BlockProperties {
             Stage::MarkerMarking => {
                 if layout.is_span() {
                     if matches!(self.layout_type, BlockType::ReverseBadge) {
-                        canvas.apply(Color::WHITE);
+                        canvas.apply(style.background_color());
                     }
                     else {
                         canvas.apply(style.label_color(&self.class));
@@ -637,6 +976,44 @@ impl FontSize {
 }
 
 
+//------------ Halo ----------------------------------------------------------
+
+/// The halo drawn around text to keep it readable over busy backgrounds.
+///
+/// The halo is stroked in the background color before the text is filled.
+/// Its width is relative to the font size.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Halo {
+    /// No halo at all, selected via `:nohalo`.
+    None,
+
+    /// The normal halo, selected via `:halo`.
+    #[default]
+    Normal,
+
+    /// A wider halo for very busy backgrounds, selected via `:widehalo`.
+    Wide,
+}
+
+impl Halo {
+    pub fn from_symbols(symbols: &mut SymbolSet) -> Option<Self> {
+        if symbols.take("nohalo") { Some(Halo::None) }
+        else if symbols.take("halo") { Some(Halo::Normal) }
+        else if symbols.take("widehalo") { Some(Halo::Wide) }
+        else { None }
+    }
+
+    /// Returns the width of the halo stroke relative to the font size.
+    fn factor(self) -> Option<f64> {
+        match self {
+            Halo::None => None,
+            Halo::Normal => Some(0.3),
+            Halo::Wide => Some(0.5),
+        }
+    }
+}
+
+
 //------------ Anchor --------------------------------------------------------
 
 /// The compass direction where to anchor a label.
@@ -760,3 +1137,39 @@ impl TextAnchor {
     }
 }
 
+
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::f64::consts::{FRAC_PI_4, PI};
+
+    /// Checks that a label ends up on the given side of a diagonal path.
+    fn check_side(orientation: f64, normal: kurbo::Vec2) {
+        let path_angle = FRAC_PI_4;
+        let outer = Rect::new(0., -8., 30., 2.);
+        let distance = 5.;
+        let marker = MarkerOffset { orientation, scale: 1., fit: true };
+        let direction = marker.direction(path_angle);
+        let shift = kurbo::Vec2::from_angle(direction)
+            * MarkerOffset::fit_distance(outer, direction, distance);
+        let near = [
+            (outer.x0, outer.y0), (outer.x1, outer.y0),
+            (outer.x0, outer.y1), (outer.x1, outer.y1),
+        ].into_iter().map(|(x, y)| {
+            (kurbo::Vec2::new(x, y) + shift).dot(normal)
+        }).fold(f64::INFINITY, f64::min);
+        assert!((near - distance).abs() < 1e-9);
+    }
+
+    #[test]
+    fn marker_label_side() {
+        // The path runs down and to the right in canvas coordinates where
+        // y grows downwards, so its right is down and to the left.
+        let right = kurbo::Vec2::new(-1., 1.).normalize();
+        check_side(0., right); // :right
+        check_side(PI, -right); // :left
+    }
+}
@@ -8,26 +8,48 @@
 ///    top, left, bottom of the position, respectively. The default of none
 ///    of these is provided is `:right`.
 ///
+/// In addition, an angle in degrees can be given as the optional third
+/// argument of the `marker` procedure. It is added to the orientation given
+/// by the symbols. Since the y axis of the canvas points downwards, positive
+/// angles rotate the marker clockwise. For example, `:top` is the same as
+/// an angle of 270 or -90.
+///
 /// *  `:closed`, `:removed`: The entity described by the marker has been
 ///    closed or removed.
+///
+/// *  `:branchleft`, `:branchright`: For junction markers, the side the
+///    branch leaves to as seen from the track looking in the direction the
+///    marker points. At low detail, the marker then shows the branch
+///    instead of the symmetric chevron. Without these, the junction is
+///    undirected.
+///
+/// *  `:mindetail1` to `:mindetail5`: Only draw the marker at the given
+///    detail level and above. This can be used to keep minor markers off
+///    overview maps. By default, markers are drawn at all detail levels.
+///
+/// Markers can be drawn larger or smaller than normal via the `marker_scale`
+/// render parameter, e.g., `with marker_scale = 1.5 { ... }`. The scale must
+/// be positive and is clamped to the range from 0.25 to 4. It defaults to 1.
 
+use std::ops;
 use std::collections::HashMap;
 use std::f64::consts::PI;
+use std::sync::Arc;
 use femtomap::world;
 use femtomap::import::eval::{EvalErrors, Failed, SymbolSet};
-use femtomap::path::Position;
+use femtomap::path::{Position, Trace};
 use femtomap::render::{
-    Canvas, Color, DashPattern, Group, LineCap, Matrix, Operator
+    Canvas, Color, DashPattern, Group, LineCap, LineWidth, Matrix, Operator
 };
 use lazy_static::lazy_static;
 use crate::railway::class::Railway;
-use crate::railway::import::eval::Scope;
+use crate::railway::import::eval::{Scope, ScopeExt};
 use crate::railway::measures::Measures;
+use crate::config::MeasuresConfig;
+use crate::railway::style;
 use crate::railway::style::Style;
-use super::{AnyFeature, AnyShape, Category, Feature};
-
-
-const CASING_COLOR: Color = Color::rgba(1., 1., 1., 0.7);
+use super::{AnyFeature, AnyShape, Category, Feature, TraceExt};
+use super::svgmarker::SvgMarker;
 
 
 //------------ from_args -----------------------------------------------------
@@ -35,10 +57,13 @@ const CASING_COLOR: Color = Color::rgba(1., 1., 1., 0.7);
 pub fn from_args(
     symbols: SymbolSet,
     position: Position,
+    angle: Option<f64>,
     scope: &Scope,
     err: &mut EvalErrors,
 ) -> Result<AnyFeature, Failed> {
-    StandardMarker::from_arg(symbols, position, scope, err).map(Into::into)
+    StandardMarker::from_arg(
+        symbols, position, angle, scope, err
+    ).map(Into::into)
 }
 
 
@@ -59,20 +84,53 @@ pub struct StandardMarker {
     /// The feature class.
     class: Railway,
 
+    /// The factor to scale the marker by.
+    scale: f64,
+
     /// The marker to use.
-    marker: Marker,
+    marker: AnyMarker,
+
+    /// The smallest detail level the marker is drawn at.
+    min_detail: u8,
+
+    /// The side of the branch if the marker is a directed junction.
+    branch: Option<Branch>,
 }
 
 
 impl StandardMarker {
+    /// Creates a marker from its symbols, position, and optional angle.
+    ///
+    /// The angle is given in degrees.
     pub fn from_arg(
         mut symbols: SymbolSet,
         position: Position,
+        angle: Option<f64>,
         scope: &Scope,
         err: &mut EvalErrors,
     ) -> Result<Self, Failed> {
-        let orientation = Self::rotation_from_symbols(&mut symbols, err)?;
-        let class = Railway::from_symbols(&mut symbols, scope);
+        let min_detail = Self::min_detail_from_symbols(&mut symbols);
+        let branch = Branch::from_symbols(&mut symbols, err)?;
+        let (orientation, class, marker) = Self::parse(
+            &mut symbols, angle, scope, err
+        )?;
+        Ok(StandardMarker {
+            position, orientation, class,
+            scale: scope.marker_scale(),
+            marker, min_detail, branch,
+        })
+    }
+
+    /// Takes orientation, class, and the marker from the symbols.
+    fn parse(
+        symbols: &mut SymbolSet,
+        angle: Option<f64>,
+        scope: &Scope,
+        err: &mut EvalErrors,
+    ) -> Result<(f64, Railway, AnyMarker), Failed> {
+        let orientation = Self::rotation_from_symbols(symbols, err)?
+            + angle.unwrap_or(0.).to_radians();
+        let class = Railway::from_symbols(symbols, scope);
         let _ = symbols.take("casing");
         let pos = symbols.pos();
         let marker = match symbols.take_final(err)? {
@@ -82,14 +140,17 @@ impl StandardMarker {
                 return Err(Failed)
             }
         };
-        let marker = match OLD_MARKERS.get(marker.as_str()) {
-            Some(marker) => *marker,
-            None => {
-                err.add(pos, "missing marker");
-                return Err(Failed)
+        let marker = match scope.builtin().markers().get(marker.as_str()) {
+            Some(marker) => AnyMarker::Svg(marker),
+            None => match OLD_MARKERS.get(marker.as_str()) {
+                Some(marker) => AnyMarker::Builtin(*marker),
+                None => {
+                    err.add(pos, "missing marker");
+                    return Err(Failed)
+                }
             }
         };
-        Ok(StandardMarker { position, orientation, class, marker })
+        Ok((orientation, class, marker))
     }
 
     fn rotation_from_symbols(
@@ -117,25 +178,94 @@ impl StandardMarker {
         }
     }
 
+    fn min_detail_from_symbols(symbols: &mut SymbolSet) -> u8 {
+        for &(name, detail) in Self::MIN_DETAILS {
+            if symbols.take(name) {
+                return detail
+            }
+        }
+        0
+    }
+
+    const MIN_DETAILS: &'static [(&'static str, u8)] = &[
+        ("mindetail1", 1),
+        ("mindetail2", 2),
+        ("mindetail3", 3),
+        ("mindetail4", 4),
+        ("mindetail5", 5),
+    ];
+
     pub fn class(&self) -> &Railway {
         &self.class
     }
 
+    /// Returns the angle in radians added to the position’s direction.
+    pub fn orientation(&self) -> f64 {
+        self.orientation
+    }
+
+    /// Returns the factor the marker is scaled by.
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
     fn render(&self, style: &Style, canvas: &mut Canvas) {
-        let mut canvas = canvas.sketch().into_group();
         let (point, angle) = self.position.resolve(style);
-        canvas.apply(
-            Matrix::identity().translate(
-                point
-            ).rotate(angle + self.orientation)
-        );
-        canvas.apply(style.primary_marker_color(&self.class));
-        if style.detail() >= 4 {
-            (self.marker.large)(&mut canvas, style.measures())
+        let angle = angle + self.orientation;
+        match self.branch {
+            Some(branch) if !large_markers(style) => {
+                AnyMarker::Builtin(branch.marker()).render(
+                    point, angle, &self.class, self.scale, style, canvas
+                )
+            }
+            _ => {
+                self.marker.render(
+                    point, angle, &self.class, self.scale, style, canvas
+                )
+            }
+        }
+    }
+}
+
+
+//------------ Branch --------------------------------------------------------
+
+/// The side a branch leaves a junction to.
+#[derive(Clone, Copy, Debug)]
+enum Branch {
+    Left,
+    Right,
+}
+
+impl Branch {
+    fn from_symbols(
+        symbols: &mut SymbolSet, err: &mut EvalErrors
+    ) -> Result<Option<Self>, Failed> {
+        let res = if symbols.take("branchleft") {
+            Branch::Left
+        }
+        else if symbols.take("branchright") {
+            Branch::Right
         }
         else {
-            (self.marker.small)(&mut canvas, style.measures())
+            return Ok(None)
+        };
+        if !symbols.contains("de.abzw") && !symbols.contains("junction") {
+            err.add(symbols.pos(), "branch side given for a non-junction");
+            return Err(Failed)
         }
+        Ok(Some(res))
+    }
+
+    /// Returns the marker showing only the branch.
+    ///
+    /// The first branch marker leaves towards negative x which is to the
+    /// right when looking along the marker’s positive y axis.
+    fn marker(self) -> Marker {
+        OLD_MARKERS[match self {
+            Branch::Left => "de.abzw.second",
+            Branch::Right => "de.abzw.first",
+        }]
     }
 }
 
@@ -144,10 +274,191 @@ impl Feature for StandardMarker {
         self.position.storage_bounds()
     }
 
+    fn class(&self) -> Option<&Railway> {
+        Some(&self.class)
+    }
+
+    fn group(&self) -> super::Group {
+        super::Group::with_railway(Category::Marker, &self.class)
+    }
+
+    fn anchor(&self, style: &Style) -> Option<kurbo::Point> {
+        Some(self.position.resolve(style).0)
+    }
+
+    fn shape(
+        &self, style: &Style, _canvas: &Canvas
+    ) -> AnyShape {
+        if style.detail() < f64::from(self.min_detail) {
+            return AnyShape::empty()
+        }
+        AnyShape::single_stage(|style: &Style, canvas: &mut Canvas| {
+            self.render(style, canvas)
+        })
+    }
+}
+
+
+//------------ SpacedMarker --------------------------------------------------
+
+/// A marker repeated at even distances along a trace.
+pub struct SpacedMarker {
+    /// The trace to place the markers along.
+    trace: Trace,
+
+    /// The distance between markers in _dt._
+    dist: f64,
+
+    /// The angle added to the trace’s direction.
+    orientation: f64,
+
+    /// The feature class.
+    class: Railway,
+
+    /// The factor to scale the markers by.
+    scale: f64,
+
+    /// The marker to use.
+    marker: AnyMarker,
+}
+
+impl SpacedMarker {
+    /// Creates the marker from its symbols, trace, and distance.
+    ///
+    /// The symbols are the same as for a standard marker. The distance is
+    /// given in multiples of _dt._
+    pub fn from_arg(
+        mut symbols: SymbolSet,
+        trace: Trace,
+        dist: f64,
+        scope: &Scope,
+        err: &mut EvalErrors,
+    ) -> Result<Self, Failed> {
+        let (orientation, class, marker) = StandardMarker::parse(
+            &mut symbols, None, scope, err
+        )?;
+        Ok(SpacedMarker {
+            trace, dist, orientation, class,
+            scale: scope.marker_scale(),
+            marker
+        })
+    }
+
+    fn render(&self, style: &Style, canvas: &mut Canvas) {
+        let dist = self.dist * style.measures().dt();
+        for (point, angle) in self.trace.spaced_positions(dist, style) {
+            self.marker.render(
+                point, angle + self.orientation, &self.class, self.scale,
+                style, canvas
+            )
+        }
+    }
+}
+
+impl Feature for SpacedMarker {
+    fn storage_bounds(&self) -> world::Rect {
+        self.trace.storage_bounds()
+    }
+
+    fn class(&self) -> Option<&Railway> {
+        Some(&self.class)
+    }
+
+    fn group(&self) -> super::Group {
+        super::Group::with_railway(Category::Marker, &self.class)
+    }
+
+    fn hit(&self, point: kurbo::Point, radius: f64, style: &Style) -> bool {
+        self.trace.is_near(style.transform() * point, radius, style)
+    }
+
+    fn shape(
+        &self, _style: &Style, _canvas: &Canvas
+    ) -> AnyShape {
+        AnyShape::single_stage(|style: &Style, canvas: &mut Canvas| {
+            self.render(style, canvas)
+        })
+    }
+}
+
+
+//------------ Ticks ---------------------------------------------------------
+
+/// Ticks across a trace at regular real world distances.
+///
+/// This is used for kilometre or mileage ticks. A tick is drawn at each
+/// multiple of the distance from the start of the trace.
+pub struct Ticks {
+    /// The trace to place the ticks along.
+    trace: Trace,
+
+    /// The distance between ticks in metres.
+    every: f64,
+
+    /// The feature class.
+    class: Railway,
+
+    /// The factor to scale the ticks by.
+    scale: f64,
+
+    /// How far the ticks reach beyond the trace in storage coordinates.
+    margin: f64,
+}
+
+impl Ticks {
+    /// Creates the ticks with the distance between them in metres.
+    ///
+    /// The measures are needed to determine how far the ticks reach
+    /// beyond the trace.
+    pub fn new(
+        class: Railway, trace: Trace, every: f64, scope: &Scope,
+        measures: &MeasuresConfig,
+    ) -> Self {
+        let scale = scope.marker_scale();
+        let margin = scale * style::max_storage_size(
+            0, measures, |units| 0.5 * units.sh()
+        );
+        Ticks { trace, every, class, scale, margin }
+    }
+
+    fn render(&self, style: &Style, canvas: &mut Canvas) {
+        let units = Units::new(style, self.scale);
+        let half = 0.5 * units.sh();
+        let mut sketch = canvas.sketch();
+        sketch.apply(style.primary_marker_color(&self.class));
+        sketch.apply(LineWidth(units.light_track()));
+        for (point, dir) in self.trace.positions_every(
+            self.trace.canvas_distance(self.every, style), style
+        ) {
+            let dir = kurbo::Vec2::from_angle(dir + 0.5 * PI) * half;
+            sketch.apply([
+                kurbo::PathEl::MoveTo(point - dir),
+                kurbo::PathEl::LineTo(point + dir),
+            ]);
+        }
+        sketch.stroke();
+    }
+}
+
+impl Feature for Ticks {
+    fn storage_bounds(&self) -> world::Rect {
+        kurbo::Rect::from(
+            self.trace.storage_bounds()
+        ).inflate(self.margin, self.margin).into()
+    }
+
+    fn class(&self) -> Option<&Railway> {
+        Some(&self.class)
+    }
+
     fn group(&self) -> super::Group {
         super::Group::with_railway(Category::Marker, &self.class)
     }
 
+    fn hit(&self, point: kurbo::Point, radius: f64, style: &Style) -> bool {
+        self.trace.is_near(style.transform() * point, radius, style)
+    }
+
     fn shape(
         &self, _style: &Style, _canvas: &Canvas
     ) -> AnyShape {
@@ -158,6 +469,113 @@ impl Feature for StandardMarker {
 }
 
 
+//------------ AnyMarker -----------------------------------------------------
+
+/// Either a built-in marker or one loaded from an SVG file.
+enum AnyMarker {
+    Builtin(Marker),
+    Svg(Arc<SvgMarker>),
+}
+
+impl AnyMarker {
+    /// Renders the marker at a point in canvas coordinates.
+    ///
+    /// The `angle` is the final rotation of the marker in radians.
+    fn render(
+        &self, point: kurbo::Point, angle: f64, class: &Railway, scale: f64,
+        style: &Style, canvas: &mut Canvas,
+    ) {
+        let mut canvas = canvas.sketch().into_group();
+        canvas.apply(Matrix::identity().translate(point).rotate(angle));
+        canvas.apply(style.primary_marker_color(class));
+        let units = Units::new(style, scale);
+        match *self {
+            AnyMarker::Builtin(marker) => {
+                if large_markers(style) {
+                    (marker.large)(&mut canvas, units)
+                }
+                else {
+                    (marker.small)(&mut canvas, units)
+                }
+            }
+            AnyMarker::Svg(ref marker) => {
+                marker.render(&mut canvas, units.measures)
+            }
+        }
+    }
+}
+
+
+/// Returns whether the large version of built-in markers is used.
+fn large_markers(style: &Style) -> bool {
+    style.detail() >= 4.
+}
+
+
+//------------ Legend --------------------------------------------------------
+
+/// Returns the names of all built-in markers in alphabetical order.
+///
+/// Casing markers are left out since they only make sense underneath
+/// their marker.
+pub fn builtin_names() -> Vec<&'static str> {
+    let mut res = OLD_MARKERS.keys().copied().filter(|name| {
+        !name.ends_with(".casing")
+    }).collect::<Vec<_>>();
+    res.sort_unstable();
+    res
+}
+
+/// Renders a built-in marker for the legend.
+///
+/// The marker is attached to `point` in canvas coordinates and points
+/// downwards. Does nothing if there is no built-in marker by that name.
+pub fn render_legend(
+    name: &str, class: &Railway, point: kurbo::Point,
+    style: &Style, canvas: &mut Canvas,
+) {
+    if let Some(marker) = OLD_MARKERS.get(name) {
+        AnyMarker::Builtin(*marker).render(
+            point, 0., class, 1., style, canvas
+        )
+    }
+}
+
+
+//------------ Units ---------------------------------------------------------
+
+/// The units passed to a marker’s render function.
+///
+/// This derefs to the measures scaled for the marker and additionally
+/// provides the colors a marker may need besides its primary color.
+#[derive(Clone, Copy, Debug)]
+struct Units {
+    measures: Measures,
+    casing: Color,
+}
+
+impl Units {
+    fn new(style: &Style, scale: f64) -> Self {
+        Units {
+            measures: style.measures() * scale,
+            casing: style.casing_color(),
+        }
+    }
+
+    fn casing_color(self) -> Color {
+        self.casing
+    }
+}
+
+impl ops::Deref for Units {
+    type Target = Measures;
+
+    fn deref(&self) -> &Measures {
+        &self.measures
+    }
+}
+
+
 //------------ Marker --------------------------------------------------------
 
 #[derive(Clone, Copy)]
@@ -167,7 +585,7 @@ struct Marker {
 }
 
 type RenderFn = &'static (
-    dyn Fn(&mut Group, Measures) + Sync
+    dyn Fn(&mut Group, Units) + Sync
 );
 
 
@@ -208,7 +626,7 @@ macro_rules! make_marker {
 
 markers! {
     ("de.abzw", "junction") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let st = u.main_track();
             let sw = 0.9 * u.sw();
             let sh = 0.9 * u.sh();
@@ -225,13 +643,13 @@ markers! {
             canvas.apply_line_width(u.main_track());
             stroke_round(canvas)
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             chevron(canvas, 0.4 * u.sw(), 0., u.sh());
             canvas.fill()
         }
     ),
     ("de.abzw.casing", "junction.casing") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             chevron(canvas,
                 0.5 * u.sw() - 0.5 * u.sp(),
                 0.25 * u.sh() + 0.5 * u.sp(), 0.75 * u.sh() - 0.5 * u.sp()
@@ -245,16 +663,16 @@ markers! {
             canvas.move_to(0., 0.5 * u.sh() + u.sp());
             canvas.line_to(0., u.sh() - 0.5 * u.sp());
             canvas.apply_line_width(u.sp() * 2.);
-            canvas.apply(CASING_COLOR);
+            canvas.apply(u.casing_color());
             //canvas.apply(Operator::DestinationOut);
             stroke_round(canvas);
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             junction_small_casing(canvas, u)
         }
     ),
     ("de.abzw.first", "junction.first") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(-0.5 * u.sw() + 0.5 * u.sp(), 0.75 * u.sh() - 0.5 * u.sp());
             canvas.line_to(0., 0.25 * u.sh() + 0.5 * u.sp());
             canvas.line_to(0., 0.);
@@ -266,7 +684,7 @@ markers! {
         }
     ),
     ("de.abzw.second", "junction.second") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(0.5 * u.sw() - 0.5 * u.sp(), 0.75 * u.sh() - 0.5 * u.sp());
             canvas.line_to(0., 0.25 * u.sh() + 0.5 * u.sp());
             canvas.line_to(0., 0.);
@@ -279,7 +697,7 @@ markers! {
     ),
 
     ("de.anst") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(0., 0.);
             canvas.line_to(0., u.sh() - 0.5 * u.sp());
             canvas.move_to(-0.3 * u.sw(), u.sh() - 0.5 * u.sp());
@@ -287,7 +705,7 @@ markers! {
             canvas.apply_line_width(u.sp());
             stroke_round(canvas)
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(0., 0.);
             canvas.line_to(0., u.sh() - 0.75 * u.sp());
             canvas.move_to(-0.3 * u.sw(), u.sh() - 0.75 * u.sp());
@@ -298,7 +716,7 @@ markers! {
     ),
 
     ("de.aw") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.apply_line_width(2. * u.sp());
             canvas.new_path();
             canvas.arc(0., 0.5 * u.sh(), 0.5 * u.sh() - u.sp(), 0., 2. * PI);
@@ -314,7 +732,7 @@ markers! {
     ),
 
     ("de.awanst") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(0., 0.);
             canvas.line_to(0., u.sh() - 0.5 * u.sp());
 
@@ -325,7 +743,7 @@ markers! {
             canvas.apply_line_width(u.sp());
             stroke_round(canvas)
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(0., 0.);
             canvas.line_to(0., u.sh() - 0.75 * u.sp());
             canvas.move_to(-0.3 * u.sw(), u.sh() - 0.75 * u.sp());
@@ -336,7 +754,7 @@ markers! {
     ),
 
     ("de.bbf", "servicestation") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let hsp = 0.5 * u.sp();
             canvas.move_to(-0.5 * u.sw() + hsp, 2.5 * u.sp());
             canvas.line_to(-0.5 * u.sw() + hsp, u.sh() - 0.5 * u.sp());
@@ -353,7 +771,7 @@ markers! {
             canvas.close_path();
             canvas.fill()
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             stop_small(canvas, u);
             canvas.move_to(-0.5 * u.sw(), 0.);
             canvas.line_to(-0.5 * u.sw(), u.sh());
@@ -365,15 +783,15 @@ markers! {
     ),
 
     ("de.bf", "de.kbf", "station") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             station(canvas, u)
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             station_small(canvas, u)
         }
     ),
     ("de.kbf") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let hsp = 0.5 * u.sp();
             canvas.move_to(-0.5 * u.sw() + hsp, 2.5 * u.sp());
             canvas.line_to(-0.5 * u.sw() + hsp, u.sh() - hsp);
@@ -392,20 +810,20 @@ markers! {
             canvas.close_path();
             canvas.fill()
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             station_xsmall(canvas, u)
         }
     ),
     ("de.bf.casing", "station.casing") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             station_casing(canvas, u)
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             station_small_casing(canvas, u)
         }
     ),
     ("de.bf.first", "station.first") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let hsp = 0.5 * u.sp();
             canvas.move_to(-0.5 * u.sw() + hsp, 2.5 * u.sp());
             canvas.line_to(-0.5 * u.sw() + hsp, u.sh() - hsp);
@@ -426,7 +844,7 @@ markers! {
         }
     ),
     ("de.bf.second", "station.second") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let hsp = 0.5 * u.sp();
             canvas.move_to(0.5 * u.sw() - hsp, 2.5 * u.sp());
             canvas.line_to(0.5 * u.sw() - hsp, u.sh() - hsp);
@@ -448,7 +866,7 @@ markers! {
     ),
 
     ("de.bft") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             chevron(canvas,
                 0.5 * u.sw() - 0.5 * u.sp(),
                 0.25 * u.sh() + 0.5 * u.sp(), 0.75 * u.sh() - 0.5 * u.sp()
@@ -465,7 +883,7 @@ markers! {
             canvas.close_path();
             canvas.fill();
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             /*
             canvas.move_to(-0.01 * u.sw(), 0.);
             canvas.line_to(-0.5 * u.sw(), 0.7 * u.sh());
@@ -486,7 +904,7 @@ markers! {
         }
     ),
     ("de.bft.casing") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             chevron(canvas,
                 0.5 * u.sw() - 0.5 * u.sp(),
                 0.25 * u.sh() + 0.5 * u.sp(), 0.75 * u.sh() - 0.5 * u.sp()
@@ -500,12 +918,12 @@ markers! {
             canvas.move_to(0., 0.5 * u.sh() + u.sp());
             canvas.line_to(0., u.sh() - 0.5 * u.sp());
             canvas.apply_line_width(u.sp() * 2.);
-            canvas.apply(CASING_COLOR);
+            canvas.apply(u.casing_color());
             stroke_round(canvas)
         }
     ),
     ("de.bft.first") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(0., 0.);
             canvas.line_to(0., 0.25 * u.sh());
             canvas.line_to(-0.5 * u.sw() + 0.5 * u.sp(), 0.75 * u.sh() - 0.5 * u.sp());
@@ -521,7 +939,7 @@ markers! {
         }
     ),
     ("de.bft.second") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(0., 0.);
             canvas.line_to(0., 0.25 * u.sh());
             canvas.line_to(0.5 * u.sw() - 0.5 * u.sp(), 0.75 * u.sh() - 0.5 * u.sp());
@@ -538,7 +956,7 @@ markers! {
     ),
 
     ("de.bk", "block") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             chevron(canvas,
                 0.5 * u.sw() - 0.5 * u.sp(),
                 0.3 * u.sh() + 0.5 * u.sp(), 0.8 * u.sh() - 0.5 * u.sp()
@@ -548,7 +966,7 @@ markers! {
             canvas.apply_line_width(u.sp());
             stroke_round(canvas)
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(0., 0.);
             canvas.line_to(-0.4 * u.sw(), u.sh());
             canvas.line_to(-0.4 * u.sw() + 0.8 * u.sp(), u.sh());
@@ -567,7 +985,7 @@ markers! {
         }
     ),
     ("de.bk.casing", "block.casing") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             chevron(canvas,
                 0.5 * u.sw() - 0.5 * u.sp(),
                 0.3 * u.sh() + 0.5 * u.sp(), 0.8 * u.sh() - 0.5 * u.sp()
@@ -577,7 +995,7 @@ markers! {
             canvas.apply_line_width(u.sp() * 2.);
             stroke_round(canvas)
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             chevron(canvas,
                 0.4 * u.sw() - 0.5 * u.sp(), 0.5 * u.sp(), u.sh() - 0.5 * u.sp(),
             );
@@ -586,7 +1004,7 @@ markers! {
         }
     ),
     ("de.bk.first", "block.first") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(0., 0.);
             canvas.line_to(0., 0.3 * u.sh());
             canvas.line_to(-0.5 * u.sw() + 0.5 * u.sp(), 0.8 * u.sh() - 0.5 * u.sp());
@@ -595,7 +1013,7 @@ markers! {
         }
     ),
     ("de.bk.second", "block.second") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(0., 0.);
             canvas.line_to(0., 0.3 * u.sh());
             canvas.line_to(0.5 * u.sw() - 0.5 * u.sp(), 0.8 * u.sh() - 0.5 * u.sp());
@@ -605,7 +1023,7 @@ markers! {
     ),
 
     ("de.bw") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.apply_line_width(2. * u.sp());
             canvas.new_path();
             canvas.arc(0., 0.5 * u.sh(), 0.5 * u.sh() - u.sp(), 0., 2. * PI);
@@ -623,7 +1041,7 @@ markers! {
     ),
 
     ("de.dirgr") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let r = 0.8 * u.dt();
             canvas.apply_line_width(u.sp());
             canvas.move_to(0., -0.5 * u.dt());
@@ -634,7 +1052,7 @@ markers! {
             canvas.arc(0., 3. * r, 0.5 * r, 0., 2. * PI);
             canvas.fill()
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let r = 0.25 * u.sh();
             canvas.arc(0., 3. * r, r, 0., 2. * PI);
             canvas.fill();
@@ -646,7 +1064,7 @@ markers! {
     ),
 
     ("de.dkst") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let dt = u.dt();
             let sw = 0.9 * u.sw();
             let sh = 0.9 * u.sh();
@@ -664,7 +1082,7 @@ markers! {
             canvas.apply_line_width(u.main_track());
             stroke_round(canvas)
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             chevron(canvas,
                 0.4 * u.sw() - 0.5 * u.sp(), 0.5 * u.sp(), u.sh() - 0.5 * u.sp(),
             );
@@ -674,7 +1092,7 @@ markers! {
     ),
 
     ("de.est") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.apply_line_width(2. * u.sp());
             canvas.new_path();
             canvas.arc(0., 0.5 * u.sh(), 0.5 * u.sh() - u.sp(), 0., 2. * PI);
@@ -690,13 +1108,13 @@ markers! {
     ),
 
     ("de.exbf") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(-0.5 * u.sw() + 0.5 * u.sp(), u.sh() + 1.5 * u.sp());
             canvas.line_to(0.5 * u.sw() - 0.5 * u.sp(), u.sh() + 1.5 * u.sp());
             canvas.apply_line_width(u.sp());
             stroke_round(canvas)
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(-0.5 * u.sw() + 0.5 * u.sp(), u.sh() + 1. * u.sp());
             canvas.line_to(0.5 * u.sw() - 0.5 * u.sp(), u.sh() + 1. * u.sp());
             canvas.apply_line_width(u.sp());
@@ -705,7 +1123,7 @@ markers! {
     ),
 
     ("de.gbf", "goodsstation") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(-0.5 * u.sw(), 2. * u.sp());
             canvas.line_to(-0.5 * u.sw(), u.sh() - 2. * u.sp());
             canvas.line_to(0., u.sh());
@@ -737,7 +1155,7 @@ markers! {
             canvas.fill()
             */
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(-0.5 * u.sw(), 0.);
             canvas.line_to(-0.5 * u.sw(), 0.4 * u.sh());
             canvas.line_to(0., u.sh());
@@ -749,31 +1167,31 @@ markers! {
     ),
 
     ("de.hp", "de.khp", "stop") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             stop(canvas, u)
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             stop_small(canvas, u)
         }
     ),
     ("de.khp") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             stop(canvas, u)
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             stop_xsmall(canvas, u)
         }
     ),
     ("de.hp.casing", "stop.casing") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             station_casing(canvas, u)
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             station_small_casing(canvas, u)
         }
     ),
     ("de.hp.first", "stop.first") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let hsp = 0.5 * u.sp();
             canvas.move_to(-0.5 * u.sw() + hsp, 2.5 * u.sp());
             canvas.line_to(-0.5 * u.sw() + hsp, u.sh() - 0.5 * u.sp());
@@ -785,7 +1203,7 @@ markers! {
         }
     ),
     ("de.hp.second", "stop.second") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let hsp = 0.5 * u.sp();
             canvas.move_to(0.5 * u.sw() - hsp, 2.5 * u.sp());
             canvas.line_to(0.5 * u.sw() - hsp, u.sh() - 0.5 * u.sp());
@@ -798,7 +1216,7 @@ markers! {
     ),
 
     ("de.hp.bft") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             stop(canvas, u);
             chevron(canvas,
                 0.5 * u.sw() - 0.5 * u.sp(),
@@ -816,7 +1234,7 @@ markers! {
     ),
 
     ("de.hst") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let hsp = 0.5 * u.sp();
             canvas.move_to(-0.5 * u.sw() + hsp, 2.5 * u.sp());
             canvas.line_to(-0.5 * u.sw() + hsp, u.sh() - 0.5 * u.sp());
@@ -829,7 +1247,7 @@ markers! {
             canvas.apply_line_width(u.sp());
             canvas.stroke()
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             stop_small(canvas, u);
             canvas.move_to(0., 1.75 * u.sp());
             canvas.line_to(0., u.sh() - 0.5 * u.sp());
@@ -839,7 +1257,7 @@ markers! {
     ),
 
     ("de.inbf") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(-0.5 * u.sw(), 2. * u.sp());
             canvas.line_to(-0.5 * u.sw(), 4. * u.dt() - 2. * u.sp());
             canvas.line_to(0.5 * u.sw(), 4. * u.dt() - 2. * u.sp());
@@ -847,7 +1265,7 @@ markers! {
             canvas.close_path();
             canvas.fill()
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(-0.5 * u.sw(), 0.);
             canvas.line_to(-0.5 * u.sw(), 2. * u.dt());
             canvas.line_to(0.5 * u.sw(), 2. * u.dt());
@@ -858,7 +1276,7 @@ markers! {
     ),
 
     ("de.ldst") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let hsp = 0.5 * u.sp();
             canvas.move_to(-0.5 * u.sw() + hsp, 2.5 * u.sp());
             canvas.line_to(-0.5 * u.sw() + hsp, 0.7 * u.sh());
@@ -869,7 +1287,7 @@ markers! {
             canvas.apply_line_width(u.sp());
             canvas.stroke()
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let hsp = 0.5 * u.sp();
             canvas.move_to(-0.5 * u.sw() + hsp, 1.75 * u.sp());
             canvas.line_to(-0.5 * u.sw() + hsp, 0.6 * u.sh());
@@ -883,7 +1301,7 @@ markers! {
     ),
 
     ("de.lgr") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let r = 0.8 * u.dt();
             canvas.arc(0., 3. * r, r, 0., 2. * PI);
             canvas.fill();
@@ -892,7 +1310,7 @@ markers! {
             canvas.line_to(0., 2. * r);
             canvas.stroke();
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let r = 0.25 * u.sh();
             canvas.arc(0., 3. * r, r, 0., 2. * PI);
             canvas.fill();
@@ -904,7 +1322,7 @@ markers! {
     ),
 
     ("de.stw", "signalbox") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.apply_line_width(u.sp());
             canvas.move_to(0., 0.);
             canvas.line_to(0., 2. * u.dt());
@@ -915,7 +1333,7 @@ markers! {
     ),
 
     ("de.stw.casing", "signalbox.casing") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.apply_line_width(1.5 * u.sp());
             canvas.move_to(0., 0.);
             canvas.line_to(0., 2. * u.dt());
@@ -927,7 +1345,7 @@ markers! {
     ),
 
     ("de.uest", "crossover") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let dt = u.dt();
             let sw = 0.9 * u.sw();
             let sh = 0.9 * u.sh();
@@ -945,7 +1363,7 @@ markers! {
             canvas.apply_line_width(u.main_track());
             stroke_round(canvas)
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(0., 0.);
             canvas.line_to(-0.4 * u.sw(), u.sh());
             canvas.line_to(0.4 * u.sw(), u.sh());
@@ -965,7 +1383,7 @@ markers! {
         }
     ),
     ("de.uest.casing", "crossover.casing") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             chevron(canvas,
                 0.5 * u.sw() - 0.5 * u.sp(),
                 0.3 * u.sh() + 0.5 * u.sp(), 0.8 * u.sh() - 0.5 * u.sp()
@@ -976,12 +1394,12 @@ markers! {
             canvas.apply(Operator::DestinationOut);
             stroke_round(canvas);
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             junction_small_casing(canvas, u)
         }
     ),
     ("de.uest.first", "crossover.first") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(
                 -0.5 * u.sw() + 0.5 * u.sp(), 0.8 * u.sh() - 0.5 * u.sp()
             );
@@ -993,7 +1411,7 @@ markers! {
         }
     ),
     ("de.uest.second", "crossover.second") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(
                 0.5 * u.sw() - 0.5 * u.sp(), 0.8 * u.sh() - 0.5 * u.sp()
             );
@@ -1006,7 +1424,7 @@ markers! {
     ),
 
     ("de.zst", "de.kzst") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             stop(canvas, u);
             canvas.arc(
                 0., u.sh() - 0.5 * (u.sh() - 2. * u.sp()),
@@ -1015,13 +1433,13 @@ markers! {
             );
             canvas.fill()
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             stop_small(canvas, u)
         }
     ),
 
     ("de.tram.inbf") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let dt = u.light_double() + u.light_track() * 0.5;
 
             canvas.move_to(-dt, -dt);
@@ -1034,7 +1452,7 @@ markers! {
     ),
 
     ("de.tram.inhp") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let dt = u.light_double();
 
             canvas.move_to(-dt, -dt);
@@ -1048,7 +1466,7 @@ markers! {
     ),
 
     ("ltd-stop") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let hsp = 0.5 * u.sp();
             stop(canvas, u);
             canvas.move_to(-0.5 * u.sw() + hsp, 2.5 * u.sp());
@@ -1056,13 +1474,13 @@ markers! {
             canvas.apply_line_width(u.sp());
             stroke_round(canvas)
         },
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             stop_small(canvas, u)
         }
     ),
 
     ("ref") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.apply_line_width(u.sp());
             canvas.move_to(0., 0.);
             canvas.line_to(0., 0.5 * u.sh());
@@ -1070,7 +1488,7 @@ markers! {
         }
     ),
     ("refdt") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.apply_line_width(u.sp());
             canvas.move_to(0., 0.);
             canvas.line_to(0., u.dt());
@@ -1078,7 +1496,7 @@ markers! {
         }
     ),
     ("statdt") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.apply_line_width(u.main_track());
             canvas.move_to(0., 0.);
             canvas.line_to(0., u.dt());
@@ -1087,7 +1505,7 @@ markers! {
     ),
 
     ("tunnel.l") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(0., 0.);
             canvas.move_to(0., 0.);
             canvas.line_to(1.0 * u.dt(), 0.0);
@@ -1097,7 +1515,7 @@ markers! {
         }
     ),
     ("tunnel.r") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.move_to(0., 0.);
             canvas.line_to(-1.0 * u.dt(), 0.0);
             canvas.line_to(-1.75 * u.dt(), -0.75 * u.dt());
@@ -1106,7 +1524,7 @@ markers! {
         }
     ),
     ("tunnel.dt") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             canvas.apply_line_width(u.sp());
             canvas.move_to(0., 0.);
             canvas.line_to(0., u.dt());
@@ -1115,7 +1533,7 @@ markers! {
     ),
 
     ("sigr") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let u = u.main_offset() * 0.3;
             canvas.move_to(0., 0.);
             canvas.line_to(0., 2. * u);
@@ -1126,7 +1544,7 @@ markers! {
         }
     ),
     ("sigl") => (
-        |canvas: &mut Group, u: Measures| {
+        |canvas: &mut Group, u: Units| {
             let u = u.main_offset() * 0.3;
             canvas.move_to(0., 0.);
             canvas.line_to(0., -2. * u);
@@ -1142,7 +1560,7 @@ markers! {
 //------------ Component Functions -------------------------------------------
 
 fn station(
-    canvas: &mut Group, u: Measures
+    canvas: &mut Group, u: Units
 ) {
     canvas.move_to(-0.5 * u.sw(), 0.);
     canvas.line_to(-0.5 * u.sw(), u.sh());
@@ -1154,7 +1572,7 @@ fn station(
 
 
 fn station_small(
-    canvas: &mut Group, u: Measures
+    canvas: &mut Group, u: Units
 ) {
     canvas.move_to(-0.5 * u.sw(), 0.);
     canvas.line_to(-0.5 * u.sw(), u.sh());
@@ -1172,7 +1590,7 @@ fn station_small(
 }
 
 fn station_xsmall(
-    canvas: &mut Group, u: Measures
+    canvas: &mut Group, u: Units
 ) {
     let sp = 0.8 * u.sp();
     let hsp = 0.5 * u.sp();
@@ -1194,7 +1612,7 @@ fn station_xsmall(
 }
 
 fn station_casing(
-    canvas: &mut Group, u: Measures
+    canvas: &mut Group, u: Units
 ) {
     let hsp = 0.5 * (u.sp() * 2. - u.sp());
     canvas.move_to(-0.5 * u.sw() - hsp, 2.5 * u.sp() - hsp);
@@ -1202,12 +1620,12 @@ fn station_casing(
     canvas.line_to(0.5 * u.sw() + hsp, u.sh() + hsp);
     canvas.line_to(0.5 * u.sw() + hsp, 2.5 * u.sp() - hsp);
     canvas.close_path();
-    canvas.apply(CASING_COLOR);
+    canvas.apply(u.casing_color());
     canvas.fill();
 }
 
 fn station_small_casing(
-    canvas: &mut Group, u: Measures
+    canvas: &mut Group, u: Units
 ) {
     let hsp = 0.5 * u.sp();
     canvas.move_to(-0.5 * u.sw(), 1.75 * u.sp() - hsp);
@@ -1215,7 +1633,7 @@ fn station_small_casing(
     canvas.line_to(0.5 * u.sw(), u.sh());
     canvas.line_to(0.5 * u.sw(), 1.75 * u.sp() - hsp);
     canvas.close_path();
-    canvas.apply(CASING_COLOR);
+    canvas.apply(u.casing_color());
     canvas.apply_line_width(u.sp() * 2.);
     /*
     top_ds_rect(canvas,
@@ -1231,7 +1649,7 @@ fn station_small_casing(
 }
 
 fn stop(
-    canvas: &mut Group, u: Measures
+    canvas: &mut Group, u: Units
 )  {
     let hsp = 0.5 * u.main_track();
     canvas.move_to(-0.5 * u.sw() + hsp, 0.);
@@ -1245,7 +1663,7 @@ fn stop(
 }
 
 fn stop_small(
-    canvas: &mut Group, u: Measures
+    canvas: &mut Group, u: Units
 ) {
     let hsp = 0.5 * u.sp();
     canvas.move_to(-0.5 * u.sw() + hsp, 0.);
@@ -1268,7 +1686,7 @@ fn stop_small(
 }
 
 fn stop_xsmall(
-    canvas: &mut Group, u: Measures
+    canvas: &mut Group, u: Units
 ) {
     let sp = 0.8 * u.sp();
     let hsp = 0.5 * sp;
@@ -1286,7 +1704,7 @@ fn stop_xsmall(
 }
 
 fn junction_small_casing(
-    canvas: &mut Group, u: Measures
+    canvas: &mut Group, u: Units
 )  {
     chevron(canvas, 0.4 * u.sw() - 0.5 * u.sp(), 0., u.sh() - 0.5 * u.sp());
     canvas.apply_line_width(u.sp() * 2.);
@@ -1,7 +1,15 @@
-use femtomap::render::Canvas;
+use std::fmt;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::sync::Arc;
+use femtomap::path::{Trace, Transform};
+use femtomap::render::{Canvas, Color, LineWidth, Outline};
 use femtomap::world::Rect;
+use kurbo::{Line, ParamCurveNearest, PathEl, Point};
+use serde::Serialize;
+use crate::config::MeasuresConfig;
 use crate::railway::class;
-use crate::railway::style::Style;
+use crate::railway::style::{self, Style};
 
 
 //------------ Submodules with actual features -------------------------------
@@ -12,6 +20,8 @@ pub mod dot;
 pub mod guide;
 pub mod label;
 pub mod marker;
+pub mod route;
+pub mod svgmarker;
 pub mod track;
 
 
@@ -30,23 +40,80 @@ pub struct Store {
 
     /// Borders
     pub borders: FeatureSet,
+
+    /// The extent of each region.
+    pub regions: BTreeMap<String, RegionExtent>,
 }
 
-#[derive(Default)]
 pub struct StoreBuilder {
     pub railway: FeatureSetBuilder,
     pub line_labels: FeatureSetBuilder,
     pub tt_labels: FeatureSetBuilder,
     pub borders: FeatureSetBuilder,
+
+    /// The measures the features will be rendered with.
+    ///
+    /// Features that need to know their size in storage coordinates to
+    /// determine their bounds use these.
+    measures: MeasuresConfig,
+}
+
+impl Default for StoreBuilder {
+    fn default() -> Self {
+        StoreBuilder {
+            railway: FeatureSetBuilder::new("railway"),
+            line_labels: FeatureSetBuilder::new("line_labels"),
+            tt_labels: FeatureSetBuilder::new("tt_labels"),
+            borders: FeatureSetBuilder::new("borders"),
+            measures: MeasuresConfig::default(),
+        }
+    }
 }
 
 impl StoreBuilder {
+    /// Sets the measures the features will be rendered with.
+    pub fn set_measures(&mut self, measures: MeasuresConfig) {
+        self.measures = measures;
+    }
+
+    /// Returns the measures the features will be rendered with.
+    pub fn measures(&self) -> &MeasuresConfig {
+        &self.measures
+    }
+
+    /// Sets the region all features inserted from now on belong to.
+    pub fn set_region(&mut self, name: &str) {
+        self.railway.set_region(name);
+        self.line_labels.set_region(name);
+        self.tt_labels.set_region(name);
+        self.borders.set_region(name);
+    }
+
+    /// Restricts the features inserted from now on to an area.
+    ///
+    /// The area is given in storage coordinates. See
+    /// [`FeatureSetBuilder::set_limit`] for details.
+    pub fn set_limit(&mut self, limit: kurbo::Rect) {
+        self.railway.set_limit(limit);
+        self.line_labels.set_limit(limit);
+        self.tt_labels.set_limit(limit);
+        self.borders.set_limit(limit);
+    }
+
     pub fn finalize(self) -> Store {
+        let mut regions = BTreeMap::<_, RegionExtent>::new();
+        let mut finalize = |set: FeatureSetBuilder| {
+            for (name, extent) in set.regions {
+                regions.entry(name).or_default().merge(extent);
+            }
+            set.features.finalize()
+        };
         Store {
-            railway: self.railway.finalize(),
-            line_labels: self.line_labels.finalize(),
-            tt_labels: self.tt_labels.finalize(),
-            borders: self.borders.finalize(),
+            railway: finalize(self.railway),
+            line_labels: finalize(self.line_labels),
+            tt_labels: finalize(self.tt_labels),
+            borders: finalize(self.borders),
+            regions,
         }
     }
 }
@@ -55,7 +122,177 @@ impl StoreBuilder {
 //------------ FeatureSet and FeatureSetBuilder ------------------------------
 
 pub type FeatureSet = femtomap::feature::FeatureSet<AnyFeature>;
-pub type FeatureSetBuilder = femtomap::feature::FeatureSetBuilder<AnyFeature>;
+
+/// A builder for a feature set.
+///
+/// In addition to collecting the features, the builder keeps track of the
+/// extent of the regions the features are loaded from.
+pub struct FeatureSetBuilder {
+    /// The name of the feature set used in feature identifiers.
+    name: &'static str,
+
+    /// The actual feature set builder.
+    features: femtomap::feature::FeatureSetBuilder<AnyFeature>,
+
+    /// The name of the region currently being loaded.
+    region: Option<Arc<str>>,
+
+    /// The extent of the regions loaded so far.
+    ///
+    /// Only features that were actually inserted count.
+    regions: HashMap<String, RegionExtent>,
+
+    /// The index of the next feature of each region.
+    ///
+    /// Dropped features count here, too, so that identifiers don’t depend
+    /// on the limit or zoom range.
+    indexes: HashMap<String, usize>,
+
+    /// The area outside of which features are dropped.
+    limit: Option<kurbo::Rect>,
+}
+
+impl FeatureSetBuilder {
+    fn new(name: &'static str) -> Self {
+        FeatureSetBuilder {
+            name,
+            features: Default::default(),
+            region: None,
+            regions: HashMap::new(),
+            indexes: HashMap::new(),
+            limit: None,
+        }
+    }
+
+    fn set_region(&mut self, name: &str) {
+        // Regions are listed even if all their features are dropped.
+        self.regions.entry(name.into()).or_default();
+        self.region = Some(name.into());
+    }
+
+    /// Drops features inserted from now on if they are outside an area.
+    ///
+    /// The area is given in storage coordinates. Features that overlap
+    /// the area only partially are kept. Dropped features don’t count
+    /// towards the extent of their region but the identifiers of the
+    /// features that are kept are the same as without a limit.
+    fn set_limit(&mut self, limit: kurbo::Rect) {
+        self.limit = Some(limit);
+    }
+
+    /// Inserts a feature for the given detail range and layer.
+    ///
+    /// If the railway class of the feature has a layer, it is used instead
+    /// of `layer`. If the class limits the zoom levels, the detail range is
+    /// narrowed to the store scales of these levels. If nothing is left of
+    /// the range, the feature is dropped. Since several zoom levels share a
+    /// store scale, the zoom levels themselves are checked when rendering.
+    ///
+    /// If a region is being loaded, the feature is given an identifier
+    /// made from the region’s name, the name of the feature set, and the
+    /// number of features of the region inserted into the set before it,
+    /// including dropped ones. It stays the same across reloads as long as
+    /// the region doesn’t change. Only features that are kept are added to
+    /// the region’s extent.
+    pub fn insert(
+        &mut self,
+        feature: impl Into<AnyFeature>,
+        detail: (f64, f64),
+        layer: i16,
+    ) {
+        let mut feature = feature.into();
+        let layer = feature.feature.class().and_then(|class| {
+            class.layer()
+        }).unwrap_or(layer);
+        let detail = match feature.feature.class() {
+            Some(class) => Self::clamp_detail(detail, class.zoom_range()),
+            None => Some(detail),
+        };
+        if let Some(region) = self.region.as_ref() {
+            let index = self.indexes.entry(
+                region.as_ref().into()
+            ).or_default();
+            feature.id = Some(FeatureId {
+                region: region.clone(), set: self.name, index: *index
+            });
+            *index += 1;
+        }
+        let detail = match detail {
+            Some(detail) => detail,
+            None => return,
+        };
+        let bounds = kurbo::Rect::from(feature.feature.storage_bounds());
+        if let Some(limit) = self.limit {
+            if
+                bounds.x1 < limit.x0 || bounds.x0 > limit.x1
+                || bounds.y1 < limit.y0 || bounds.y0 > limit.y1
+            {
+                return
+            }
+        }
+        if let Some(region) = self.region.as_ref() {
+            self.regions.entry(
+                region.as_ref().into()
+            ).or_default().add(bounds);
+        }
+        feature.detail = detail;
+        self.features.insert(
+            feature, (detail.0 - style::MAX_STORE_SCALE_STEP, detail.1), layer
+        )
+    }
+
+    /// Narrows a detail range to a range of zoom levels.
+    ///
+    /// Returns `None` if the resulting range is empty.
+    fn clamp_detail(
+        (mut low, mut high): (f64, f64),
+        (min_zoom, max_zoom): (Option<u8>, Option<u8>),
+    ) -> Option<(f64, f64)> {
+        // Leave a little room since the range is compared against the
+        // exact store scale of the zoom level.
+        if let Some(zoom) = min_zoom {
+            low = low.max(style::zoom_store_scales(zoom).0 - 0.01);
+        }
+        if let Some(zoom) = max_zoom {
+            high = high.min(style::zoom_store_scales(zoom).1 + 0.01);
+        }
+        (low <= high).then_some((low, high))
+    }
+}
+
+
+//------------ RegionExtent --------------------------------------------------
+
+/// The extent of a region.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RegionExtent {
+    /// The union of the storage bounds of all features of the region.
+    ///
+    /// This is `None` if the region has no features.
+    pub bounds: Option<kurbo::Rect>,
+
+    /// The number of features in the region.
+    pub count: usize,
+}
+
+impl RegionExtent {
+    /// Adds a feature with the given bounds.
+    fn add(&mut self, bounds: kurbo::Rect) {
+        self.bounds = Some(match self.bounds {
+            Some(current) => current.union(bounds),
+            None => bounds,
+        });
+        self.count += 1;
+    }
+
+    fn merge(&mut self, other: RegionExtent) {
+        self.bounds = match (self.bounds, other.bounds) {
+            (Some(left), Some(right)) => Some(left.union(right)),
+            (left, right) => left.or(right),
+        };
+        self.count += other.count;
+    }
+}
 
 
 //------------ Feature -------------------------------------------------------
@@ -65,6 +302,52 @@ pub trait Feature {
 
     fn group(&self) -> Group;
 
+    /// Returns the railway class of the feature if it has one.
+    ///
+    /// The class is used to hide features that didn’t exist in the year
+    /// selected by the style.
+    fn class(&self) -> Option<&class::Railway> {
+        None
+    }
+
+    /// Returns identifying information about the feature.
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo::new(self.group(), self.class())
+    }
+
+    /// Returns the point in canvas coordinates the feature is attached to.
+    ///
+    /// This is used for picking and to mark the point in the bounds overlay
+    /// of proof mode. Features without a single such point return `None`.
+    fn anchor(&self, _style: &Style) -> Option<Point> {
+        None
+    }
+
+    /// Returns the length of the feature in canvas units.
+    ///
+    /// Only features that run along a line, such as track, have a length.
+    /// The default returns `None`.
+    fn length(&self, _style: &Style) -> Option<f64> {
+        None
+    }
+
+    /// Returns whether picking at a point selects the feature.
+    ///
+    /// The point is given in storage coordinates and the radius in canvas
+    /// units. It is only called for features whose bounds are near the
+    /// point. The default checks whether the anchor is within the radius
+    /// and accepts features without an anchor. Features running along a
+    /// line should check the distance to their outline and features
+    /// covering an area whether the point is inside.
+    fn hit(&self, point: Point, radius: f64, style: &Style) -> bool {
+        match self.anchor(style) {
+            Some(anchor) => {
+                (style.transform() * point).distance(anchor) <= radius
+            }
+            None => true
+        }
+    }
+
     fn shape(
         &self, style: &Style, canvas: &Canvas
     ) -> AnyShape;
@@ -76,6 +359,23 @@ pub trait Shape<'a> {
     fn render(&self, stage: Stage, style: &Style, canvas: &mut Canvas);
 
     fn stages(&self) -> StageSet;
+
+    /// Returns the box in canvas coordinates used for collision detection.
+    ///
+    /// Shapes that return `None`, which is the default, never collide.
+    fn collision_box(&self) -> Option<kurbo::Rect> {
+        None
+    }
+
+    /// Returns the order of the shape within a stage.
+    ///
+    /// Within a group, shapes with a higher bias are rendered after shapes
+    /// with a lower bias for every stage. Shapes with the same bias keep
+    /// their order. The default is zero. Features can replace the bias of
+    /// their shapes via a `:zbiasN` symbol in their class.
+    fn z_bias(&self) -> i16 {
+        0
+    }
 }
 
 impl<'a, T0, T1> Shape<'a> for (T0, T1)
@@ -91,12 +391,267 @@ where
     fn stages(&self) -> StageSet {
         self.0.stages().add_set(self.1.stages())
     }
+
+    fn collision_box(&self) -> Option<kurbo::Rect> {
+        union_boxes([self.0.collision_box(), self.1.collision_box()])
+    }
+
+    fn z_bias(&self) -> i16 {
+        self.0.z_bias().max(self.1.z_bias())
+    }
+}
+
+impl<'a, T0, T1, T2> Shape<'a> for (T0, T1, T2)
+where
+    T0: Shape<'a>,
+    T1: Shape<'a>,
+    T2: Shape<'a>,
+{
+    fn render(&self, stage: Stage, style: &Style, canvas: &mut Canvas) {
+        self.0.render(stage, style, canvas);
+        self.1.render(stage, style, canvas);
+        self.2.render(stage, style, canvas);
+    }
+
+    fn stages(&self) -> StageSet {
+        self.0.stages().add_set(self.1.stages()).add_set(self.2.stages())
+    }
+
+    fn collision_box(&self) -> Option<kurbo::Rect> {
+        union_boxes([
+            self.0.collision_box(), self.1.collision_box(),
+            self.2.collision_box(),
+        ])
+    }
+
+    fn z_bias(&self) -> i16 {
+        self.0.z_bias().max(self.1.z_bias()).max(self.2.z_bias())
+    }
+}
+
+impl<'a, T0, T1, T2, T3> Shape<'a> for (T0, T1, T2, T3)
+where
+    T0: Shape<'a>,
+    T1: Shape<'a>,
+    T2: Shape<'a>,
+    T3: Shape<'a>,
+{
+    fn render(&self, stage: Stage, style: &Style, canvas: &mut Canvas) {
+        self.0.render(stage, style, canvas);
+        self.1.render(stage, style, canvas);
+        self.2.render(stage, style, canvas);
+        self.3.render(stage, style, canvas);
+    }
+
+    fn stages(&self) -> StageSet {
+        self.0.stages().add_set(self.1.stages())
+            .add_set(self.2.stages()).add_set(self.3.stages())
+    }
+
+    fn collision_box(&self) -> Option<kurbo::Rect> {
+        union_boxes([
+            self.0.collision_box(), self.1.collision_box(),
+            self.2.collision_box(), self.3.collision_box(),
+        ])
+    }
+
+    fn z_bias(&self) -> i16 {
+        self.0.z_bias().max(self.1.z_bias())
+            .max(self.2.z_bias()).max(self.3.z_bias())
+    }
+}
+
+/// A shape made of any number of shapes of the same type.
+///
+/// The shapes are rendered in order.
+impl<'a, T: Shape<'a>> Shape<'a> for Vec<T> {
+    fn render(&self, stage: Stage, style: &Style, canvas: &mut Canvas) {
+        self.iter().for_each(|shape| shape.render(stage, style, canvas))
+    }
+
+    fn stages(&self) -> StageSet {
+        self.iter().fold(StageSet::empty(), |set, shape| {
+            set.add_set(shape.stages())
+        })
+    }
+
+    fn collision_box(&self) -> Option<kurbo::Rect> {
+        union_boxes(self.iter().map(|shape| shape.collision_box()))
+    }
+
+    fn z_bias(&self) -> i16 {
+        self.iter().map(|shape| shape.z_bias()).max().unwrap_or(0)
+    }
+}
+
+impl<'a, T: Shape<'a>> Shape<'a> for Option<T> {
+    fn render(&self, stage: Stage, style: &Style, canvas: &mut Canvas) {
+        if let Some(shape) = self.as_ref() {
+            shape.render(stage, style, canvas)
+        }
+    }
+
+    fn stages(&self) -> StageSet {
+        match self.as_ref() {
+            Some(shape) => shape.stages(),
+            None => StageSet::empty(),
+        }
+    }
+
+    fn collision_box(&self) -> Option<kurbo::Rect> {
+        self.as_ref().and_then(|shape| shape.collision_box())
+    }
+
+    fn z_bias(&self) -> i16 {
+        self.as_ref().map(|shape| shape.z_bias()).unwrap_or(0)
+    }
+}
+
+/// Returns the smallest box containing all given collision boxes.
+///
+/// Returns `None` if none of the boxes are present.
+fn union_boxes(
+    boxes: impl IntoIterator<Item = Option<kurbo::Rect>>
+) -> Option<kurbo::Rect> {
+    boxes.into_iter().flatten().reduce(|left, right| left.union(right))
 }
 
 
 //------------ AnyFeature ----------------------------------------------------
 
-pub struct AnyFeature(Box<dyn Feature + Send + Sync>);
+/// The color of the bounds overlay in proof mode.
+const BOUNDS_COLOR: Color = Color::rgb(1.0, 0.0, 0.8);
+
+pub struct AnyFeature {
+    /// The actual feature.
+    feature: Box<dyn Feature + Send + Sync>,
+
+    /// The identifier of the feature.
+    ///
+    /// This is assigned when the feature is inserted into a feature set
+    /// while loading a region.
+    id: Option<FeatureId>,
+
+    /// The detail range the feature is shown for in store scale.
+    ///
+    /// The range the feature is stored with in the feature set is larger,
+    /// see [`style::MAX_STORE_SCALE_STEP`].
+    detail: (f64, f64),
+}
+
+impl AnyFeature {
+    /// Returns the group and information for picking the feature.
+    ///
+    /// Returns `None` if the feature isn’t shown with the given style.
+    ///
+    /// The point is given in storage coordinates and the radius in canvas
+    /// units.
+    pub fn pick(
+        &self, point: Point, radius: f64, style: &Style
+    ) -> Option<(Group, FeatureInfo)> {
+        if !self.is_shown(style) || !self.feature.hit(point, radius, style) {
+            return None
+        }
+        Some((self.feature.group(), self.info()))
+    }
+
+    /// Draws the bounds and anchor point of the feature.
+    ///
+    /// This is used by the bounds overlay of proof mode.
+    pub fn render_bounds(&self, style: &Style, canvas: &mut Canvas) {
+        if !self.is_shown(style) {
+            return
+        }
+        let bounds = style.transform() * kurbo::Rect::from(
+            self.feature.storage_bounds()
+        );
+        let mut sketch = canvas.sketch();
+        sketch.apply(BOUNDS_COLOR);
+        sketch.apply(LineWidth(0.5 * style.canvas_bp()));
+        sketch.apply([
+            PathEl::MoveTo(Point::new(bounds.x0, bounds.y0)),
+            PathEl::LineTo(Point::new(bounds.x1, bounds.y0)),
+            PathEl::LineTo(Point::new(bounds.x1, bounds.y1)),
+            PathEl::LineTo(Point::new(bounds.x0, bounds.y1)),
+            PathEl::ClosePath,
+        ]);
+        sketch.stroke();
+        if let Some(anchor) = self.feature.anchor(style) {
+            let size = 2. * style.canvas_bp();
+            sketch.apply([
+                PathEl::MoveTo(anchor - (size, size)),
+                PathEl::LineTo(anchor + (size, size)),
+                PathEl::MoveTo(anchor - (size, -size)),
+                PathEl::LineTo(anchor + (size, -size)),
+            ]);
+            sketch.stroke();
+        }
+    }
+
+    /// Returns a description of the feature for a dump of the map.
+    pub fn dump(&self) -> FeatureDump {
+        let bounds = kurbo::Rect::from(self.feature.storage_bounds());
+        FeatureDump {
+            info: self.info(),
+            bounds: [bounds.x0, bounds.y0, bounds.x1, bounds.y1],
+        }
+    }
+
+    /// Returns the identifier of the feature if it has one.
+    pub fn id(&self) -> Option<&FeatureId> {
+        self.id.as_ref()
+    }
+
+    /// Returns the category of the feature.
+    pub fn category(&self) -> Category {
+        self.feature.group().category
+    }
+
+    /// Returns the railway class of the feature if it has one.
+    pub fn class(&self) -> Option<&class::Railway> {
+        self.feature.class()
+    }
+
+    /// Returns the length of the feature in metres.
+    ///
+    /// Returns `None` if the feature doesn’t run along a line. The length
+    /// is measured along the center line of the feature. Since the scale
+    /// of the map changes with the latitude, the latitude of the center of
+    /// the feature is used to convert the length.
+    pub fn length(&self, style: &Style) -> Option<f64> {
+        let length = self.feature.length(style)?;
+        let center = kurbo::Rect::from(
+            self.feature.storage_bounds()
+        ).center();
+        let (_, lat) = crate::tile::lonlat(center);
+        Some(length / style.canvas_per_metre(lat))
+    }
+
+    /// Returns the identifying information of the feature.
+    pub fn info(&self) -> FeatureInfo {
+        self.feature.info().with_id(self.id.clone())
+    }
+
+    /// Returns whether the feature is shown with the given style.
+    fn is_shown(&self, style: &Style) -> bool {
+        if !style.shows_detail(self.detail) {
+            return false
+        }
+        if !style.shows_category(self.feature.group().category) {
+            return false
+        }
+        if !style.shows_class(self.feature.class()) {
+            return false
+        }
+        match self.feature.class() {
+            Some(class) => {
+                style.shows_zoom(class.zoom_range())
+                    && style.shows_year(class)
+            }
+            None => true,
+        }
+    }
+}
 
 impl femtomap::feature::Feature for AnyFeature {
     type Group = Group;
@@ -104,23 +659,34 @@ impl femtomap::feature::Feature for AnyFeature {
     type Shape<'a> = AnyShape<'a>;
 
     fn storage_bounds(&self) -> Rect {
-        self.0.storage_bounds()
+        self.feature.storage_bounds()
     }
 
     fn group(&self, _: &Self::Style) -> Self::Group {
-        self.0.group()
+        self.feature.group()
     }
 
     fn shape(
         &self, style: &Self::Style, canvas: &Canvas
     ) -> Option<Self::Shape<'_>> {
-        Some(self.0.shape(style, canvas))
+        if !self.is_shown(style) {
+            return None
+        }
+        let shape = self.feature.shape(style, canvas);
+        match self.feature.class().and_then(|class| class.z_bias()) {
+            Some(z_bias) => Some(shape.with_z_bias(z_bias)),
+            None => Some(shape)
+        }
     }
 }
 
 impl<T: Feature + Send + Sync + 'static> From<T> for AnyFeature {
     fn from(src: T) -> Self {
-        Self(Box::new(src))
+        Self {
+            feature: Box::new(src),
+            id: None,
+            detail: (f64::NEG_INFINITY, f64::INFINITY),
+        }
     }
 }
 
@@ -130,6 +696,11 @@ impl<T: Feature + Send + Sync + 'static> From<T> for AnyFeature {
 pub struct AnyShape<'a>(Box<dyn Shape<'a> + 'a>);
 
 impl<'a> AnyShape<'a> {
+    /// Creates a shape that doesn’t render anything.
+    pub fn empty() -> Self {
+        Self::from(None::<BaseFnShape<fn(&Style, &mut Canvas)>>)
+    }
+
     pub fn single_stage<F: Fn(&Style, &mut Canvas) + 'a>(
         op: F
     ) -> Self {
@@ -143,6 +714,19 @@ impl<'a> AnyShape<'a> {
     pub fn stages(&self) -> StageSet {
         self.0.stages()
     }
+
+    pub fn collision_box(&self) -> Option<kurbo::Rect> {
+        self.0.collision_box()
+    }
+
+    pub fn z_bias(&self) -> i16 {
+        self.0.z_bias()
+    }
+
+    /// Returns the shape with its z-bias replaced.
+    pub fn with_z_bias(self, z_bias: i16) -> Self {
+        Self::from(ZBiasShape { shape: self, z_bias })
+    }
 }
 
 impl<'a, T: Shape<'a> + 'a> From<T> for AnyShape<'a> {
@@ -152,6 +736,33 @@ impl<'a, T: Shape<'a> + 'a> From<T> for AnyShape<'a> {
 }
 
 
+//------------ ZBiasShape ----------------------------------------------------
+
+/// A shape with its z-bias replaced.
+struct ZBiasShape<'a> {
+    shape: AnyShape<'a>,
+    z_bias: i16,
+}
+
+impl<'a> Shape<'a> for ZBiasShape<'a> {
+    fn render(&self, stage: Stage, style: &Style, canvas: &mut Canvas) {
+        self.shape.render(stage, style, canvas)
+    }
+
+    fn stages(&self) -> StageSet {
+        self.shape.stages()
+    }
+
+    fn collision_box(&self) -> Option<kurbo::Rect> {
+        self.shape.collision_box()
+    }
+
+    fn z_bias(&self) -> i16 {
+        self.z_bias
+    }
+}
+
+
 //------------ BaseFnShape ---------------------------------------------------
 
 struct BaseFnShape<F: Fn(&Style, &mut Canvas)> {
@@ -171,9 +782,166 @@ impl<'a, F: Fn(&Style, &mut Canvas) + 'a> Shape<'a> for BaseFnShape<F> {
 }
 
 
+//------------ TraceExt ------------------------------------------------------
+
+/// Additional functionality for traces.
+pub trait TraceExt {
+    /// Returns evenly spaced positions along the trace.
+    ///
+    /// The trace is divided into parts of equal length as close to `dist`
+    /// as possible. For each part, the point in the middle and the
+    /// direction of the trace there are returned, both in canvas
+    /// coordinates. The distance is given in canvas units, too.
+    fn spaced_positions(&self, dist: f64, style: &Style) -> SpacedPositions;
+
+    /// Returns the positions at each multiple of a distance along the trace.
+    ///
+    /// The start of the trace itself is not included. The distance as well
+    /// as the returned points and directions are in canvas coordinates.
+    fn positions_every(&self, dist: f64, style: &Style) -> SpacedPositions;
+
+    /// Converts a real world distance in metres into canvas units.
+    ///
+    /// Since the scale of the map changes with the latitude, the latitude
+    /// of the center of the trace is used.
+    fn canvas_distance(&self, metres: f64, style: &Style) -> f64;
+
+    /// Returns the outline moved to the left by `dist` canvas units.
+    ///
+    /// Left is seen in the direction of the path, i.e., of increasing
+    /// chainage. This is the side of the left neighbor of a track and
+    /// where the down track of a double track line goes. For instance,
+    /// for a path running from west to east:
+    ///
+    /// ```text
+    /// outline_left(dist)     north of the path, the down track
+    /// outline(style)         west ------------------------> east
+    /// outline_right(dist)    south of the path, the up track
+    /// ```
+    ///
+    /// Femtomap offsets to the left for positive offsets, so this is the
+    /// same as `outline_offset(dist, style)`.
+    fn outline_left(&self, dist: f64, style: &Style) -> Outline;
+
+    /// Returns the outline moved to the right by `dist` canvas units.
+    ///
+    /// This is the opposite side of [`outline_left`][Self::outline_left]
+    /// and the same as `outline_offset(-dist, style)`.
+    fn outline_right(&self, dist: f64, style: &Style) -> Outline;
+
+    /// Returns whether a point is within `radius` of the trace.
+    ///
+    /// The point is given in canvas coordinates and the radius in canvas
+    /// units. The outline is approximated by a polyline.
+    fn is_near(&self, point: Point, radius: f64, style: &Style) -> bool;
+}
+
+impl TraceExt for Trace {
+    fn spaced_positions(&self, dist: f64, style: &Style) -> SpacedPositions {
+        SpacedPositions::new(&self.outline(style), dist)
+    }
+
+    fn positions_every(&self, dist: f64, style: &Style) -> SpacedPositions {
+        SpacedPositions::every(&self.outline(style), dist)
+    }
+
+    fn canvas_distance(&self, metres: f64, style: &Style) -> f64 {
+        let center = kurbo::Rect::from(self.storage_bounds()).center();
+        let (_, lat) = crate::tile::lonlat(center);
+        metres * style.canvas_per_metre(lat)
+    }
+
+    fn outline_left(&self, dist: f64, style: &Style) -> Outline {
+        self.outline_offset(dist, style)
+    }
+
+    fn outline_right(&self, dist: f64, style: &Style) -> Outline {
+        self.outline_offset(-dist, style)
+    }
+
+    fn is_near(&self, point: Point, radius: f64, style: &Style) -> bool {
+        let outline = self.outline(style);
+        let len = outline.base_arclen();
+        if len <= 0. {
+            return false
+        }
+
+        // Shorten the step ever so slightly so that rounding doesn’t make us
+        // miss the end of the outline.
+        let step = (0.5 * radius).max(0.25 * style.measures().dt());
+        let steps = (len / step).ceil();
+        let step = len / (steps + 1e-6);
+        let points = outline.iter_positions(
+            step, Some(0.)
+        ).map(|(pos, _)| pos).collect::<Vec<_>>();
+        match points.as_slice() {
+            [] => false,
+            [single] => single.distance(point) <= radius,
+            points => {
+                points.windows(2).any(|pair| {
+                    Line::new(pair[0], pair[1]).nearest(
+                        point, 0.
+                    ).distance_sq <= radius * radius
+                })
+            }
+        }
+    }
+}
+
+
+//------------ SpacedPositions -----------------------------------------------
+
+/// An iterator over evenly spaced positions along an outline.
+///
+/// This is returned by [`TraceExt::spaced_positions`].
+pub struct SpacedPositions(std::vec::IntoIter<(Point, f64)>);
+
+impl SpacedPositions {
+    fn new(outline: &Outline, dist: f64) -> Self {
+        let len = outline.base_arclen();
+        let mut res = Vec::new();
+        if dist > 0. && len > 0. {
+            // There is at least one part so that short traces get one
+            // position in their middle.
+            let count = (len / dist).round().max(1.);
+            let part = len / count;
+            let mut positions = outline.positions();
+            let mut step = 0.5 * part;
+            for _ in 0..count as usize {
+                match positions.advance(step) {
+                    Some(item) => res.push(item),
+                    None => break,
+                }
+                step = part;
+            }
+        }
+        SpacedPositions(res.into_iter())
+    }
+
+    fn every(outline: &Outline, dist: f64) -> Self {
+        let mut res = Vec::new();
+        if dist > 0. {
+            let mut positions = outline.positions();
+            while let Some(item) = positions.advance(dist) {
+                res.push(item)
+            }
+        }
+        SpacedPositions(res.into_iter())
+    }
+}
+
+impl Iterator for SpacedPositions {
+    type Item = (Point, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+
 //------------ Stage ---------------------------------------------------------
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
 #[repr(u16)]
 pub enum Stage {
     /// Background.
@@ -237,6 +1005,10 @@ impl Iterator for StageIter {
 
 //------------ StageSet ------------------------------------------------------
 
+/// A set of stages.
+///
+/// Each stage is represented by the bit at the position of its
+/// discriminant.
 #[derive(Clone, Copy, Debug, Default)]
 pub struct StageSet(u16);
 
@@ -259,7 +1031,7 @@ impl StageSet {
     }
 
     pub const fn add(self, stage: Stage) -> Self {
-        Self(self.0 | (stage as u16))
+        Self(self.0 | Self::bit(stage))
     }
 
     pub const fn add_set(self, set: StageSet) -> Self {
@@ -267,7 +1039,11 @@ impl StageSet {
     }
 
     pub fn contains(self, stage: Stage) -> bool {
-        self.0 & (stage as u16) != 0
+        self.0 & Self::bit(stage) != 0
+    }
+
+    const fn bit(stage: Stage) -> u16 {
+        1 << (stage as u16)
     }
 
     pub fn iter(self) -> impl Iterator<Item = Stage> {
@@ -296,9 +1072,247 @@ pub enum Category {
     Label,
 }
 
+impl Category {
+    const ALL: [Category; 4] = [
+        Category::Back, Category::Marker, Category::Track, Category::Label,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Category::Back => "back",
+            Category::Marker => "marker",
+            Category::Track => "track",
+            Category::Label => "label",
+        }
+    }
+}
+
+impl FromStr for Category {
+    type Err = CategoryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter().find(|item| item.as_str() == s).ok_or(
+            CategoryError
+        )
+    }
+}
+
+
+//------------ CategorySet ---------------------------------------------------
+
+/// A set of feature categories.
+///
+/// This is used to only render some of the categories of a layer.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct CategorySet(u8);
+
+impl CategorySet {
+    pub const fn empty() -> Self {
+        CategorySet(0)
+    }
+
+    pub const fn all() -> Self {
+        CategorySet(0x0F)
+    }
+
+    pub const fn add(self, category: Category) -> Self {
+        CategorySet(self.0 | (1 << category as u8))
+    }
+
+    pub fn contains(self, category: Category) -> bool {
+        self.0 & (1 << category as u8) != 0
+    }
+}
+
+impl Default for CategorySet {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl FromStr for CategorySet {
+    type Err = CategoryError;
+
+    /// Parses a comma separated list of category names.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',').try_fold(Self::empty(), |set, item| {
+            Ok(set.add(Category::from_str(item)?))
+        })
+    }
+}
+
+
+//------------ CategoryError -------------------------------------------------
+
+/// An unknown category name was encountered.
+#[derive(Clone, Copy, Debug)]
+pub struct CategoryError;
+
+
+//------------ FeatureInfo ---------------------------------------------------
+
+/// Identifying information about a feature.
+///
+/// This is what is returned when querying for features at a location.
+#[derive(Clone, Debug, Serialize)]
+pub struct FeatureInfo {
+    /// The identifier of the feature if it has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<FeatureId>,
+
+    /// The category of the feature.
+    pub category: &'static str,
+
+    /// The railway class of the feature if it has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class: Option<ClassInfo>,
+
+    /// The class of the second line of combined track.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub combined: Option<ClassInfo>,
+
+    /// The text of a label if it is known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+impl FeatureInfo {
+    pub fn new(group: Group, class: Option<&class::Railway>) -> Self {
+        Self {
+            id: None,
+            category: group.category.as_str(),
+            class: class.map(ClassInfo::new),
+            combined: None,
+            text: None,
+        }
+    }
+
+    pub fn with_combined(mut self, class: Option<&class::Railway>) -> Self {
+        self.combined = class.map(ClassInfo::new);
+        self
+    }
+
+    pub fn with_text(mut self, text: Option<String>) -> Self {
+        self.text = text;
+        self
+    }
+
+    pub fn with_id(mut self, id: Option<FeatureId>) -> Self {
+        self.id = id;
+        self
+    }
+}
+
+
+//------------ FeatureId -----------------------------------------------------
+
+/// The identifier of a feature.
+///
+/// The identifier consists of the name of the region the feature was
+/// loaded from, the name of the feature set it was inserted into, and the
+/// index of the feature among the region’s features in that feature set.
+/// It is shown as `{region}/{set}/{index}`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct FeatureId {
+    /// The name of the region.
+    region: Arc<str>,
+
+    /// The name of the feature set.
+    set: &'static str,
+
+    /// The index of the feature within the region and feature set.
+    index: usize,
+}
+
+impl FeatureId {
+    /// Returns the name of the region the feature was loaded from.
+    pub fn region(&self) -> &str {
+        &self.region
+    }
+
+    /// Returns the name of the feature set the feature was inserted into.
+    pub fn set(&self) -> &str {
+        self.set
+    }
+
+    /// Returns the index of the feature within its region and feature set.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl fmt::Display for FeatureId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.region, self.set, self.index)
+    }
+}
+
+impl Serialize for FeatureId {
+    fn serialize<S: serde::Serializer>(
+        &self, serializer: S
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+
+//------------ FeatureDump ---------------------------------------------------
+
+/// The description of a feature in a dump of the map.
+#[derive(Clone, Debug, Serialize)]
+pub struct FeatureDump {
+    /// The identifying information of the feature.
+    #[serde(flatten)]
+    pub info: FeatureInfo,
+
+    /// The storage bounds of the feature.
+    ///
+    /// These are given as `[x0, y0, x1, y1]` in storage coordinates.
+    pub bounds: [f64; 4],
+}
+
+
+//------------ ClassInfo -----------------------------------------------------
+
+/// The railway class of a feature as part of [`FeatureInfo`].
+#[derive(Clone, Debug, Serialize)]
+pub struct ClassInfo {
+    /// The railway category.
+    pub category: String,
+
+    /// The status of the feature.
+    pub status: String,
+
+    /// The year the feature was opened if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opened: Option<u16>,
+
+    /// The year the feature was closed if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub closed: Option<u16>,
+}
+
+impl ClassInfo {
+    fn new(class: &class::Railway) -> Self {
+        Self {
+            category: format!("{:?}", class.category()).to_lowercase(),
+            status: format!("{:?}", class.status()).to_lowercase(),
+            opened: class.opened(),
+            closed: class.closed(),
+        }
+    }
+}
+
 
 //------------ Group ---------------------------------------------------------
 
+/// The group of a feature determining the order of rendering.
+///
+/// Within a feature set, features are ordered by their layer first and
+/// only features of the same layer are ordered by their group. The layer
+/// is zero unless given via the `layer` render param or a `:layerN` symbol
+/// in the railway class of a feature. This way, map authors can force a
+/// feature to be drawn on top of others regardless of its category.
 #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub struct Group {
     category: Category,
@@ -347,5 +1361,82 @@ mod test {
             )
         );
     }
+
+    #[test]
+    fn clamp_detail() {
+        assert_eq!(
+            FeatureSetBuilder::clamp_detail((0.9, 3.9), (None, None)),
+            Some((0.9, 3.9))
+        );
+        let (low, high) = FeatureSetBuilder::clamp_detail(
+            (0.9, 3.9), (Some(8), Some(11))
+        ).unwrap();
+        assert!((low - 1.49).abs() < 1e-9);
+        assert!((high - 3.51).abs() < 1e-9);
+        assert_eq!(
+            FeatureSetBuilder::clamp_detail((0.9, 1.9), (Some(12), None)),
+            None
+        );
+    }
+
+    #[test]
+    fn stage_set_round_trip() {
+        for stage in Stage::default() {
+            let set = StageSet::from(stage);
+            for other in Stage::default() {
+                assert_eq!(
+                    set.contains(other), stage == other,
+                    "{:?} in set of {:?}", other, stage
+                );
+            }
+        }
+        let set = StageSet::from_slice(&[Stage::Back, Stage::MarkerMarking]);
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            [Stage::Back, Stage::MarkerMarking]
+        );
+        assert_eq!(StageSet::all().iter().count(), Stage::default().count());
+        assert_eq!(StageSet::empty().iter().count(), 0);
+    }
+
+    #[test]
+    fn feature_id_display() {
+        let id = FeatureId {
+            region: "de.bw".into(), set: "railway", index: 12
+        };
+        assert_eq!(id.to_string(), "de.bw/railway/12");
+    }
+
+    /// Checks the orientation that [`TraceExt::outline_left`] relies on.
+    ///
+    /// Traces can only be built from positions of imported paths, so this
+    /// can’t check an actual outline. Instead, it checks that the canvas
+    /// has north at smaller y, so that the left of an eastbound trace – the
+    /// side femtomap offsets to for positive distances – is north.
+    #[test]
+    fn left_of_eastbound_is_north() {
+        use crate::railway::legend::Legend;
+        use crate::railway::map::{LayerId, ScriptId};
+        use crate::railway::style::StyleSettings;
+        use crate::tile;
+
+        let layer = LayerId::El(ScriptId::Original);
+        let tile_id = Legend::tile_id(
+            tile::LayerId::Railway(layer), tile::TileFormat::Png
+        );
+        let style = Style::new(layer, &tile_id, &StyleSettings::default());
+        let canvas = |lon, lat| {
+            style.transform() * tile::storage_point(lon, lat)
+        };
+        let (west, east) = (canvas(10.00, 50.), canvas(10.01, 50.));
+        let north = canvas(10.005, 50.005);
+        let centre = west.midpoint(east);
+
+        // Left of the direction of travel with y pointing down.
+        let dir = east - west;
+        let left = kurbo::Vec2::new(dir.y, -dir.x);
+        assert!(north.y < centre.y);
+        assert!(left.dot(north - centre) > 0.);
+    }
 }
 
@@ -0,0 +1,95 @@
+//! Rendering of route highlights.
+//!
+//! A route highlight is a wide line drawn underneath the track along a
+//! route, e.g., the route a certain train takes. Since it is drawn in the
+//! background, the track stays visible on top of it. For that to work
+//! nicely, the color of the highlight should normally be translucent.
+
+use femtomap::world;
+use femtomap::path::Trace;
+use femtomap::render::{Canvas, Color, LineWidth, Outline};
+use crate::railway::class::Railway;
+use crate::railway::style::Style;
+use super::{
+    AnyShape, Category, Group, Feature, Shape, Stage, StageSet, TraceExt,
+};
+
+
+//------------ RouteContour --------------------------------------------------
+
+/// A contour highlighting a route.
+pub struct RouteContour {
+    /// The class of the route.
+    ///
+    /// This is only used to hide the route for years it didn’t exist.
+    class: Railway,
+
+    /// The color of the highlight.
+    color: Color,
+
+    /// The width of the highlight in multiples of _dt._
+    width: f64,
+
+    /// The trace of the route.
+    trace: Trace,
+}
+
+impl RouteContour {
+    pub fn new(
+        class: Railway, color: Color, width: f64, trace: Trace
+    ) -> Self {
+        RouteContour { class, color, width, trace }
+    }
+}
+
+impl Feature for RouteContour {
+    fn storage_bounds(&self) -> world::Rect {
+        self.trace.storage_bounds()
+    }
+
+    fn class(&self) -> Option<&Railway> {
+        Some(&self.class)
+    }
+
+    fn group(&self) -> Group {
+        Group::with_category(Category::Back)
+    }
+
+    fn hit(&self, point: kurbo::Point, radius: f64, style: &Style) -> bool {
+        self.trace.is_near(style.transform() * point, radius, style)
+    }
+
+    fn shape(
+        &self, style: &Style, _canvas: &Canvas
+    ) -> AnyShape {
+        RouteShape {
+            contour: self, trace: self.trace.outline(style)
+        }.into()
+    }
+}
+
+
+//------------ RouteShape ----------------------------------------------------
+
+struct RouteShape<'a> {
+    contour: &'a RouteContour,
+    trace: Outline,
+}
+
+impl<'a> Shape<'a> for RouteShape<'a> {
+    fn render(&self, stage: Stage, style: &Style, canvas: &mut Canvas) {
+        if matches!(stage, Stage::Back) {
+            let mut sketch = canvas.sketch();
+            sketch.apply(&self.trace);
+            sketch.apply(LineWidth(
+                self.contour.width * style.measures().dt()
+            ));
+            sketch.apply(self.contour.color);
+            sketch.stroke();
+        }
+    }
+
+    fn stages(&self) -> StageSet {
+        StageSet::from(Stage::Back)
+    }
+}
@@ -0,0 +1,460 @@
+//! Markers loaded from SVG files.
+//!
+//! In addition to the built-in markers, a directory of SVG files can be
+//! given in the map config. Each file becomes a marker named after the
+//! file’s basename, i.e., `foo.casing.svg` becomes the marker
+//! `:foo.casing`. These markers take precedence over built-in markers of
+//! the same name.
+//!
+//! Only a small subset of SVG is supported: the file must have a `viewBox`
+//! attribute and its drawing ops are taken from the `d` attribute of all
+//! `path` elements. The marker is scaled so that the height of the view box
+//! equals the station height and the horizontal center of the view box is
+//! placed at the marker’s position. As with the built-in markers, the marker
+//! hangs below the position in its unrotated state.
+//!
+//! Paths are filled using the marker’s color unless they have a
+//! `fill="none"` attribute in which case they are stroked using the width
+//! given by their `stroke-width` attribute or a width of one unit.
+
+use std::{fmt, fs};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use femtomap::render::Group;
+use kurbo::{BezPath, PathEl, Point};
+use crate::railway::measures::Measures;
+
+
+//------------ SvgMarkerSet --------------------------------------------------
+
+/// A set of markers loaded from SVG files.
+#[derive(Clone, Debug, Default)]
+pub struct SvgMarkerSet {
+    markers: HashMap<String, Arc<SvgMarker>>,
+}
+
+impl SvgMarkerSet {
+    /// Loads all markers from the SVG files in the given directory.
+    ///
+    /// Files that don’t end in `.svg` are ignored. All errors encountered
+    /// are returned together.
+    pub fn load(dir: &Path) -> Result<Self, SvgMarkerErrors> {
+        let mut res = Self::default();
+        let mut errors = SvgMarkerErrors::default();
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                errors.push(dir, err.to_string());
+                return Err(errors)
+            }
+        };
+        for entry in entries {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(err) => {
+                    errors.push(dir, err.to_string());
+                    continue;
+                }
+            };
+            if path.extension().map(|ext| ext != "svg").unwrap_or(true) {
+                continue
+            }
+            let name = match path.file_stem().and_then(|name| name.to_str()) {
+                Some(name) => name.to_string(),
+                None => {
+                    errors.push(&path, "invalid marker name");
+                    continue;
+                }
+            };
+            match SvgMarker::load(&path) {
+                Ok(marker) => {
+                    res.markers.insert(name, Arc::new(marker));
+                }
+                Err(err) => errors.push(&path, err),
+            }
+        }
+        if errors.is_empty() {
+            Ok(res)
+        }
+        else {
+            Err(errors)
+        }
+    }
+
+    /// Returns the marker with the given name if present.
+    pub fn get(&self, name: &str) -> Option<Arc<SvgMarker>> {
+        self.markers.get(name).cloned()
+    }
+}
+
+
+//------------ SvgMarker -----------------------------------------------------
+
+/// A single marker loaded from an SVG file.
+#[derive(Clone, Debug)]
+pub struct SvgMarker {
+    /// The horizontal center of the view box.
+    center: f64,
+
+    /// The top of the view box.
+    top: f64,
+
+    /// The height of the view box.
+    height: f64,
+
+    /// The paths of the marker.
+    paths: Vec<SvgPath>,
+}
+
+#[derive(Clone, Debug)]
+struct SvgPath {
+    /// The path itself in view box coordinates.
+    path: BezPath,
+
+    /// The stroke width in view box units if the path is to be stroked.
+    stroke: Option<f64>,
+}
+
+impl SvgMarker {
+    /// Loads a marker from the given file.
+    fn load(path: &Path) -> Result<Self, String> {
+        let data = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        Self::from_str(&data)
+    }
+
+    /// Parses a marker from the content of an SVG file.
+    fn from_str(data: &str) -> Result<Self, String> {
+        let svg = Self::tags(data, "svg").next().ok_or("missing svg element")?;
+        let view_box = attribute(svg, "viewBox").ok_or("missing viewBox")?;
+        let view_box = view_box.split(|ch: char| {
+            ch.is_whitespace() || ch == ','
+        }).filter(|item| !item.is_empty()).map(|item| {
+            item.parse::<f64>()
+        }).collect::<Result<Vec<_>, _>>().map_err(|_| "invalid viewBox")?;
+        let [left, top, width, height] = <[f64; 4]>::try_from(
+            view_box
+        ).map_err(|_| "invalid viewBox")?;
+        if width <= 0. || height <= 0. {
+            return Err("invalid viewBox".into())
+        }
+
+        let mut paths = Vec::new();
+        for tag in Self::tags(data, "path") {
+            let d = attribute(tag, "d").ok_or("path without d attribute")?;
+            let path = BezPath::from_svg(d).map_err(|err| {
+                format!("invalid path data: {}", err)
+            })?;
+            let stroke = if attribute(tag, "fill") == Some("none") {
+                Some(match attribute(tag, "stroke-width") {
+                    Some(width) => {
+                        width.trim().parse::<f64>().map_err(|_| {
+                            "invalid stroke-width"
+                        })?
+                    }
+                    None => 1.
+                })
+            }
+            else {
+                None
+            };
+            paths.push(SvgPath { path, stroke });
+        }
+        if paths.is_empty() {
+            return Err("no path elements".into())
+        }
+
+        Ok(Self {
+            center: left + 0.5 * width,
+            top,
+            height,
+            paths,
+        })
+    }
+
+    /// Returns an iterator over the attribute portion of all named tags.
+    fn tags<'a>(
+        data: &'a str, name: &'a str
+    ) -> impl Iterator<Item = &'a str> + 'a {
+        Elements(data).filter_map(move |(tag, attrs)| {
+            (tag == name).then_some(attrs)
+        })
+    }
+
+    /// Renders the marker.
+    ///
+    /// This follows the same contract as the built-in markers: the canvas
+    /// has already been transformed and colored.
+    pub fn render(&self, canvas: &mut Group, u: Measures) {
+        let scale = u.sh() / self.height;
+        let point = |point: Point| {
+            ((point.x - self.center) * scale, (point.y - self.top) * scale)
+        };
+        for path in &self.paths {
+            let mut start = Point::ZERO;
+            let mut current = Point::ZERO;
+            for el in path.path.elements() {
+                match *el {
+                    PathEl::MoveTo(p) => {
+                        let (x, y) = point(p);
+                        canvas.move_to(x, y);
+                        start = p;
+                        current = p;
+                    }
+                    PathEl::LineTo(p) => {
+                        let (x, y) = point(p);
+                        canvas.line_to(x, y);
+                        current = p;
+                    }
+                    PathEl::QuadTo(p1, p2) => {
+                        let (x1, y1) = point(current.lerp(p1, 2. / 3.));
+                        let (x2, y2) = point(p2.lerp(p1, 2. / 3.));
+                        let (x3, y3) = point(p2);
+                        canvas.curve_to(x1, y1, x2, y2, x3, y3);
+                        current = p2;
+                    }
+                    PathEl::CurveTo(p1, p2, p3) => {
+                        let (x1, y1) = point(p1);
+                        let (x2, y2) = point(p2);
+                        let (x3, y3) = point(p3);
+                        canvas.curve_to(x1, y1, x2, y2, x3, y3);
+                        current = p3;
+                    }
+                    PathEl::ClosePath => {
+                        canvas.close_path();
+                        current = start;
+                    }
+                }
+            }
+            match path.stroke {
+                Some(width) => {
+                    canvas.apply_line_width(width * scale);
+                    canvas.stroke();
+                }
+                None => {
+                    canvas.fill();
+                }
+            }
+        }
+    }
+}
+
+
+//------------ SvgMarkerErrors -----------------------------------------------
+
+/// The errors that happened while loading SVG markers.
+#[derive(Clone, Debug, Default)]
+pub struct SvgMarkerErrors {
+    errors: Vec<(PathBuf, String)>,
+}
+
+impl SvgMarkerErrors {
+    fn push(&mut self, path: &Path, err: impl fmt::Display) {
+        self.errors.push((path.into(), err.to_string()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn extend(&mut self, other: Self) {
+        self.errors.extend(other.errors)
+    }
+}
+
+impl fmt::Display for SvgMarkerErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (path, err) in &self.errors {
+            writeln!(f, "{}: {}", path.display(), err)?;
+        }
+        Ok(())
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the value of the attribute with the given name in a tag.
+///
+/// The tag is the attribute portion of an element as returned by
+/// [`Elements`]. Returns `None` if the attribute is missing or the tag is
+/// malformed before the attribute is reached.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+    loop {
+        rest = rest.trim_start_matches(|ch: char| {
+            ch.is_whitespace() || ch == '/'
+        });
+        if rest.is_empty() {
+            return None
+        }
+        let end = rest.find(|ch: char| {
+            ch.is_whitespace() || ch == '='
+        })?;
+        let key = &rest[..end];
+        rest = rest[end..].trim_start().strip_prefix('=')?.trim_start();
+        let quote = rest.chars().next()?;
+        if quote != '"' && quote != '\'' {
+            return None
+        }
+        rest = &rest[1..];
+        let end = rest.find(quote)?;
+        if key == name {
+            return Some(&rest[..end])
+        }
+        rest = &rest[end + 1..];
+    }
+}
+
+
+//------------ Elements ------------------------------------------------------
+
+/// An iterator over the start tags of an SVG document.
+///
+/// Each item is the element name and the attribute portion of the tag.
+/// Comments, CDATA sections, processing instructions, declarations, and end
+/// tags are skipped.
+struct Elements<'a>(&'a str);
+
+impl<'a> Iterator for Elements<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let start = self.0.find('<')?;
+            let rest = &self.0[start + 1..];
+            if let Some(rest) = rest.strip_prefix("!--") {
+                self.0 = skip_past(rest, "-->");
+                continue
+            }
+            if let Some(rest) = rest.strip_prefix("![CDATA[") {
+                self.0 = skip_past(rest, "]]>");
+                continue
+            }
+            if rest.starts_with(['?', '!', '/']) {
+                self.0 = skip_past(rest, ">");
+                continue
+            }
+
+            let name_end = rest.find(|ch: char| {
+                ch.is_whitespace() || ch == '/' || ch == '>'
+            }).unwrap_or(rest.len());
+            let (name, rest) = rest.split_at(name_end);
+
+            // Find the closing bracket outside of quoted values.
+            let mut quote = None;
+            let end = rest.char_indices().find(|&(_, ch)| {
+                match quote {
+                    Some(q) => {
+                        if ch == q {
+                            quote = None
+                        }
+                        false
+                    }
+                    None => {
+                        if ch == '"' || ch == '\'' {
+                            quote = Some(ch)
+                        }
+                        ch == '>'
+                    }
+                }
+            }).map(|(idx, _)| idx).unwrap_or(rest.len());
+            self.0 = rest.get(end + 1..).unwrap_or("");
+            return Some((name, &rest[..end]))
+        }
+    }
+}
+
+/// Returns the part of `data` after the first occurrence of `end`.
+///
+/// Returns an empty string if `end` doesn’t appear at all.
+fn skip_past<'a>(data: &'a str, end: &str) -> &'a str {
+    match data.find(end) {
+        Some(idx) => &data[idx + end.len()..],
+        None => "",
+    }
+}
+
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn svg(body: &str) -> String {
+        format!(
+            "<?xml version=\"1.0\"?>\n\
+             <svg xmlns=\"http://www.w3.org/2000/svg\" \
+             viewBox=\"0 0 10 10\">{}</svg>",
+            body
+        )
+    }
+
+    #[test]
+    fn plain_path() {
+        let marker = SvgMarker::from_str(
+            &svg("<path d=\"M 0 0 L 10 10\"/>")
+        ).unwrap();
+        assert_eq!(marker.paths.len(), 1);
+        assert!(marker.paths[0].stroke.is_none());
+    }
+
+    #[test]
+    fn skip_comments() {
+        let marker = SvgMarker::from_str(&svg(
+            "<!-- <path d=\"M 0 0 L 5 5\"/> -->\
+             <path d=\"M 0 0 L 10 10\"/>"
+        )).unwrap();
+        assert_eq!(marker.paths.len(), 1);
+        assert!(
+            SvgMarker::from_str(&svg("<!-- <path d=\"M 0 0\"/> -->"))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn skip_cdata() {
+        let marker = SvgMarker::from_str(&svg(
+            "<style><![CDATA[ <path d=\"M 0 0 L 5 5\"/> ]]></style>\
+             <path d=\"M 0 0 L 10 10\"/>"
+        )).unwrap();
+        assert_eq!(marker.paths.len(), 1);
+    }
+
+    #[test]
+    fn skip_prefixed_elements() {
+        let marker = SvgMarker::from_str(&svg(
+            "<pathfoo d=\"M 0 0 L 5 5\"/><path d=\"M 0 0 L 10 10\"/>"
+        )).unwrap();
+        assert_eq!(marker.paths.len(), 1);
+        assert!(
+            SvgMarker::from_str(&svg("<pathfoo d=\"M 0 0 L 5 5\"/>"))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn exact_attribute_names() {
+        let tag = " id=\"d\" fooid=\"M 1 1\" data-d=\"x\" d=\"M 0 0\"";
+        assert_eq!(attribute(tag, "d"), Some("M 0 0"));
+        assert_eq!(attribute(tag, "id"), Some("d"));
+        assert_eq!(attribute(" title=\"a d='x'\"", "d"), None);
+        assert_eq!(attribute(" fill = 'none' ", "fill"), Some("none"));
+
+        let marker = SvgMarker::from_str(&svg(
+            "<path id=\"pad\" class=\"d=x\" d=\"M 0 0 L 10 10\" \
+             fill=\"none\" stroke-width=\"2\"/>"
+        )).unwrap();
+        assert_eq!(marker.paths.len(), 1);
+        assert_eq!(marker.paths[0].stroke, Some(2.));
+    }
+
+    #[test]
+    fn quoted_bracket() {
+        let marker = SvgMarker::from_str(&svg(
+            "<path class=\"a>b\" d=\"M 0 0 L 10 10\"/>"
+        )).unwrap();
+        assert_eq!(marker.paths.len(), 1);
+    }
+}
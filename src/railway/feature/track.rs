@@ -24,8 +24,27 @@
 //!
 //! * In detail levels 0 to 2, `:double` marks a track as double track.
 //!
+//! * In detail level 4, `:triple` indicates three tracks of the same line,
+//!   one in the middle and one each a full dt to the left and right. The
+//!   middle track is considered an up-and-down track. In lower detail levels,
+//!   `:triple` is drawn like `:double`.
+//!
 //! * `:tight` is a deprecated shortcut for `:leftother:rightother`.
 //!
+//! * In detail levels 3 and up, `:bridge` adds the bridge decoration, i.e.,
+//!   parallel lines on both sides of the track with ticks at their ends.
+//!
+//! In detail level 4, `:tunnel` draws the track hollow, i.e., with an inset
+//! in the background color.
+//!
+//! In detail level 4, track with a gauge other than the base gauge of its
+//! region gets a glyph on its center line once every seg.
+//!
+//! In detail level 4, `:arrows` places an arrowhead once every seg on open
+//! track with passenger service showing the direction of travel. Up tracks
+//! point along the path, down tracks against it, and up-and-down tracks as
+//! well as single track without a direction both ways.
+//!
 //! Placement within a sequence of segments that whose markings should look
 //! consecutive:
 //!
@@ -35,6 +54,21 @@
 //!   spacing can be added at its end.
 //! * `:inner` if the segment is in the middle and markings need to be
 //!   “justified.”
+//!
+//! # Detail levels
+//!
+//! The detail levels given above are those of the standard map. Which
+//! detail levels track is rendered at in which way can be changed via the
+//! `track_detail` section of the map config. With the defaults given in
+//! parentheses, these are:
+//!
+//! * below `medium` (2): simplified lines of a single width colored by
+//!   class without any decorations,
+//! * from `medium`: lines with class-dependent width, pax dashes, and
+//!   `:double` as double track,
+//! * from `bridges` (3): as before plus the bridge decoration,
+//! * from `full` (4): separate lines for `:double` and `:triple`, the
+//!   electrification markings, tunnels, and gauge glyphs.
 
 #![allow(unused_imports)]
 use std::f64::consts::{FRAC_PI_2, PI};
@@ -44,11 +78,14 @@ use femtomap::path::Trace;
 use femtomap::render::{
     Canvas, Color, DashPattern, Group, LineCap, LineWidth, Outline, Sketch,
 };
-use kurbo::{PathEl, Vec2};
+use kurbo::{Line, ParamCurveNearest, PathEl, Point, Vec2};
 use crate::railway::import::eval::{Expression, Scope};
 use crate::railway::class::{GaugeGroup, Railway, Pax};
-use crate::railway::style::Style;
-use super::{AnyShape, Category, Feature, Shape, Stage, StageSet};
+use crate::railway::style::{Style, TrackLevel};
+use super::{
+    AnyShape, Category, Feature, FeatureInfo, Shape, Stage, StageSet,
+    TraceExt,
+};
 
 
 //------------ Constants -----------------------------------------------------
@@ -64,6 +101,30 @@ const NO_PAX_DASH_HALF: f64 = 4.;
 /// Which portion of the no-pax-dash stroke should be on?
 const NO_PAX_DASH_ON: f64 = 0.7;
 
+/// How many seasonal-pax-dash strokes go into a seg?
+///
+/// Seasonal service is the closest to full service, so the strokes are
+/// long with short gaps.
+const SEASONAL_PAX_DASH_RATIO: f64 = 3.;
+
+/// Which portion of the seasonal-pax-dash stroke should be on?
+const SEASONAL_PAX_DASH_ON: f64 = 0.8;
+
+/// How many heritage-pax-dash strokes go into a seg?
+///
+/// This is the rhythm of the old inside rendering: two strokes per seg,
+/// half of each on.
+const HERITAGE_PAX_DASH_RATIO: f64 = 2.;
+
+/// Which portion of the heritage-pax-dash stroke should be on?
+const HERITAGE_PAX_DASH_ON: f64 = 0.5;
+
+/// Where in a seg are direction arrows placed?
+///
+/// This keeps them clear of the electrification and gauge markings which
+/// all end before the last sixth of a seg.
+const ARROW_OFFSET: f64 = 0.92;
+
 
 //------------ TrackClass ----------------------------------------------------
 
@@ -75,9 +136,40 @@ pub struct TrackClass {
 
     /// The setup of the track and neighboring tracks.
     setup: Setup,
+
+    /// Is this a triple track line?
+    triple: bool,
+
+    /// Is the track on a bridge?
+    bridge: bool,
+
+    /// Is the track in a tunnel?
+    tunnel: bool,
+
+    /// Should direction arrows be drawn?
+    arrows: bool,
+
+    /// The class of the second line if the track carries two lines.
+    combined: Option<Railway>,
 }
 
 impl TrackClass {
+    /// Creates a single track of the given railway class.
+    ///
+    /// The track has no neighbors and is a bridge or tunnel if the class’s
+    /// surface says so.
+    pub fn new(class: Railway) -> Self {
+        TrackClass {
+            bridge: class.surface().is_bridge(),
+            tunnel: class.surface().is_tunnel(),
+            class,
+            setup: Setup::default(),
+            triple: false,
+            arrows: false,
+            combined: None,
+        }
+    }
+
     pub fn from_arg(
         arg: Expression,
         scope: &Scope,
@@ -89,27 +181,73 @@ impl TrackClass {
         Ok(class)
     }
 
+    /// Creates the track class from a symbol set.
+    ///
+    /// A `:combined` symbol is ignored here. The `track` procedure uses it
+    /// to take the class of a second line, see [`Self::set_combined`].
     pub fn from_symbols(symbols: &mut SymbolSet, scope: &Scope) -> Self {
         let _ = symbols.take("tight"); // XXX Deprecated.
         let _ = symbols.take("flip"); // XXX Deprecated.
-        let _ = symbols.take("combined"); // XXX Deprecated.
+        let _ = symbols.take("combined");
         let _ = symbols.take("leftsame"); // XXX Deprecated.
         let _ = symbols.take("leftother"); // XXX Deprecated.
         let _ = symbols.take("rightsame"); // XXX Deprecated.
         let _ = symbols.take("rightother"); // XXX Deprecated.
+        let triple = symbols.take("triple");
+        let arrows = symbols.take("arrows");
+        let class = Railway::from_symbols(symbols, scope);
         TrackClass {
-            class: Railway::from_symbols(symbols, scope),
+            bridge: class.surface().is_bridge(),
+            tunnel: class.surface().is_tunnel(),
+            class,
             setup: Setup::from_symbols(symbols),
+            triple,
+            arrows,
+            combined: None,
         }
     }
 
+    /// Makes the track carry a second line of the given class.
+    ///
+    /// Anything not given in `other` is taken from the track’s own class.
+    pub fn set_combined(&mut self, mut other: Railway) {
+        other.update(&self.class);
+        self.combined = Some(other);
+    }
+
     pub fn class(&self) -> &Railway {
         &self.class
     }
 
+    /// Returns the class of the second line of combined track.
+    pub fn combined(&self) -> Option<&Railway> {
+        self.combined.as_ref()
+    }
+
     pub fn double(&self) -> bool {
         self.class.double()
     }
+
+    pub fn triple(&self) -> bool {
+        self.triple
+    }
+
+    pub fn bridge(&self) -> bool {
+        self.bridge
+    }
+
+    pub fn tunnel(&self) -> bool {
+        self.tunnel
+    }
+
+    pub fn arrows(&self) -> bool {
+        self.arrows
+    }
+
+    /// Returns whether the track should be drawn as a multi-track line.
+    fn multi(&self) -> bool {
+        self.double() || self.triple()
+    }
 }
 
 
@@ -165,7 +303,9 @@ impl Direction {
 /// Describes the purpose of a neighboring track.
 ///
 /// This is used for drawing track and line decorations and describes the
-/// purpose of a track running parallel to this track.
+/// purpose of a track running parallel to this track. Left and right are
+/// seen in the direction of the path, the same sides as those of
+/// [`TraceExt::outline_left`] and [`TraceExt::outline_right`].
 #[derive(Clone, Copy, Debug, Default)]
 pub enum Neighbor {
     /// There is no neighbor at all.
@@ -226,7 +366,7 @@ impl Neighbor {
 
 //------------ Setup ---------------------------------------------------------
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default)]
 struct Setup {
     /// The direction of the track for a multitrack line.
     ///
@@ -264,6 +404,30 @@ impl Setup {
             right: self.right,
         }
     }
+
+    fn triple_left(self) -> Self {
+        Self {
+            direction: Some(Direction::Down),
+            left: self.left,
+            right: Neighbor::Same(Direction::Updown),
+        }
+    }
+
+    fn triple_middle(self) -> Self {
+        Self {
+            direction: Some(Direction::Updown),
+            left: Neighbor::Same(Direction::Down),
+            right: Neighbor::Same(Direction::Up),
+        }
+    }
+
+    fn triple_right(self) -> Self {
+        Self {
+            direction: Some(Direction::Up),
+            left: Neighbor::Same(Direction::Updown),
+            right: self.right,
+        }
+    }
 }
 
 
@@ -288,26 +452,45 @@ impl Feature for TrackContour {
         self.trace.storage_bounds()
     }
 
+    fn class(&self) -> Option<&Railway> {
+        Some(&self.class.class)
+    }
+
     fn group(&self) -> super::Group {
         super::Group::with_railway(Category::Track, &self.class.class)
     }
 
+    fn info(&self) -> FeatureInfo {
+        FeatureInfo::new(
+            self.group(), self.class()
+        ).with_combined(self.class.combined())
+    }
+
+    fn hit(&self, point: Point, radius: f64, style: &Style) -> bool {
+        self.trace.is_near(style.transform() * point, radius, style)
+    }
+
+    fn length(&self, style: &Style) -> Option<f64> {
+        Some(self.trace.outline(style).base_arclen())
+    }
+
     fn shape(
         &self, style: &Style, _canvas: &Canvas
     ) -> AnyShape {
-        if style.detail() <= 1 {
-            AnyShape::from(
-                ContourShape::new(&self.class, self.trace.outline(style))
-            )
-        }
-        else if style.detail() == 2 {
-            return AnyShape::from(ContourShape2::new(self, style));
-        }
-        else if style.detail() == 3 {
-            return AnyShape::from(ContourShape2::new(self, style));
-        }
-        else {
-            return ContourShape4::new(self, style)
+        match style.track_level() {
+            TrackLevel::Simple => {
+                AnyShape::from(ContourShape::new(self, style))
+            }
+            TrackLevel::Medium => {
+                AnyShape::from(ContourShape2::new(self, style))
+            }
+            TrackLevel::Bridges => {
+                AnyShape::from((
+                    ContourShape2::new(self, style),
+                    BridgeDecor::new(self, style),
+                ))
+            }
+            TrackLevel::Full => ContourShape4::new(self, style),
         }
     }
 }
@@ -315,121 +498,230 @@ impl Feature for TrackContour {
 
 //------------ ContourShape --------------------------------------------------
 
-struct ContourShape<'a> {
-    class: &'a TrackClass,
-    trace: Outline,
+/// The largest distance of a simplified line from the actual line.
+///
+/// This is given in pixels.
+const SIMPLIFY_TOLERANCE: f64 = 0.25;
+
+/// The shape of a track contour for the simple track level.
+///
+/// This draws simplified lines of a single width colored by class without
+/// any decorations. Since the curves of the lines are hardly visible at
+/// this level, the outline is replaced by a polyline with as few points as
+/// possible that strays no further than a fraction of a pixel from it.
+struct ContourShape {
+    color: Color,
+    width: f64,
+    casing_width: Option<f64>,
+    line: Vec<Point>,
 }
 
-impl<'a> ContourShape<'a> {
+impl ContourShape {
     fn new(
-        class: &'a TrackClass,
-        trace: Outline,
+        contour: &TrackContour,
+        style: &Style,
     ) -> Self {
-        Self { class, trace }
+        let width = style.measures().main_track();
+        let factor = style.line_width_factor(width);
+        let color = if contour.class.class.is_open_no_pax() {
+            style.track_color(&contour.class.class).lighten(0.7)
+        }
+        else {
+            style.track_color(&contour.class.class)
+        };
+        Self {
+            color,
+            width: width * factor,
+            casing_width: contour.casing.then(|| {
+                let skip = style.measures().main_skip()
+                    * style.casing_skip_factor(&contour.class.class);
+                (width + 2. * skip) * factor
+            }),
+            line: simplify_outline(
+                &contour.trace.outline(style),
+                SIMPLIFY_TOLERANCE * style.canvas_px()
+            ),
+        }
     }
-}
 
+    fn apply_line(&self, sketch: &mut Sketch) {
+        let mut points = self.line.iter();
+        if let Some(&first) = points.next() {
+            sketch.apply([PathEl::MoveTo(first)]);
+            for &point in points {
+                sketch.apply([PathEl::LineTo(point)]);
+            }
+        }
+    }
+}
 
-impl<'a> Shape<'a> for ContourShape<'a> {
+impl<'a> Shape<'a> for ContourShape {
     fn render(&self, stage: Stage, style: &Style, canvas: &mut Canvas) {
-        if !matches!(stage, Stage::Base) {
-            return
+        match stage {
+            Stage::Casing => {
+                if let Some(width) = self.casing_width {
+                    let mut sketch = canvas.sketch();
+                    sketch.apply(style.track_casing_color());
+                    sketch.apply(LineWidth(width));
+                    self.apply_line(&mut sketch);
+                    sketch.stroke();
+                }
+            }
+            Stage::Base => {
+                let mut sketch = canvas.sketch();
+                sketch.apply(self.color);
+                sketch.apply(LineWidth(self.width));
+                self.apply_line(&mut sketch);
+                sketch.stroke();
+            }
+            _ => { }
         }
-        let mut canvas = canvas.sketch().into_group();
-        self.apply_line_width(style, &mut canvas);
-        self.apply_line_color(style, &mut canvas);
-        canvas.apply(&self.trace);
-        canvas.stroke()
-     }
+    }
 
     fn stages(&self) -> StageSet {
-        StageSet::from(Stage::Base)
+        if self.casing_width.is_some() {
+            StageSet::from(Stage::Casing).add(Stage::Base)
+        }
+        else {
+            StageSet::from(Stage::Base)
+        }
     }
 }
 
-impl<'a> ContourShape<'a> {
-    fn apply_line_width(&self, style: &Style, canvas: &mut Group) {
-        let line_width = if style.detail() < 1 {
-            style.measures().main_track()
-        }
-        else if self.class.double() {
-            style.measures().class_double(&self.class.class) * 1.4
+
+//------------ ContourShape2 -------------------------------------------------
+
+/// The shape of a track contour for the medium and bridges track levels.
+struct ContourShape2 {
+    look: TrackLook,
+    outline: Outline,
+}
+
+impl ContourShape2 {
+    fn new(
+        contour: &TrackContour,
+        style: &Style,
+    ) -> Self {
+        let outline = contour.trace.outline(style);
+        Self {
+            look: TrackLook::new(
+                &contour.class, contour.casing, outline.base_arclen(), style
+            ),
+            outline
         }
-        else {
-            style.measures().class_track(&self.class.class)
-        };
-        canvas.apply_line_width(line_width);
     }
+}
 
-    fn apply_line_color(&self, style: &Style, canvas: &mut Group) {
-        canvas.apply(
-            if self.class.class.is_open_no_pax() {
-                style.track_color(&self.class.class).lighten(0.7)
-            }
-            else {
-                style.track_color(&self.class.class)
-            }
-        );
+impl<'a> Shape<'a> for ContourShape2 {
+    fn render(&self, stage: Stage, style: &Style, canvas: &mut Canvas) {
+        self.look.render(stage, style, canvas, |sketch| {
+            sketch.apply(&self.outline);
+        })
+    }
+
+    fn stages(&self) -> StageSet {
+        self.look.stages()
     }
 }
 
 
-//------------ ContourShape2 -------------------------------------------------
+//------------ TrackLook -----------------------------------------------------
 
-struct ContourShape2 {
+/// How a track is drawn at the medium track level.
+///
+/// This is separate from the outline so that the legend can draw track
+/// following the same rules.
+///
+/// Combined track is drawn in alternating colors of its two lines. Solid
+/// track gets dashes of half a seg in the second color on top. For dashed
+/// track, every other dash is drawn in the second color instead.
+struct TrackLook {
     open: bool,
     color: Color,
     width: f64,
     casing_width: Option<f64>,
     dash: Option<DashPattern<2>>,
-    outline: Outline,
+
+    /// The color and dashes of the second line of combined track.
+    combined: Option<(Color, DashPattern<2>)>,
 }
 
-impl ContourShape2 {
+impl TrackLook {
+    /// Creates the look for a track of the given length in canvas units.
     fn new(
-        contour: &TrackContour,
-        style: &Style,
+        class: &TrackClass, casing: bool, len: f64, style: &Style,
     ) -> Self {
-        let outline = contour.trace.outline(style);
-        let dash = Self::project_dash(
-            &contour.class, &outline, style
-        ).or_else(|| {
-            Self::pax_dash(&contour.class, &outline, style)
+        let dash = Self::project_dash(class, len, style).or_else(|| {
+            Self::pax_dash(class, len, style)
         });
-        let width = if contour.class.double() {
-            style.measures().class_double(&contour.class.class)
+        let second = class.combined().map(|other| style.track_color(other));
+        let (dash, combined) = match (dash, second) {
+            (Some((on, off, offset)), Some(second)) => {
+                let gap = on + 2. * off;
+                (
+                    Some(DashPattern::new([on, gap], offset)),
+                    Some((
+                        second,
+                        DashPattern::new([on, gap], offset + on + off)
+                    ))
+                )
+            }
+            (Some((on, off, offset)), None) => {
+                (Some(DashPattern::new([on, off], offset)), None)
+            }
+            (None, Some(second)) => {
+                let seg = style.measures().seg();
+                let seg = calc_seg_len(len, seg).unwrap_or(seg);
+                (
+                    None,
+                    Some((
+                        second, DashPattern::new([0.5 * seg, 0.5 * seg], 0.)
+                    ))
+                )
+            }
+            (None, None) => (None, None)
+        };
+        let width = if class.multi() {
+            style.measures().class_double(&class.class)
         }
         else {
-            style.measures().class_track(&contour.class.class)
+            style.measures().class_track(&class.class)
         };
+        let factor = style.line_width_factor(width);
 
         Self {
-            open: contour.class.class.status().is_open(),
-            color: style.track_color(&contour.class.class),
-            width,
-            casing_width: contour.casing.then(|| {
-                width + 2. * style.measures().class_skip(&contour.class.class)
+            open: class.class.status().is_open(),
+            color: style.track_color(&class.class),
+            width: width * factor,
+            casing_width: casing.then(|| {
+                let skip = style.measures().class_skip(&class.class)
+                    * style.casing_skip_factor(&class.class);
+                (width + 2. * skip) * factor
             }),
             dash,
-            outline
+            combined,
         }
     }
 
+    /// Returns the dashes of project track as on, off, and offset.
     fn project_dash(
-        class: &TrackClass, outline: &Outline, style: &Style
-    ) -> Option<DashPattern<2>> {
+        class: &TrackClass, len: f64, style: &Style
+    ) -> Option<(f64, f64, f64)> {
         if !class.class.status().is_project() {
             return None
         }
 
-        calc_seg(outline, style.measures().seg()).map(|seg| {
-            DashPattern::new([0.7 * seg, 0.3 * seg], 0.35 * seg)
+        calc_seg_len(len, style.measures().seg()).map(|seg| {
+            (0.7 * seg, 0.3 * seg, 0.35 * seg)
         })
     }
 
+    /// Returns the dashes of track without full passenger service.
+    ///
+    /// The dashes are returned as on, off, and offset.
     fn pax_dash(
-        class: &TrackClass, outline: &Outline, style: &Style
-    ) -> Option<DashPattern<2>> {
+        class: &TrackClass, len: f64, style: &Style
+    ) -> Option<(f64, f64, f64)> {
         // For historical reasons, an missing explicit pax defaults to no pax
         // for open lines and full pax for closed ones.
         if class.class.status().is_open() {
@@ -444,91 +736,82 @@ impl ContourShape2 {
         }
 
         if matches!(class.class.pax(), Pax::None) {
-            calc_seg(outline, style.measures().seg() * 0.125).map(|dist| {
-                DashPattern::new([dist * 0.7, dist * 0.3], dist * 0.35)
+            calc_seg_len(len, style.measures().seg() * 0.125).map(|dist| {
+                (dist * 0.7, dist * 0.3, dist * 0.35)
             })
         }
         else {
-            calc_seg(outline, style.measures().seg() * 0.25).map(|dist| {
-                DashPattern::new([dist * 0.8, dist * 0.2], dist * 0.4)
+            calc_seg_len(len, style.measures().seg() * 0.25).map(|dist| {
+                (dist * 0.8, dist * 0.2, dist * 0.4)
             })
         }
     }
-}
 
-impl<'a> Shape<'a> for ContourShape2 {
-    fn render(&self, stage: Stage, _style: &Style, canvas: &mut Canvas) {
-        match stage {
+    /// Renders a stage of the track.
+    ///
+    /// The closure `path` needs to apply the path of the track to the
+    /// sketch it is given.
+    fn render(
+        &self, stage: Stage, style: &Style, canvas: &mut Canvas,
+        path: impl Fn(&mut Sketch),
+    ) {
+        // The last element tells whether the track color is drawn and the
+        // second line of combined track needs to go on top.
+        let (color, dash, marking) = match stage {
             Stage::Casing => {
                 if let Some(width) = self.casing_width {
-                    canvas.sketch().apply(
-                        Color::rgba(1., 1., 1., 0.8)
-                    ).apply(
-                        LineWidth(width)
-                    ).apply(
-                        &self.outline
-                    ).stroke();
+                    let mut sketch = canvas.sketch();
+                    sketch.apply(style.track_casing_color());
+                    sketch.apply(LineWidth(width));
+                    path(&mut sketch);
+                    sketch.stroke();
                 }
+                return
             }
-            Stage::AbandonedBase => {
-                if !self.open {
-                    canvas.sketch()
-                        .apply(
-                            if self.dash.is_some() {
-                                Color::rgba(1., 1., 1., 0.8)
-                            }
-                            else {
-                                self.color
-                            }
-                        )
-                        .apply(LineWidth(self.width))
-                        .apply(&self.outline)
-                        .stroke()
+            Stage::AbandonedBase if !self.open => {
+                if self.dash.is_some() {
+                    (style.track_casing_color(), None, false)
                 }
-            }
-            Stage::AbandonedMarking => {
-                if !self.open {
-                    if let Some(dash) = self.dash {
-                       canvas.sketch() 
-                            .apply(self.color)
-                            .apply(LineWidth(self.width))
-                            .apply(dash)
-                            .apply(&self.outline)
-                            .stroke()
-                    }
+                else {
+                    (self.color, None, true)
                 }
             }
-            Stage::LimitedBase => {
-                if self.open && self.dash.is_some() {
-                   canvas.sketch() 
-                        .apply(Color::rgba(1., 1., 1., 0.8))
-                        .apply(LineWidth(self.width))
-                        .apply(&self.outline)
-                        .stroke()
+            Stage::AbandonedMarking if !self.open => {
+                match self.dash {
+                    Some(dash) => (self.color, Some(dash), true),
+                    None => return,
                 }
             }
-            Stage::LimitedMarking => {
-                if self.open {
-                    if let Some(dash) = self.dash {
-                       canvas.sketch() 
-                            .apply(self.color)
-                            .apply(LineWidth(self.width))
-                            .apply(dash)
-                            .apply(&self.outline)
-                            .stroke()
-                    }
-                }
+            Stage::LimitedBase if self.open && self.dash.is_some() => {
+                (style.track_casing_color(), None, false)
             }
-            Stage::Base => {
-                if self.open && self.dash.is_none() {
-                   canvas.sketch() 
-                        .apply(self.color)
-                        .apply(LineWidth(self.width))
-                        .apply(&self.outline)
-                        .stroke()
+            Stage::LimitedMarking if self.open => {
+                match self.dash {
+                    Some(dash) => (self.color, Some(dash), true),
+                    None => return,
                 }
             }
-            _ => { }
+            Stage::Base if self.open && self.dash.is_none() => {
+                (self.color, None, true)
+            }
+            _ => return,
+        };
+        let mut sketch = canvas.sketch();
+        sketch.apply(color);
+        sketch.apply(LineWidth(self.width));
+        if let Some(dash) = dash {
+            sketch.apply(dash);
+        }
+        path(&mut sketch);
+        sketch.stroke();
+
+        if let (true, Some((color, dash))) = (marking, self.combined) {
+            let mut sketch = canvas.sketch();
+            sketch.apply(color);
+            sketch.apply(LineWidth(self.width));
+            sketch.apply(dash);
+            path(&mut sketch);
+            sketch.stroke();
         }
     }
 
@@ -763,8 +1046,20 @@ struct ContourShape4 {
     casing_width: Option<f64>,
     dash: Option<(f64, f64)>, // on - off
 
+    /// The width of the inset for track in a tunnel.
+    tunnel: Option<f64>,
+
+    /// The color and dashes of the second line of combined track.
+    combined: Option<(Color, (f64, f64))>,
+
     electric: Option<ElectricDecor>,
 
+    /// The gauge glyphs if they are placed along this track.
+    gauge: Option<GaugeDecor>,
+
+    /// The direction arrows of this track.
+    arrow: Option<ArrowDecor>,
+
     outline: Outline,
 }
 
@@ -773,54 +1068,172 @@ impl ContourShape4 {
         let open = contour.class.class.status().is_open();
         let color = style.track_color(&contour.class.class);
         let width = style.measures().class_track(&contour.class.class);
+        let factor = style.line_width_factor(width);
         let casing_width = contour.casing.then(|| {
-            width + 2. * style.measures().class_skip(&contour.class.class)
+            let skip = style.measures().class_skip(&contour.class.class)
+                * style.casing_skip_factor(&contour.class.class);
+            (width + 2. * skip) * factor
         });
-
-        if contour.class.double() {
+        let width = width * factor;
+        let tunnel = Self::tunnel_width(&contour.class, width, style);
+
+        if contour.class.triple() {
+            let off = style.measures().class_offset(&contour.class.class);
+            let shape = |
+                setup: Setup, direction: Direction, center: bool,
+                outline: Outline
+            | {
+                let seg = calc_seg(&outline, style.measures().seg());
+                Self {
+                    open, color, width, casing_width, tunnel,
+                    dash: Self::pax_dash(&contour.class, &outline, seg, style),
+                    combined: Self::combined(&contour.class, seg, style),
+                    electric: ElectricDecor::new(
+                        &contour.class, setup, width, seg, style
+                    ),
+                    gauge: if center {
+                        GaugeDecor::new(&contour.class.class, seg, style)
+                    }
+                    else {
+                        None
+                    },
+                    arrow: ArrowDecor::new(
+                        &contour.class, direction, seg, style
+                    ),
+                    outline,
+                }
+            };
+            let left = shape(
+                contour.class.setup.triple_left(), Direction::Down, false,
+                contour.trace.outline_left(off, style)
+            );
+            let middle = shape(
+                contour.class.setup.triple_middle(), Direction::Updown, true,
+                contour.trace.outline(style)
+            );
+            let right = shape(
+                contour.class.setup.triple_right(), Direction::Up, false,
+                contour.trace.outline_right(off, style)
+            );
+            AnyShape::from((
+                (left, middle, right),
+                BridgeDecor::new(contour, style),
+            ))
+        }
+        else if contour.class.double() {
             let off = style.measures().class_offset(&contour.class.class) * 0.5;
-            let left = contour.trace.outline_offset(off, style);
-            let dash = Self::pax_dash(&contour.class, &left, style);
-
+            let left = contour.trace.outline_left(off, style);
+            let right = contour.trace.outline_right(off, style);
+            let left_seg = calc_seg(&left, style.measures().seg());
+            let right_seg = calc_seg(&right, style.measures().seg());
+            let dash = Self::pax_dash(&contour.class, &left, left_seg, style);
+
+            // The two outlines differ in length in curves, so each track
+            // needs its own segments for the electric markings to be
+            // spread evenly along it.
             let left_electric = ElectricDecor::new(
                 &contour.class, contour.class.setup.double_left(),
-                width, &left, style
+                width, left_seg, style
             );
             let right_electric = ElectricDecor::new(
                 &contour.class, contour.class.setup.double_right(),
-                width, &left, style
+                width, right_seg, style
             );
 
             let left_shape = Self {
-                open, color, width, casing_width, dash,
+                open, color, width, casing_width, dash, tunnel,
+                combined: Self::combined(&contour.class, left_seg, style),
                 electric: left_electric,
+                gauge: None,
+                arrow: ArrowDecor::new(
+                    &contour.class, Direction::Down, left_seg, style
+                ),
                 outline: left,
             };
             let right_shape = Self {
-                open, color, width, casing_width, dash,
+                open, color, width, casing_width, dash, tunnel,
+                combined: Self::combined(&contour.class, right_seg, style),
                 electric: right_electric,
-                outline: contour.trace.outline_offset(-off, style),
+                gauge: None,
+                arrow: ArrowDecor::new(
+                    &contour.class, Direction::Up, right_seg, style
+                ),
+                outline: right,
             };
-            AnyShape::from((left_shape, right_shape))
+            AnyShape::from((
+                (left_shape, right_shape),
+                (
+                    BridgeDecor::new(contour, style),
+                    CenterGauge::new(contour, style),
+                )
+            ))
         }
         else {
             let outline = contour.trace.outline(style);
-            AnyShape::from(
+            let seg = calc_seg(&outline, style.measures().seg());
+            AnyShape::from((
                 Self {
-                    open, color, width, casing_width,
-                    dash: Self::pax_dash(&contour.class, &outline, style),
+                    open, color, width, casing_width, tunnel,
+                    dash: Self::pax_dash(&contour.class, &outline, seg, style),
+                    combined: Self::combined(&contour.class, seg, style),
                     electric: ElectricDecor::new(
-                        &contour.class, contour.class.setup, width, &outline,
-                        style
+                        &contour.class, contour.class.setup, width, seg, style
+                    ),
+                    gauge: GaugeDecor::new(&contour.class.class, seg, style),
+                    arrow: ArrowDecor::new(
+                        &contour.class,
+                        contour.class.setup.direction.unwrap_or(
+                            Direction::Updown
+                        ),
+                        seg, style
                     ),
                     outline
-                }
-            )
+                },
+                BridgeDecor::new(contour, style),
+            ))
+        }
+    }
+
+    /// Returns the width of the tunnel inset if the track is in a tunnel.
+    ///
+    /// The inset leaves a guide width of track color on either side. If the
+    /// track is too thin for that, we use half its width so that even short
+    /// tunnels remain recognizable.
+    fn tunnel_width(
+        class: &TrackClass, width: f64, style: &Style
+    ) -> Option<f64> {
+        if !class.tunnel() {
+            return None
+        }
+        let inset = width - 2. * style.measures().guide_width();
+        if inset > 0.5 * width {
+            Some(inset)
+        }
+        else {
+            Some(0.5 * width)
         }
     }
 
+    /// Returns the color and dashes of the second line of combined track.
+    ///
+    /// On solid track, the second line is drawn as dashes of half a seg on
+    /// top of the track so that the colors of the two lines alternate. On
+    /// dashed track, every other dash is drawn in its color instead.
+    fn combined(
+        class: &TrackClass, seg: Option<f64>, style: &Style
+    ) -> Option<(Color, (f64, f64))> {
+        let other = class.combined()?;
+        let seg = seg.unwrap_or_else(|| style.measures().seg());
+        Some((style.track_color(other), (0.5 * seg, 0.5 * seg)))
+    }
+
+    /// Returns the dash pattern for track without full passenger service.
+    ///
+    /// The `seg` is the one calculated for `outline` with the standard
+    /// seg length.
     fn pax_dash(
-        class: &TrackClass, outline: &Outline, style: &Style
+        class: &TrackClass, outline: &Outline, seg: Option<f64>,
+        style: &Style,
     ) -> Option<(f64, f64)> {
         // For historical reasons, an missing explicit pax defaults to no pax
         // for open lines and full pax for closed ones.
@@ -835,44 +1248,44 @@ impl ContourShape4 {
             }
         }
 
+        // Each kind of limited service gets its own rhythm so they can be
+        // told apart: many short strokes for no service, two long strokes
+        // per seg for heritage service, and three nearly solid strokes for
+        // seasonal service. Closed lines only get here with an explicit pax
+        // other than full.
+        let (ratio, on) = match class.class.pax() {
+            Pax::None => (NO_PAX_DASH_RATIO, NO_PAX_DASH_ON),
+            Pax::Heritage => (HERITAGE_PAX_DASH_RATIO, HERITAGE_PAX_DASH_ON),
+            Pax::Seasonal => (SEASONAL_PAX_DASH_RATIO, SEASONAL_PAX_DASH_ON),
+            Pax::Full => return None,
+        };
+
         // If we have enough space, we want to operate with full segs so the
-        // electrification and gauge markings are properly centered. Onyl if
-        // that doesn’t work will be fall back to full “dash groups” since we
+        // electrification and gauge markings are properly centered. Only if
+        // that doesn’t work will we fall back to full “dash groups” since we
         // won’t have those markings.
-
-        let seg = calc_seg(outline, style.measures().seg());
-
-        if matches!(class.class.pax(), Pax::None) {
-            let dist = match seg {
-                Some(seg) => seg / NO_PAX_DASH_RATIO,
-                None => {
-                    calc_seg(
-                        outline, style.measures().seg() / NO_PAX_DASH_RATIO,
-                    )?
-                }
-            };
-            Some((dist * NO_PAX_DASH_ON, dist * (1. - NO_PAX_DASH_ON)))
-        }
-        else {
-            // XXX Fix this ...
-            calc_seg(outline, style.measures().seg() * 0.25).map(|dist| {
-                (dist * 0.8, dist * 0.2)
-            })
-        }
+        let dist = match seg {
+            Some(seg) => seg / ratio,
+            None => calc_seg(outline, style.measures().seg() / ratio)?,
+        };
+        Some((dist * on, dist * (1. - on)))
     }
 }
 
 impl<'a> Shape<'a> for ContourShape4 {
-    fn render(&self, stage: Stage, _style: &Style, canvas: &mut Canvas) {
+    fn render(&self, stage: Stage, style: &Style, canvas: &mut Canvas) {
         match stage {
             Stage::Casing => {
                 if let Some(width) = self.casing_width {
-                    self.render_casing(width, &mut canvas.sketch());
+                    self.render_casing(width, style, &mut canvas.sketch());
                 }
             }
             Stage::AbandonedBase => {
                 if !self.open {
-                    self.render_base(&mut canvas.sketch());
+                    let mut canvas = canvas.sketch();
+                    self.render_base(style, &mut canvas);
+                    self.render_tunnel(style, &mut canvas);
+                    self.render_combined(&mut canvas);
                 }
             }
             Stage::AbandonedMarking => {
@@ -888,7 +1301,9 @@ impl<'a> Shape<'a> for ContourShape4 {
             }
             Stage::LimitedBase => {
                 if self.open && self.dash.is_some() {
-                    self.render_base(&mut canvas.sketch());
+                    let mut canvas = canvas.sketch();
+                    self.render_base(style, &mut canvas);
+                    self.render_tunnel(style, &mut canvas);
                 }
             }
             Stage::LimitedMarking => {
@@ -905,12 +1320,22 @@ impl<'a> Shape<'a> for ContourShape4 {
             Stage::Base => {
                 if self.open && !self.dash.is_some() {
                     let mut canvas = canvas.sketch();
-                    self.render_base(&mut canvas);
+                    self.render_base(style, &mut canvas);
+                    self.render_tunnel(style, &mut canvas);
+                    self.render_combined(&mut canvas);
                     if let Some(electric) = self.electric {
                         electric.render(&self.outline, &mut canvas)
                     }
                 }
             }
+            Stage::Marking => {
+                if let Some(gauge) = self.gauge.as_ref() {
+                    gauge.render(&self.outline, canvas)
+                }
+                if let Some(arrow) = self.arrow.as_ref() {
+                    arrow.render(&self.outline, canvas)
+                }
+            }
             _ => { }
         }
     }
@@ -922,6 +1347,12 @@ impl<'a> Shape<'a> for ContourShape4 {
         else {
             StageSet::empty()
         };
+        let res = if self.gauge.is_some() || self.arrow.is_some() {
+            res.add(Stage::Marking)
+        }
+        else {
+            res
+        };
         if !self.open {
             if self.dash.is_some() {
                 res.add(Stage::AbandonedBase).add(Stage::AbandonedMarking)
@@ -940,9 +1371,11 @@ impl<'a> Shape<'a> for ContourShape4 {
 }
 
 impl ContourShape4 {
-    fn render_casing(&self, width: f64, canvas: &mut Sketch) {
+    fn render_casing(
+        &self, width: f64, style: &Style, canvas: &mut Sketch
+    ) {
         canvas.apply(
-            Color::rgba(1., 1., 1., 0.8)
+            style.track_casing_color()
         ).apply(
             LineWidth(width)
         ).apply(
@@ -950,11 +1383,11 @@ impl ContourShape4 {
         ).stroke();
     }
 
-    fn render_base(&self, canvas: &mut Sketch) {
+    fn render_base(&self, style: &Style, canvas: &mut Sketch) {
         canvas
             .apply(
                 if self.dash.is_some() {
-                    Color::rgba(1., 1., 1., 0.8)
+                    style.track_casing_color()
                 }
                 else {
                     self.color
@@ -965,18 +1398,61 @@ impl ContourShape4 {
             .stroke()
     }
 
+    /// Draws the inset of track in a tunnel.
+    ///
+    /// This happens right after the base so that markings are drawn on
+    /// top of the inset.
+    fn render_tunnel(&self, style: &Style, canvas: &mut Sketch) {
+        let width = match self.tunnel {
+            Some(width) => width,
+            None => return,
+        };
+        canvas.apply(
+            style.background_color()
+        ).apply(
+            LineWidth(width)
+        ).apply(
+            &self.outline
+        ).stroke();
+    }
+
+    /// Draws the second line of combined track on top of solid track.
+    fn render_combined(&self, canvas: &mut Sketch) {
+        if self.dash.is_some() {
+            return
+        }
+        if let Some((color, dash)) = self.combined {
+            self.render_dashes(color, None, dash, canvas)
+        }
+    }
+
     fn render_dashed_track(
-        &self, (on, off): (f64, f64), canvas: &mut Sketch
+        &self, dash: (f64, f64), canvas: &mut Sketch
     ) {
-       canvas.apply(self.color).apply(LineWidth(self.width));
+        let second = self.combined.map(|(color, _)| color);
+        self.render_dashes(self.color, second, dash, canvas)
+    }
+
+    /// Draws dashes along the outline.
+    ///
+    /// If `second` is given, every other dash is drawn in this color.
+    fn render_dashes(
+        &self, color: Color, second: Option<Color>, (on, off): (f64, f64),
+        canvas: &mut Sketch
+    ) {
+        canvas.apply(color).apply(LineWidth(self.width));
 
         let mut positions = self.outline.positions();
         if positions.advance(0.5 * off).is_none() {
             return
         }
+        let mut odd = false;
         loop {
             match positions.advance_sub(on) {
                 Some(sub) => {
+                    if let Some(second) = second {
+                        canvas.apply(if odd { second } else { color });
+                    }
                     canvas.apply(&sub).stroke()
                 }
                 None => return
@@ -984,8 +1460,368 @@ impl ContourShape4 {
             if positions.advance(off).is_none() {
                 return
             }
+            odd = !odd;
+        }
+    }
+}
+
+
+//------------ BridgeDecor ---------------------------------------------------
+
+/// The decoration for track on a bridge.
+///
+/// This draws a line parallel to the outer tracks on either side with short
+/// ticks pointing away from the track at both ends. The lines are separated
+/// from the track by a gap in the casing color.
+struct BridgeDecor {
+    color: Color,
+    width: f64,
+    gap: f64,
+    tick: f64,
+    left: Outline,
+    right: Outline,
+}
+
+impl BridgeDecor {
+    fn new(contour: &TrackContour, style: &Style) -> Option<Self> {
+        if !contour.class.bridge() {
+            return None
+        }
+        let class = &contour.class.class;
+        let width = style.measures().class_track(class);
+        let skip = style.measures().class_skip(class);
+        let offset = if contour.class.triple() {
+            style.measures().class_offset(class)
+        }
+        else if contour.class.double() {
+            style.measures().class_offset(class) * 0.5
+        }
+        else {
+            0.
+        };
+        let dist = offset + 0.5 * width + skip;
+        Some(Self {
+            color: style.track_color(class),
+            width: 0.5 * width,
+            gap: 0.5 * skip,
+            tick: skip,
+            left: contour.trace.outline_left(dist, style),
+            right: contour.trace.outline_right(dist, style),
+        })
+    }
+
+    fn render_side(
+        &self, outline: &Outline, side: f64,
+        style: &Style, canvas: &mut Canvas
+    ) {
+        canvas.sketch()
+            .apply(style.track_casing_color())
+            .apply(LineWidth(self.width + 2. * self.gap))
+            .apply(outline)
+            .stroke();
+
+        let mut canvas = canvas.sketch();
+        canvas.apply(self.color);
+        canvas.apply(LineWidth(self.width));
+        canvas.apply(LineCap::Round);
+        canvas.apply(outline);
+        canvas.stroke();
+
+        // The ticks are drawn at both ends regardless of the length of the
+        // outline so that even very short bridges show both of them.
+        let start = outline.positions().advance(0.);
+        let end = outline.positions().advance(outline.base_arclen());
+        for (pos, dir) in start.into_iter().chain(end) {
+            let dir = Vec2::from_angle(dir + FRAC_PI_2) * side * self.tick;
+            canvas.apply([
+                PathEl::MoveTo(pos),
+                PathEl::LineTo(pos + dir),
+            ]);
+            canvas.stroke();
+        }
+    }
+}
+
+impl<'a> Shape<'a> for BridgeDecor {
+    fn render(&self, stage: Stage, style: &Style, canvas: &mut Canvas) {
+        if matches!(stage, Stage::Marking) {
+            self.render_side(&self.left, -1., style, canvas);
+            self.render_side(&self.right, 1., style, canvas);
+        }
+    }
+
+    fn stages(&self) -> StageSet {
+        StageSet::from(Stage::Marking)
+    }
+}
+
+
+//------------ GaugeDecor ----------------------------------------------------
+
+/// The decoration for track with a gauge other than the region’s base.
+///
+/// This places a glyph on the center line of the track once every seg. If
+/// the track is electrified, the glyphs are moved to a quarter seg so they
+/// don’t collide with the electrification markings.
+///
+/// The decoration is rendered along with the track on the center line. For
+/// double track, [`CenterGauge`] provides the center line.
+struct GaugeDecor {
+    glyph: GaugeGlyph,
+    color: Color,
+    knockout: Color,
+    radius: f64,
+    width: f64,
+    seg: f64,
+    offset: f64,
+}
+
+impl GaugeDecor {
+    /// Creates the decoration for the given seg of the center line.
+    fn new(class: &Railway, seg: Option<f64>, style: &Style) -> Option<Self> {
+        let glyph = Self::glyph(class)?;
+        let seg = seg?;
+        let electric = style.cat_color(class).is_some()
+            || style.rail_color(class).is_some();
+        Some(Self {
+            glyph,
+            color: style.track_color(class),
+            knockout: style.background_color(),
+            radius: 0.5 * style.measures().dt(),
+            width: style.measures().guide_width(),
+            seg,
+            offset: if electric { 0.25 * seg } else { 0.5 * seg },
+        })
+    }
+
+    /// Returns the glyph for a class if it needs one.
+    fn glyph(class: &Railway) -> Option<GaugeGlyph> {
+        if class.station() {
+            return None
+        }
+        GaugeGlyph::new(class.gauge_group(), class.gauge().secondary())
+    }
+
+    fn render(&self, outline: &Outline, canvas: &mut Canvas) {
+        let mut canvas = canvas.sketch();
+        canvas.apply(LineWidth(self.width));
+        outline.iter_positions(
+            self.seg, Some(self.offset)
+        ).for_each(|(pos, dir)| {
+            self.render_glyph(pos, dir, &mut canvas)
+        });
+    }
+
+    fn render_glyph(&self, pos: kurbo::Point, dir: f64, canvas: &mut Sketch) {
+        let outer = self.radius + 0.5 * self.width;
+        match self.glyph {
+            GaugeGlyph::Narrow => {
+                canvas.apply(self.color);
+                canvas.apply(kurbo::Circle::new(pos, outer));
+                canvas.fill();
+            }
+            GaugeGlyph::StandardNarrow => {
+                let circle = kurbo::Circle::new(pos, self.radius);
+                canvas.apply(self.knockout);
+                canvas.apply(circle);
+                canvas.fill();
+                canvas.apply(self.color);
+                canvas.apply(circle);
+                canvas.stroke();
+            }
+            GaugeGlyph::StandardBroad => {
+                let square = Self::square(pos, dir, self.radius);
+                canvas.apply(self.knockout);
+                canvas.apply(square);
+                canvas.fill();
+                canvas.apply(self.color);
+                canvas.apply(square);
+                canvas.stroke();
+            }
+            GaugeGlyph::Broad => {
+                canvas.apply(self.color);
+                canvas.apply(Self::square(pos, dir, outer));
+                canvas.fill();
+            }
+            GaugeGlyph::Multi => {
+                canvas.apply(self.color);
+                canvas.apply(kurbo::Circle::new(pos, outer));
+                canvas.fill();
+                canvas.apply(self.knockout);
+                canvas.apply(kurbo::CircleSegment::new(
+                    pos, self.radius - 0.5 * self.width, 0.,
+                    dir + FRAC_PI_2, PI,
+                ));
+                canvas.fill();
+            }
+        }
+    }
+
+    fn square(pos: kurbo::Point, dir: f64, radius: f64) -> [PathEl; 5] {
+        let fwd = Vec2::from_angle(dir) * radius;
+        let side = Vec2::from_angle(dir + FRAC_PI_2) * radius;
+        [
+            PathEl::MoveTo(pos + fwd + side),
+            PathEl::LineTo(pos - fwd + side),
+            PathEl::LineTo(pos - fwd - side),
+            PathEl::LineTo(pos + fwd - side),
+            PathEl::ClosePath
+        ]
+    }
+}
+
+
+
+//------------ CenterGauge ---------------------------------------------------
+
+/// The gauge decoration on the center line of double track.
+///
+/// Neither of the two tracks runs along the center line, so it needs its
+/// own outline.
+struct CenterGauge {
+    gauge: GaugeDecor,
+    outline: Outline,
+}
+
+impl CenterGauge {
+    fn new(contour: &TrackContour, style: &Style) -> Option<Self> {
+        // Only construct the outline if there is a glyph to place.
+        GaugeDecor::glyph(&contour.class.class)?;
+        let outline = contour.trace.outline(style);
+        let gauge = GaugeDecor::new(
+            &contour.class.class,
+            calc_seg(&outline, style.measures().seg()),
+            style
+        )?;
+        Some(CenterGauge { gauge, outline })
+    }
+}
+
+impl<'a> Shape<'a> for CenterGauge {
+    fn render(&self, stage: Stage, _style: &Style, canvas: &mut Canvas) {
+        if matches!(stage, Stage::Marking) {
+            self.gauge.render(&self.outline, canvas)
         }
     }
+
+    fn stages(&self) -> StageSet {
+        StageSet::from(Stage::Marking)
+    }
+}
+
+
+//------------ GaugeGlyph ----------------------------------------------------
+
+/// The glyph used to mark a gauge.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum GaugeGlyph {
+    /// A filled circle for gauges narrower than the base gauge.
+    Narrow,
+
+    /// A hollow circle for standard gauge in a broad gauge region.
+    StandardNarrow,
+
+    /// A hollow square for standard gauge in a narrow gauge region.
+    StandardBroad,
+
+    /// A filled square for gauges broader than the base gauge.
+    Broad,
+
+    /// A half-filled circle for track with more than one gauge.
+    Multi,
+}
+
+impl GaugeGlyph {
+    fn new(group: GaugeGroup, secondary: Option<u16>) -> Option<Self> {
+        if secondary.is_some() {
+            return Some(GaugeGlyph::Multi)
+        }
+        match group {
+            GaugeGroup::Narrow => Some(GaugeGlyph::Narrow),
+            GaugeGroup::StandardNarrow => Some(GaugeGlyph::StandardNarrow),
+            GaugeGroup::Base => None,
+            GaugeGroup::StandardBroad => Some(GaugeGlyph::StandardBroad),
+            GaugeGroup::Broad => Some(GaugeGlyph::Broad),
+        }
+    }
+}
+
+
+//------------ ArrowDecor ----------------------------------------------------
+
+/// The decoration showing the direction of travel.
+///
+/// This places an arrowhead on a track once every seg. On tracks used both
+/// ways, two arrowheads are placed back to back. The decoration is
+/// rendered along with the track.
+struct ArrowDecor {
+    color: Color,
+
+    /// Half the width and length of an arrowhead.
+    size: f64,
+
+    /// The direction of travel on the track.
+    direction: Direction,
+
+    /// The seg of the track.
+    seg: f64,
+}
+
+impl ArrowDecor {
+    fn new(
+        class: &TrackClass, direction: Direction, seg: Option<f64>,
+        style: &Style,
+    ) -> Option<Self> {
+        if !class.arrows() || !class.class.is_open()
+            || matches!(class.class.pax(), Pax::None)
+        {
+            return None
+        }
+
+        // The arrowheads may reach into the gap towards a neighboring
+        // track but must stay clear of its own half.
+        Some(Self {
+            color: style.track_color(&class.class),
+            size: 0.5 * style.measures().class_track(&class.class)
+                + 0.4 * style.measures().class_skip(&class.class),
+            direction,
+            seg: seg?,
+        })
+    }
+
+    fn render(&self, outline: &Outline, canvas: &mut Canvas) {
+        let mut canvas = canvas.sketch();
+        canvas.apply(self.color);
+        outline.iter_positions(
+            self.seg, Some(ARROW_OFFSET * self.seg)
+        ).for_each(|(pos, dir)| {
+            match self.direction {
+                Direction::Up => {
+                    self.render_head(pos, dir, &mut canvas)
+                }
+                Direction::Down => {
+                    self.render_head(pos, dir + PI, &mut canvas)
+                }
+                Direction::Updown => {
+                    let shift = Vec2::from_angle(dir) * self.size;
+                    self.render_head(pos + shift, dir, &mut canvas);
+                    self.render_head(pos - shift, dir + PI, &mut canvas);
+                }
+            }
+        });
+    }
+
+    fn render_head(&self, pos: Point, dir: f64, canvas: &mut Sketch) {
+        let fwd = Vec2::from_angle(dir) * self.size;
+        let side = Vec2::from_angle(dir + FRAC_PI_2) * self.size;
+        canvas.apply([
+            PathEl::MoveTo(pos + fwd),
+            PathEl::LineTo(pos - fwd + side),
+            PathEl::LineTo(pos - fwd - side),
+            PathEl::ClosePath
+        ]);
+        canvas.fill();
+    }
 }
 
 
@@ -1019,16 +1855,14 @@ impl ElectricDecor {
         class: &TrackClass,
         setup: Setup,
         width: f64,
-        outline: &Outline,
+        seg: Option<f64>,
         style: &Style,
     ) -> Option<Self> {
         if class.class.station() || !class.class.category().is_railway() {
             return None
         }
 
-        let dist = calc_seg(
-            outline, style.measures().seg()
-        )? / NO_PAX_DASH_RATIO;
+        let dist = seg? / NO_PAX_DASH_RATIO;
 
         let (cat, rail) = match (
             style.cat_color(&class.class), style.rail_color(&class.class)
@@ -1081,43 +1915,37 @@ impl ElectricDecor {
         })
     }
 
-    fn dl(setup: Setup, width: f64, skip: f64, track: f64) -> f64 {
-        if matches!(setup.direction, Some(Direction::Updown)) {
-            return 0.
-        }
-        match setup.left {
-            Neighbor::None => -0.5 * width - track,
-            Neighbor::Same(Direction::Updown) => 0.,
-            Neighbor::Same(dir) if Some(dir) == setup.direction => {
-                0.
-            }
-            Neighbor::Other => -0.5 * width - 0.2 * skip,
-            _ => -0.5 * width - 0.5 * skip,
-        }
+    fn dl_dr(setup: Setup, width: f64, skip: f64, track: f64) -> (f64, f64) {
+        (
+            -Self::reach(setup.direction, setup.left, width, skip, track),
+            Self::reach(setup.direction, setup.right, width, skip, track),
+        )
     }
 
-    fn dr(setup: Setup, width: f64, skip: f64, track: f64) -> f64 {
-        if matches!(setup.direction, Some(Direction::Updown)) {
+    /// Returns how far the marks extend from the center of the track.
+    ///
+    /// The marks must never reach the neighboring track. Since the stroke
+    /// width of the track may have been scaled up, we clamp them to the
+    /// actual gap between the two tracks rather than the nominal skip.
+    /// Marks of two tracks of the same line meet in the middle of the gap
+    /// while marks towards a different line stop well short of it.
+    fn reach(
+        direction: Option<Direction>, neighbor: Neighbor,
+        width: f64, skip: f64, track: f64
+    ) -> f64 {
+        if matches!(direction, Some(Direction::Updown)) {
             return 0.
         }
-        match setup.right {
+        let gap = (track + skip - width).max(0.);
+        match neighbor {
             Neighbor::None => 0.5 * width + track,
             Neighbor::Same(Direction::Updown) => 0.,
-            Neighbor::Same(dir) if Some(dir) == setup.direction => {
-                0.
-            }
-            Neighbor::Other => 0.5 * width + 0.2 * skip,
-            _ => 0.5 * width + 0.5 * skip,
+            Neighbor::Same(dir) if Some(dir) == direction => 0.,
+            Neighbor::Same(_) => 0.5 * width + (0.5 * skip).min(0.5 * gap),
+            Neighbor::Other => 0.5 * width + (0.2 * skip).min(0.4 * gap),
         }
     }
 
-    fn dl_dr(setup: Setup, width: f64, skip: f64, track: f64) -> (f64, f64) {
-        (
-            Self::dl(setup, width, skip, track),
-            Self::dr(setup, width, skip, track)
-        )
-    }
-
     fn render(&self, outline: &Outline, canvas: &mut Sketch) {
         if let Some(cat) = self.cat {
             self.render_cat(outline, cat, canvas);
@@ -1640,23 +2468,26 @@ impl Feature for TrackCasing {
         self.trace.storage_bounds()
     }
 
+    fn class(&self) -> Option<&Railway> {
+        Some(&self.class.class)
+    }
+
     fn group(&self) -> super::Group {
         super::Group::with_railway(Category::Track, &self.class.class)
     }
 
+    fn hit(&self, point: Point, radius: f64, style: &Style) -> bool {
+        self.trace.is_near(style.transform() * point, radius, style)
+    }
+
     fn shape(
         &self, style: &Style, _canvas: &Canvas
     ) -> AnyShape {
-        let line_width = if self.class.double() {
-            2.2 * style.measures().dt()
-        }
-        else {
-            1.2 * style.measures().dt()
-        };
+        let line_width = style.track_casing_width(self.class.double());
 
         AnyShape::single_stage(move |style: &Style, canvas: &mut Canvas| {
             let mut canvas = canvas.sketch();
-            canvas.apply(Color::rgba(1., 1., 1., 0.7));
+            canvas.apply(style.casing_color());
             canvas.apply(LineWidth(line_width));
             canvas.apply(self.trace.iter_outline(style));
             canvas.stroke();
@@ -1665,12 +2496,37 @@ impl Feature for TrackCasing {
 }
 
 
+//------------ Legend --------------------------------------------------------
+
+/// Renders a straight piece of track for the legend.
+///
+/// The track runs from `start` to `end` in canvas coordinates. It is drawn
+/// the way the medium track level draws it.
+pub fn render_legend(
+    class: &TrackClass, start: kurbo::Point, end: kurbo::Point,
+    style: &Style, canvas: &mut Canvas,
+) {
+    let look = TrackLook::new(class, true, (end - start).hypot(), style);
+    for stage in look.stages().iter() {
+        look.render(stage, style, canvas, |sketch| {
+            sketch.apply([PathEl::MoveTo(start), PathEl::LineTo(end)]);
+        })
+    }
+}
+
+
 //------------ Helper Functions ----------------------------------------------
 
 fn calc_seg(
     outline: &Outline, base_seg: f64
 ) -> Option<f64> {
-    let len = outline.base_arclen();
+    calc_seg_len(outline.base_arclen(), base_seg)
+}
+
+/// Calculates the seg length for a path of the given length.
+///
+/// Returns `None` if the path is shorter than `base_seg`.
+fn calc_seg_len(len: f64, base_seg: f64) -> Option<f64> {
     if len < base_seg {
         return None
     }
@@ -1684,4 +2540,153 @@ fn calc_seg(
     Some(len / full)
 }
 
+/// Approximates an outline by a polyline.
+///
+/// The outline is sampled at steps of twice the tolerance and the samples
+/// are then reduced via [`simplify_line`].
+fn simplify_outline(outline: &Outline, tolerance: f64) -> Vec<Point> {
+    let len = outline.base_arclen();
+    if len <= 0. || tolerance <= 0. {
+        return Vec::new()
+    }
+
+    // Shorten the step ever so slightly so that rounding doesn’t make us
+    // miss the end of the outline.
+    let steps = (len / (2. * tolerance)).ceil();
+    let step = len / (steps + 1e-6);
+    let points = outline.iter_positions(
+        step, Some(0.)
+    ).map(|(pos, _)| pos).collect::<Vec<_>>();
+    simplify_line(&points, tolerance)
+}
+
+/// Simplifies a polyline using the Ramer-Douglas-Peucker algorithm.
+///
+/// Drops as many points as possible while keeping the line within
+/// `tolerance` of all the original points. The first and last points are
+/// always kept.
+fn simplify_line(points: &[Point], tolerance: f64) -> Vec<Point> {
+    if points.len() < 3 {
+        return points.into()
+    }
+    let last = points.len() - 1;
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[last] = true;
+
+    let tolerance = tolerance * tolerance;
+    let mut stack = vec![(0, last)];
+    while let Some((start, end)) = stack.pop() {
+        let line = Line::new(points[start], points[end]);
+        let (idx, dist) = (start + 1..end).fold((start, 0.), |max, idx| {
+            let dist = line.nearest(points[idx], 0.).distance_sq;
+            if dist > max.1 { (idx, dist) } else { max }
+        });
+        if dist > tolerance {
+            keep[idx] = true;
+            stack.push((start, idx));
+            stack.push((idx, end));
+        }
+    }
+
+    points.iter().zip(keep).filter_map(|(&point, keep)| {
+        keep.then_some(point)
+    }).collect()
+}
+
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
 
+    #[test]
+    fn electric_reach() {
+        let (track, skip) = (1., 2.);
+        let offset = track + skip;
+        for width in [0.5, 1., 2., 2.5, 4.] {
+            for neighbor in [
+                Neighbor::Same(Direction::Up), Neighbor::Other
+            ] {
+                let (dl, dr) = ElectricDecor::dl_dr(
+                    Setup { direction: None, left: neighbor, right: neighbor },
+                    width, skip, track
+                );
+                assert!(-dl <= 0.5 * offset.max(width));
+                assert!(dr <= 0.5 * offset.max(width));
+            }
+            let (_, dr) = ElectricDecor::dl_dr(
+                Setup {
+                    direction: None,
+                    left: Neighbor::None,
+                    right: Neighbor::Other
+                },
+                width, skip, track
+            );
+            assert!(dr < 0.5 * offset || width >= offset);
+        }
+    }
+
+    #[test]
+    fn simplify_polyline() {
+        let straight = (0..10).map(|x| {
+            Point::new(f64::from(x), 0.)
+        }).collect::<Vec<_>>();
+        assert_eq!(
+            simplify_line(&straight, 0.1),
+            [Point::new(0., 0.), Point::new(9., 0.)]
+        );
+
+        let bend = [
+            Point::new(0., 0.), Point::new(1., 0.05), Point::new(2., 0.),
+            Point::new(3., 1.), Point::new(4., 2.),
+        ];
+        assert_eq!(
+            simplify_line(&bend, 0.1),
+            [Point::new(0., 0.), Point::new(2., 0.), Point::new(4., 2.)]
+        );
+    }
+
+    #[test]
+    fn simplify_long_curve() {
+        // A quarter circle with a radius of 1000 units sampled the way
+        // simplify_outline does it.
+        let (radius, tolerance) = (1000., 0.5);
+        let steps = (0.5 * PI * radius / (2. * tolerance)).ceil() as u32;
+        let points = (0..=steps).map(|i| {
+            let angle = 0.5 * PI * f64::from(i) / f64::from(steps);
+            Point::new(radius * angle.cos(), radius * angle.sin())
+        }).collect::<Vec<_>>();
+        let simple = simplify_line(&points, tolerance);
+
+        // Chords whose sagitta stays within the tolerance cover at most
+        // 0.063 radians, so at least 25 segments are needed. Halving the
+        // arc until that holds results in 32.
+        assert!(points.len() > 1500);
+        assert!(simple.len() > 25 && simple.len() < 60, "{}", simple.len());
+        assert_eq!(simple.first(), points.first());
+        assert_eq!(simple.last(), points.last());
+
+        // No original point strays further than the tolerance.
+        for point in &points {
+            let dist = simple.windows(2).map(|pair| {
+                Line::new(pair[0], pair[1]).nearest(*point, 0.).distance_sq
+            }).fold(f64::INFINITY, f64::min);
+            assert!(dist <= tolerance * tolerance);
+        }
+    }
+
+    #[test]
+    fn gauge_glyph() {
+        assert_eq!(
+            GaugeGlyph::new(GaugeGroup::Narrow, None),
+            Some(GaugeGlyph::Narrow)
+        );
+        assert_eq!(GaugeGlyph::new(GaugeGroup::Base, None), None);
+        assert_eq!(
+            GaugeGlyph::new(GaugeGroup::Base, Some(1000)),
+            Some(GaugeGlyph::Multi)
+        );
+    }
+}
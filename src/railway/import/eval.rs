@@ -6,8 +6,10 @@ use femtomap::import::ast::Pos;
 use femtomap::import::eval::{EvalErrors, Failed, SymbolSet};
 use femtomap::import::path::{ImportPathSet};
 use femtomap::path::Distance;
+use femtomap::render::Color;
 use crate::railway::class::Railway;
 use crate::railway::feature::StoreBuilder;
+use crate::railway::feature::svgmarker::SvgMarkerSet;
 use crate::railway::feature::label::{Block, Layout};
 use super::{functions, procedures, units};
 
@@ -19,6 +21,7 @@ pub type Value<'s> = eval::Value<'s, Builtin>;
 pub struct Builtin {
     paths: ImportPathSet,
     store: Arc<Mutex<StoreBuilder>>,
+    markers: Arc<SvgMarkerSet>,
     base_gauge: u16,
 }
 
@@ -26,9 +29,14 @@ impl Builtin {
     pub fn new(
         paths: ImportPathSet,
         store: Arc<Mutex<StoreBuilder>>,
+        markers: Arc<SvgMarkerSet>,
         base_gauge: u16
     ) -> Self {
-        Self { paths, store, base_gauge }
+        Self { paths, store, markers, base_gauge }
+    }
+
+    pub fn markers(&self) -> &SvgMarkerSet {
+        &self.markers
     }
 
     pub fn with_store<F, T>(&self, op: F) -> T
@@ -55,18 +63,10 @@ impl eval::Builtin for Builtin {
         _scope: &eval::Scope<Self>,
         pos: Pos, err: &mut EvalErrors,
     ) -> Result<Distance, Failed> {
-        for (name, factor) in units::WORLD_DISTANCES {
-            if unit == *name {
-                return Ok(Distance::world(number * factor))
-            }
-        }
-        for (name, index, factor) in units::MAP_DISTANCES {
-            if unit == *name {
-                return Ok(Distance::map(number * factor, *index))
-            }
-        }
-        err.add(pos, format!("unknown distance unit '{}'", unit));
-        Err(Failed)
+        units::distance(number, unit).map_err(|msg| {
+            err.add(pos, msg);
+            Failed
+        })
     }
 
     fn eval_function<'s>(
@@ -125,15 +125,47 @@ impl From<Layout> for Custom {
 
 //------------ RenderParams --------------------------------------------------
 
+/// The smallest allowed marker scale.
+const MIN_MARKER_SCALE: f64 = 0.25;
+
+/// The largest allowed marker scale.
+const MAX_MARKER_SCALE: f64 = 4.;
+
+/// The parameters set via `with` statements.
+///
+/// The detail range a feature is shown for is determined by the `detail`
+/// and `zoom` params. E.g., `with detail = (1, 3) { ... }` shows all
+/// features of the block for detail levels 1 to 3 and
+/// `with zoom = :low { ... }` further limits them to the lower half of the
+/// zoom levels of each detail level. Individual features can be limited to
+/// a range of zoom levels through the `:minzoomN` and `:maxzoomN` symbols
+/// of their class. Unlike detail levels, these are exact zoom levels, so
+/// `:minzoom3` hides a feature at zoom level 2 even though both levels use
+/// the same detail.
 #[derive(Clone, Debug, Default)]
 pub struct RenderParams {
+    /// The range of detail levels features are shown for.
     detail: Option<(f64, f64)>,
+
+    /// Which zoom levels of each detail level features are shown for.
     zoom: Option<Zoom>,
+
+    /// The layer features are placed in.
     layer: Option<i16>,
+
+    /// The factor to scale markers by.
+    marker_scale: Option<f64>,
+
+    /// The railway class features are based on.
     railway: Railway,
 }
 
 impl RenderParams {
+    /// The names of all render parameters.
+    pub const NAMES: &'static [&'static str] = &[
+        "color", "detail", "layer", "link", "marker_scale", "railway", "zoom",
+    ];
+
     fn from_parent(parent: &Self) -> Self {
         Self {
             railway: parent.railway.clone(),
@@ -148,9 +180,11 @@ impl RenderParams {
         pos: Pos, err: &mut EvalErrors
     ) -> Result<(), Failed> {
         match target {
+            "color" => self.update_color(value, err),
             "detail" => self.update_detail(value, err),
             "layer" => self.update_layer(value, err),
             "link" => self.update_link(value, err),
+            "marker_scale" => self.update_marker_scale(value, err),
             "zoom" => self.update_zoom(value, err),
             "railway" => self.update_railway(value, err),
             "style" => { } // XXX Deprecated
@@ -162,6 +196,16 @@ impl RenderParams {
         Ok(())
     }
 
+    fn update_color(
+        &mut self,
+        value: Expression,
+        err: &mut EvalErrors
+    ) {
+        if let Ok(color) = color_from_value(value, err) {
+            self.railway.set_color(color)
+        }
+    }
+
     fn update_detail(
         &mut self,
         value: Expression,
@@ -219,6 +263,33 @@ impl RenderParams {
         let _ = value.eval::<String>(err);
     }
 
+    fn update_marker_scale(
+        &mut self,
+        value: Expression,
+        err: &mut EvalErrors
+    ) {
+        let pos = value.pos;
+        if let Ok(val) = value.eval::<f64>(err) {
+            match Self::check_marker_scale(val) {
+                Some(val) => self.marker_scale = Some(val),
+                None => err.add(pos, "marker scale must be positive"),
+            }
+        }
+    }
+
+    /// Returns the marker scale to use for a given value.
+    ///
+    /// Returns `None` if the value is not a positive number. Otherwise
+    /// clamps it to the allowed range.
+    fn check_marker_scale(val: f64) -> Option<f64> {
+        if val.is_nan() || val <= 0. {
+            None
+        }
+        else {
+            Some(val.clamp(MIN_MARKER_SCALE, MAX_MARKER_SCALE))
+        }
+    }
+
     fn update_zoom(
         &mut self,
         value: Expression,
@@ -269,6 +340,16 @@ impl RenderParams {
         }
     }
 
+    fn marker_scale(scope: &Scope) -> Option<f64> {
+        if let Some(scale) = scope.custom().marker_scale {
+            return Some(scale)
+        }
+        match scope.parent() {
+            Some(parent) =>  Self::marker_scale(parent),
+            None => None
+        }
+    }
+
     fn railway<'s>(scope: &'s Scope) -> &'s Railway {
         &scope.custom().railway
     }
@@ -286,6 +367,8 @@ pub trait ScopeExt {
 
     fn layer(&self) -> i16;
 
+    fn marker_scale(&self) -> f64;
+
     fn railway(&self) -> &Railway;
 
     fn base_gauge(&self) -> u16;
@@ -329,6 +412,10 @@ impl<'s> ScopeExt for Scope<'s> {
         RenderParams::layer(self).unwrap_or(0)
     }
 
+    fn marker_scale(&self) -> f64 {
+        RenderParams::marker_scale(self).unwrap_or(1.)
+    }
+
     fn railway(&self) -> &Railway {
         RenderParams::railway(self)
     }
@@ -338,6 +425,61 @@ impl<'s> ScopeExt for Scope<'s> {
     }
 }
 
+//------------ color_from_value ----------------------------------------------
+
+/// Evaluates an expression into a color.
+///
+/// Accepts both a color value and a string with a hex color code.
+pub fn color_from_value(
+    value: Expression, err: &mut EvalErrors
+) -> Result<Color, Failed> {
+    match value.value {
+        Value::Color(color) => Ok(color),
+        Value::Text(text) => {
+            Color::try_from(text).map_err(|_| {
+                err.add(value.pos, "expected color code");
+                Failed
+            })
+        }
+        _ => {
+            err.add(value.pos, "expected color");
+            Err(Failed)
+        }
+    }
+}
+
+
+//------------ Names ---------------------------------------------------------
+
+/// The kind of a name provided to the map language.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NameKind {
+    /// A function producing a value.
+    Function,
+
+    /// A procedure producing features.
+    Procedure,
+
+    /// A render parameter set through a with statement.
+    RenderParam,
+}
+
+/// Returns all names provided to the map language and their kind.
+///
+/// The names are sorted by kind and then by name. Variables are not
+/// included since they are defined by the map itself.
+pub fn builtin_names() -> Vec<(&'static str, NameKind)> {
+    let mut res = Vec::new();
+    res.extend(functions::names().map(|name| (name, NameKind::Function)));
+    res.extend(procedures::names().map(|name| (name, NameKind::Procedure)));
+    res.extend(
+        RenderParams::NAMES.iter().map(|name| (*name, NameKind::RenderParam))
+    );
+    res.sort_by_key(|(name, kind)| (*kind as u8, *name));
+    res
+}
+
+
 //------------ Zoom ----------------------------------------------------------
 
 #[derive(Clone, Copy, Debug)]
@@ -365,3 +507,31 @@ impl Zoom {
     }
 }
 
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn builtin_names() {
+        let names = super::builtin_names();
+        assert!(names.contains(&("track", NameKind::Procedure)));
+        assert!(names.contains(&("path", NameKind::Function)));
+        assert!(names.contains(&("detail", NameKind::RenderParam)));
+        assert!(names.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn check_marker_scale() {
+        let check = RenderParams::check_marker_scale;
+        assert_eq!(check(1.5), Some(1.5));
+        assert_eq!(check(0.1), Some(MIN_MARKER_SCALE));
+        assert_eq!(check(f64::INFINITY), Some(MAX_MARKER_SCALE));
+        assert_eq!(check(0.), None);
+        assert_eq!(check(-1.), None);
+        assert_eq!(check(f64::NAN), None);
+        assert_eq!(check(f64::NEG_INFINITY), None);
+    }
+}
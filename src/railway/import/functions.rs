@@ -27,6 +27,11 @@ pub fn eval<'s>(
     (function.1)(args, scope, paths, err)
 }
 
+/// Returns the names of all functions.
+pub fn names() -> impl Iterator<Item = &'static str> {
+    FUNCTIONS.iter().map(|f| f.0)
+}
+
 
 //------------ Functions -----------------------------------------------------
 
@@ -1,11 +1,13 @@
 
 use std::fmt;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 use femtomap::import::eval::{Builtin as _, LoadErrors};
 use femtomap::import::path::{ImportPathSet, PathSetError};
 use femtomap::import::watch::WatchSet;
-use crate::config::Region;
+use crate::config::{MeasuresConfig, Region};
 use crate::railway::feature::{Store, StoreBuilder};
+use crate::railway::feature::svgmarker::{SvgMarkerErrors, SvgMarkerSet};
 use super::eval::Builtin;
 
 
@@ -13,6 +15,7 @@ use super::eval::Builtin;
 
 pub struct LoadFeatures {
     features: Arc<Mutex<StoreBuilder>>,
+    markers: Arc<SvgMarkerSet>,
     err: ImportError,
 }
 
@@ -20,18 +23,56 @@ impl LoadFeatures {
     pub fn new() -> Self {
         LoadFeatures {
             features: Default::default(),
+            markers: Default::default(),
             err: Default::default(),
         }
     }
 
+    /// Only keeps features that overlap an area.
+    ///
+    /// The area is given in storage coordinates. This needs to be done
+    /// before loading any regions.
+    pub fn set_limit(&mut self, limit: kurbo::Rect) {
+        self.features.lock().unwrap().set_limit(limit)
+    }
+
+    /// Sets the measures the map will be rendered with.
+    ///
+    /// Some features need them to determine their bounds. This needs to be
+    /// done before loading any regions.
+    pub fn set_measures(&mut self, measures: &MeasuresConfig) {
+        self.features.lock().unwrap().set_measures(*measures)
+    }
+
+    /// Loads additional markers from a directory of SVG files.
+    ///
+    /// This needs to be done before loading any regions or the markers
+    /// won’t be available to them. The directory is added to `watch` so
+    /// that changed markers trigger a reload.
+    pub fn load_markers(&mut self, dir: &Path, watch: &mut WatchSet) {
+        watch.add(dir);
+        match SvgMarkerSet::load(dir) {
+            Ok(markers) => self.markers = Arc::new(markers),
+            Err(err) => self.err.markers.extend(err),
+        }
+    }
+
+    /// Loads the region with the given name.
+    ///
+    /// The name is used to keep track of the extent of the region.
     pub fn load_region(
         &mut self,
+        name: &str,
         region: &Region,
         watch: &mut WatchSet,
     ) {
+        self.features.lock().unwrap().set_region(name);
         let builtin = match ImportPathSet::load(&region.paths, watch) {
             Ok(paths) => {
-                Builtin::new(paths, self.features.clone(), region.gauge)
+                Builtin::new(
+                    paths, self.features.clone(), self.markers.clone(),
+                    region.gauge
+                )
             }
             Err(err) => {
                 self.err.paths.extend(err);
@@ -62,11 +103,14 @@ impl LoadFeatures {
 pub struct ImportError {
     paths: PathSetError,
     rules: LoadErrors,
+    markers: SvgMarkerErrors,
 }
 
 impl ImportError {
     fn check(self) -> Result<(), Self> {
-        if !self.paths.is_empty() || !self.rules.is_empty() {
+        if !self.paths.is_empty() || !self.rules.is_empty()
+            || !self.markers.is_empty()
+        {
             Err(self)
         }
         else {
@@ -78,7 +122,8 @@ impl ImportError {
 impl fmt::Display for ImportError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.paths.fmt(f)?;
-        self.rules.fmt(f)
+        self.rules.fmt(f)?;
+        self.markers.fmt(f)
     }
 }
 
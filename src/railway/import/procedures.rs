@@ -8,15 +8,16 @@ use crate::railway::class::Railway;
 use crate::railway::feature::{label, marker};
 use crate::railway::feature::{FeatureSetBuilder, StoreBuilder};
 use crate::railway::feature::border::BorderContour;
-use crate::railway::feature::area::{AreaContour, PlatformContour};
+use crate::railway::feature::area::{AreaContour, AreaFill, PlatformContour};
 use crate::railway::feature::dot::DotMarker;
 use crate::railway::feature::guide::GuideContour;
+use crate::railway::feature::route::RouteContour;
 use crate::railway::feature::label::{
     Anchor, Layout, FontSize, Label, Block, BlockProperties, TextAnchor,
 };
 use crate::railway::feature::track::{TrackCasing, TrackClass, TrackContour};
 use super::units;
-use super::eval::{ArgumentList, Scope, ScopeExt};
+use super::eval::{ArgumentList, Scope, ScopeExt, color_from_value};
 
 //------------ eval ----------------------------------------------------------
 
@@ -38,6 +39,11 @@ pub fn eval<'s>(
     (procedure.1)(pos, args, scope, err)
 }
 
+/// Returns the names of all procedures.
+pub fn names() -> impl Iterator<Item = &'static str> {
+    PROCEDURES.iter().map(|f| f.0)
+}
+
 
 //------------ Procedures ----------------------------------------------------
 
@@ -52,13 +58,26 @@ const PROCEDURES: &[(
 )] = &[
     // Draws an area.
     //
+    // ```text
+    // area(class: symbol-set, path)
+    // ```
+    //
+    // The class may contain `:hatch` or `:crosshatch` to fill the area
+    // with hatching instead of solid. See `AreaFill::from_symbols` for the
+    // symbols controlling angle and spacing of the lines.
     ("area", &|pos, args, scope, err| {
         let [class, trace] = args.into_array(err)?;
-        let class = Railway::from_arg(class, scope, err)?;
-        let trace = trace.eval(err)?;
+        let class = class.eval::<SymbolSet>(err).and_then(|mut symbols| {
+            let fill = AreaFill::from_symbols(&mut symbols);
+            let class = Railway::from_symbols(&mut symbols, scope);
+            symbols.check_exhausted(err)?;
+            Ok((class, fill))
+        });
+        let trace = trace.eval(err);
+        let ((class, fill), trace) = (class?, trace?);
         scope.builtin().with_store(|store| {
             store.railway.insert(
-                AreaContour::new(class, trace),
+                AreaContour::new(class, fill, trace),
                 scope.detail(pos, err)?,
                 scope.layer(),
                 //-100,
@@ -114,8 +133,9 @@ const PROCEDURES: &[(
     // ```
     ("casing", &|pos, args, scope, err| {
         let [class, trace] = args.into_array(err)?;
-        let class = TrackClass::from_arg(class, scope, err)?;
-        let trace = trace.eval(err)?;
+        let class = TrackClass::from_arg(class, scope, err);
+        let trace = trace.eval(err);
+        let (class, trace) = (class?, trace?);
         scope.builtin().with_store(|store| {
             store.railway.insert(
                 TrackCasing::new(class, trace),
@@ -359,13 +379,25 @@ const PROCEDURES: &[(
     // Draw a symbol.
     //
     // ```text
-    // marker(marker: symbol-set, position: position)
+    // marker(marker: symbol-set, position: position[, angle: number])
     // ```
+    //
+    // The optional angle is given in degrees and rotates the marker
+    // clockwise in addition to any orientation symbols.
     ("marker", &|pos, args, scope, err| {
-        let [class, position] = args.into_array(err)?;
+        let (class, position, angle) = match args.try_into_array() {
+            Ok([class, position, angle]) => (class, position, Some(angle)),
+            Err(args) => {
+                let [class, position] = args.into_array(err)?;
+                (class, position, None)
+            }
+        };
         let class = class.eval::<SymbolSet>(err);
-        let position = position.eval::<Position>(err)?;
+        let position = position.eval::<Position>(err);
+        let angle = angle.map(|angle| angle.eval::<f64>(err)).transpose();
         let mut class = class?;
+        let position = position?;
+        let angle = angle?;
 
         scope.builtin().with_store(|store| {
             match DotMarker::try_from_arg(
@@ -380,7 +412,7 @@ const PROCEDURES: &[(
                 }
                 None => {
                     let marker = marker::from_args(
-                        class, position, scope, err
+                        class, position, angle, scope, err
                     )?;
                     store.railway.insert(
                         marker,
@@ -393,19 +425,134 @@ const PROCEDURES: &[(
         })
     }),
 
+    // Draws a marker with a label next to it.
+    //
+    // ```text
+    // marker_label(
+    //     marker: symbol-set, position: position,
+    //     [label: symbol-set,] text: text|layout
+    // )
+    // ```
+    //
+    // The marker is drawn as with `marker`. The label is placed beyond the
+    // marker on the side the marker points to, taking both the direction
+    // of the path and the marker’s orientation into account. If an anchor
+    // is given in the label symbols, that anchor is placed beyond the
+    // marker instead.
+    ("marker_label", &|pos, args, scope, err| {
+        let (class, position, label_class, layout) =
+            match args.try_into_array()
+        {
+            Ok([class, position, label_class, layout]) => {
+                (class, position, Some(label_class), layout)
+            }
+            Err(args) => {
+                let [class, position, layout] = args.into_array(err)?;
+                (class, position, None, layout)
+            }
+        };
+        let class = class.eval::<SymbolSet>(err);
+        let position = position.eval::<Position>(err);
+        let label_class = label_class.map(|label_class| {
+            label_class.eval::<SymbolSet>(err)
+        }).transpose();
+        let text = label::text_from_expr(&layout);
+        let layout = label::layout_from_expr(layout, err);
+        let class = class?;
+        let position = position?;
+        let mut label_class = label_class?.unwrap_or_default();
+        let layout = layout?;
+
+        let marker = marker::StandardMarker::from_arg(
+            class, position.clone(), None, scope, err
+        )?;
+        let anchor = TextAnchor::from_symbols(&mut label_class);
+        let fit = anchor.is_none();
+        let anchor = anchor.unwrap_or_else(|| {
+            TextAnchor::new(Base::Start, Align::Base)
+        });
+        let properties = BlockProperties::from_symbols(
+            &mut label_class, scope
+        );
+        label_class.check_exhausted(err)?;
+        let label = Label::new(
+            Layout::hbox(anchor.h, anchor.v, properties, vec![layout]),
+            position, false, Default::default()
+        ).with_text(text).with_marker(
+            marker.orientation(), marker.scale(), fit
+        );
+
+        scope.builtin().with_store(|store| {
+            let detail = scope.detail(pos, err)?;
+            store.railway.insert(marker, detail, scope.layer());
+            store.railway.insert(label, detail, scope.layer());
+            Ok(())
+        })
+    }),
+
     // Draws a platform.
     //
+    // ```text
+    // platform([class: symbol-set,] [width: number,] path: path)
+    // ```
+    //
+    // Without a width, the path is filled as an area. With a width given in
+    // multiples of _dt,_ a band of that width is drawn along the path at
+    // detail level 4 and above.
     ("platform", &|pos, mut args, scope, err| {
         let mut class_symbols = args.take_first_if_matches(
             err
         )?.unwrap_or_default();
-        let [trace] = args.into_array(err)?;
+        let (width, trace) = match args.try_into_array() {
+            Ok([width, trace]) => (Some(width), trace),
+            Err(args) => {
+                let [trace] = args.into_array(err)?;
+                (None, trace)
+            }
+        };
         let class = Railway::from_symbols(&mut class_symbols, scope);
-        class_symbols.check_exhausted(err)?;
-        let trace = trace.eval(err)?;
+        let exhausted = class_symbols.check_exhausted(err);
+        let width = width.map(|width| width.eval::<f64>(err)).transpose();
+        let trace = trace.eval(err);
+        exhausted?;
+        let (width, trace) = (width?, trace?);
         scope.builtin().with_store(|store| {
+            let contour = PlatformContour::new(
+                class, width, trace, store.measures()
+            );
             store.railway.insert(
-                PlatformContour::new(class, trace),
+                contour,
+                scope.detail(pos, err)?,
+                scope.layer(),
+            );
+            Ok(())
+        })
+    }),
+
+    // Highlights a route.
+    //
+    // ```text
+    // route(color: color, width: number, path: path)
+    // ```
+    //
+    // Draws a line of the given color and width in multiples of _dt_
+    // underneath the track along the path. The color should normally be
+    // translucent, e.g., `hexcolor("ff000060")`.
+    ("route", &|pos, args, scope, err| {
+        let [color, width, trace] = args.into_array(err)?;
+        let color = color_from_value(color, err);
+        let width = width.eval::<f64>(err);
+        let trace = trace.eval::<Trace>(err);
+        let (color, width, trace) = (color?, width?, trace?);
+        if !width.is_finite() || width <= 0. {
+            err.add(pos, "width must be positive");
+            return Err(Failed)
+        }
+        scope.builtin().with_store(|store| {
+            store.railway.insert(
+                RouteContour::new(
+                    scope.railway().clone(), color, width, trace
+                ),
                 scope.detail(pos, err)?,
                 scope.layer(),
             );
@@ -440,16 +587,58 @@ const PROCEDURES: &[(
         })
     }),
 
+    // Draws a marker repeatedly along a path.
+    //
+    // ```text
+    // spaced_marker(marker: symbol-set, path: path, dist: number)
+    // ```
+    //
+    // The path is divided into parts of equal length as close to the
+    // distance given in multiples of _dt_ as possible and the marker is
+    // drawn in the middle of each part. The marker symbols are the same as
+    // for `marker` with the orientation relative to the path’s direction.
+    ("spaced_marker", &|pos, args, scope, err| {
+        let [class, trace, dist] = args.into_array(err)?;
+        let class = class.eval::<SymbolSet>(err);
+        let trace = trace.eval::<Trace>(err);
+        let dist = dist.eval::<f64>(err);
+        let (class, trace, dist) = (class?, trace?, dist?);
+        if !dist.is_finite() || dist <= 0. {
+            err.add(pos, "distance must be positive");
+            return Err(Failed)
+        }
+        let marker = marker::SpacedMarker::from_arg(
+            class, trace, dist, scope, err
+        )?;
+
+        scope.builtin().with_store(|store| {
+            store.railway.insert(
+                marker,
+                scope.detail(pos, err)?,
+                scope.layer(),
+            );
+            Ok(())
+        })
+    }),
+
     // Renders a station dot.
     //
     // ```text
     // statdot([marker: symbol-set,] position: position)
     // ```
+    //
+    // The marker symbols can contain the class of the dot as well as
+    // `:small`, `:medium`, or `:large` for its size and `:filled` or `:open`
+    // for its inside. By default, a dot is drawn on top of a casing. With
+    // `:casing`, only the casing is drawn and with `:over` only the dot,
+    // so the two can be placed in different layers, e.g., to keep the
+    // casing below all track and the dot above.
     ("statdot", &|pos, args, scope, err| {
         let marker = match args.try_into_array() {
             Ok([class, position]) => {
-                let class = class.eval(err)?;
-                let position = position.eval(err)?;
+                let class = class.eval(err);
+                let position = position.eval(err);
+                let (class, position) = (class?, position?);
                 DotMarker::from_arg(
                     class, position, scope, err
                 )?
@@ -495,6 +684,7 @@ const PROCEDURES: &[(
 
         let class = class.eval::<SymbolSet>(err);
         let position = position.eval(err);
+        let text = label::text_from_expr(&name);
         let name = label::block_from_expr(name, err);
         let km = label::block_from_expr(km, err);
 
@@ -622,7 +812,7 @@ const PROCEDURES: &[(
             store.railway.insert(
                 label::Label::new(
                     block, position, false, properties
-                ),
+                ).with_text(text),
                 scope.detail(pos, err)?,
                 scope.layer(),
             );
@@ -630,20 +820,97 @@ const PROCEDURES: &[(
         })
     }),
 
+    // Draws kilometre ticks along a path.
+    //
+    // ```text
+    // ticks([class: symbol-set,] path: path[, every: number])
+    // ```
+    //
+    // A short tick across the path is drawn at every multiple of the
+    // distance given in kilometres from the start of the path. The
+    // distance defaults to one kilometre. If the class contains `:label`,
+    // each tick is labelled with its distance from the start.
+    ("ticks", &|pos, mut args, scope, err| {
+        let mut class_symbols = args.take_first_if_matches(
+            err
+        )?.unwrap_or_default();
+        let (trace, every) = match args.try_into_array() {
+            Ok([trace, every]) => (trace, Some(every)),
+            Err(args) => {
+                let [trace] = args.into_array(err)?;
+                (trace, None)
+            }
+        };
+        let trace = trace.eval::<Trace>(err);
+        let every = every.map(|every| every.eval::<f64>(err)).transpose();
+        let label = class_symbols.take("label");
+        let class = Railway::from_symbols(&mut class_symbols, scope);
+        let exhausted = class_symbols.check_exhausted(err);
+        let (trace, every) = (trace?, every?.unwrap_or(1.));
+        exhausted?;
+        if !every.is_finite() || every <= 0. {
+            err.add(pos, "distance must be positive");
+            return Err(Failed)
+        }
+        let every = every * 1000.;
+
+        scope.builtin().with_store(|store| {
+            let detail = scope.detail(pos, err)?;
+            if label {
+                store.railway.insert(
+                    label::TickLabels::new(
+                        class.clone(), trace.clone(), every,
+                        store.measures(),
+                    ),
+                    detail, scope.layer(),
+                );
+            }
+            store.railway.insert(
+                marker::Ticks::new(
+                    class, trace, every, scope, store.measures()
+                ),
+                detail, scope.layer(),
+            );
+            Ok(())
+        })
+    }),
+
     // Renders a track.
     //
     // ```text
-    // track([class: symbol-set, ]path: path)
+    // track([class: symbol-set, [combined: symbol-set, ]]path: path)
     // ```
+    //
+    // If the class contains `:combined` and is followed by a second symbol
+    // set, the track carries two lines. The second set gives the class of
+    // the other line, taking everything not given from the first class.
+    // The track is then drawn in alternating colors of the two lines except
+    // at the simple track level of the lowest zoom levels where only the
+    // first line is shown. Without a second set, `:combined` is ignored.
     ("track", &|pos, mut args, scope, err| {
         let mut class_symbols = args.take_first_if_matches(
             err
         )?.unwrap_or_default();
+        let combined: Option<SymbolSet> = if class_symbols.take("combined") {
+            args.take_first_if_matches(err)?
+        }
+        else {
+            None
+        };
         let [trace] = args.into_array(err)?;
-        let trace = trace.eval(err)?;
-        let class = TrackClass::from_symbols(&mut class_symbols, scope);
+        let trace = trace.eval(err);
+        let mut class = TrackClass::from_symbols(&mut class_symbols, scope);
         let casing = class_symbols.take("casing");
-        class_symbols.check_exhausted(err)?;
+        let exhausted = class_symbols.check_exhausted(err);
+        let combined = combined.map(|mut symbols| {
+            let other = Railway::from_symbols_only(&mut symbols);
+            symbols.check_exhausted(err).map(|_| other)
+        }).transpose();
+        let trace = trace?;
+        exhausted?;
+        if let Some(other) = combined? {
+            class.set_combined(other)
+        }
 
         scope.builtin().with_store(|store| {
             store.railway.insert(
@@ -942,9 +1209,10 @@ impl LabelArgs {
         let [class, position, layout] = args.into_array(err)?;
         let class = class.eval::<SymbolSet>(err);
         let position = position.eval::<Position>(err);
-        let layout = label::layout_from_expr(layout, err)?;
+        let layout = label::layout_from_expr(layout, err);
         let mut class = class?;
         let position = position?;
+        let layout = layout?;
 
         let linenum = class.take("linenum");
         let anchor = match TextAnchor::from_symbols(&mut class) {
@@ -121,3 +121,49 @@ pub fn dt(value: f64) -> Distance {
     Distance::map(value, 1)
 }
 
+
+//------------ Resolving Units -----------------------------------------------
+
+/// Converts a number and a unit into a distance.
+///
+/// Returns an error message if the unit is unknown or the resulting value
+/// isn’t finite. The latter would only cause trouble much later when the
+/// feature is stored or rendered.
+pub fn distance(number: f64, unit: &str) -> Result<Distance, String> {
+    let res = WORLD_DISTANCES.iter().find_map(|(name, factor)| {
+        (unit == *name).then(|| (number * factor, None))
+    }).or_else(|| {
+        MAP_DISTANCES.iter().find_map(|(name, index, factor)| {
+            (unit == *name).then(|| (number * factor, Some(*index)))
+        })
+    });
+    match res {
+        Some((value, _)) if !value.is_finite() => {
+            Err(format!("distance {}{} is not finite", number, unit))
+        }
+        Some((value, None)) => Ok(Distance::world(value)),
+        Some((value, Some(index))) => Ok(Distance::map(value, index)),
+        None => Err(format!("unknown distance unit '{}'", unit)),
+    }
+}
+
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn non_finite_distances() {
+        for unit in ["m", "km", "bp", "dt", "sw"] {
+            assert!(distance(1., unit).is_ok());
+            assert!(distance(f64::NAN, unit).is_err());
+            assert!(distance(f64::INFINITY, unit).is_err());
+            assert!(distance(f64::NEG_INFINITY, unit).is_err());
+        }
+        assert!(distance(f64::MAX, "km").is_err());
+        assert!(distance(1., "furlong").is_err());
+    }
+}
+
@@ -0,0 +1,340 @@
+//! The map legend.
+//!
+//! The legend shows a sample of each railway category, status, and level of
+//! passenger service as well as each built-in marker next to its name. The
+//! samples are drawn by the same code that renders the map, so the legend
+//! always matches it.
+
+use femtomap::render::Canvas;
+use kurbo::{PathEl, Point};
+use crate::tile;
+use crate::tile::{
+    Background, DEFAULT_TILE_SIZE, Surface, TileFormat, TileId
+};
+use super::class::{Category, Pax, Railway, Status};
+use super::feature::{label, marker, CategorySet};
+use super::feature::track::{self, TrackClass};
+use super::style::Style;
+
+
+//------------ Configurable Constants ----------------------------------------
+
+/// The zoom level whose style is used for the legend.
+const ZOOM: u8 = 13;
+
+/// The margin around the legend in dt.
+const MARGIN: f64 = 2.;
+
+/// The space between a sample and its name in dt.
+const GAP: f64 = 2.;
+
+
+//------------ Legend --------------------------------------------------------
+
+/// The content of the legend.
+pub struct Legend {
+    /// The sections of the legend in the order they are shown.
+    sections: Vec<Section>,
+}
+
+impl Legend {
+    /// Creates the legend with all categories, statuses, and markers.
+    pub fn new() -> Self {
+        Legend {
+            sections: vec![
+                Section::new("category", Category::ALL.iter().map(|cat| {
+                    let mut class = Self::base_class();
+                    class.set_category(*cat);
+                    Entry::track(format!("{:?}", cat), class)
+                })),
+                Section::new("status", Status::ALL.iter().map(|status| {
+                    let mut class = Self::base_class();
+                    class.set_status(*status);
+                    Entry::track(format!("{:?}", status), class)
+                })),
+                Section::new("pax", Pax::ALL.iter().map(|pax| {
+                    let mut class = Self::base_class();
+                    class.set_pax(*pax);
+                    Entry::track(format!("{:?}", pax), class)
+                })),
+                Section::new(
+                    "marker",
+                    marker::builtin_names().into_iter().map(|name| {
+                        Entry {
+                            label: name.into(),
+                            sample: Sample::Marker(name),
+                        }
+                    })
+                ),
+            ]
+        }
+    }
+
+    /// Returns the class the samples are derived from.
+    ///
+    /// This is an open first class line with full passenger service.
+    fn base_class() -> Railway {
+        let mut res = Railway::default();
+        res.set_category(Category::First);
+        res.set_pax(Pax::Full);
+        res
+    }
+
+    /// Returns the number of rows of the legend.
+    ///
+    /// Each section has a row for its title plus one per entry.
+    pub fn rows(&self) -> usize {
+        self.sections.iter().map(|section| {
+            section.entries.len() + 1
+        }).sum()
+    }
+
+    /// Returns the tile ID for the style of the legend.
+    pub fn tile_id(layer: tile::LayerId, format: TileFormat) -> TileId {
+        TileId {
+            layer, format,
+            zoom: ZOOM,
+            x: 0,
+            y: 0,
+            proof: false,
+            size: DEFAULT_TILE_SIZE,
+            year: None,
+            categories: CategorySet::all(),
+            classes: Default::default(),
+            labels: Default::default(),
+            bounds: false,
+            background: Background::Paper,
+            fraction: 0,
+        }
+    }
+
+    /// Returns the height of a row in canvas units.
+    fn row_height(style: &Style) -> f64 {
+        style.measures().sh() + 2. * style.measures().dt()
+    }
+
+    /// Returns the length of a sample in canvas units.
+    fn sample_width(style: &Style) -> f64 {
+        2. * style.measures().seg()
+    }
+
+    /// Returns the size of the legend in canvas units.
+    ///
+    /// The canvas is only used to measure the text.
+    pub fn size(&self, style: &Style, canvas: &Canvas) -> Point {
+        let dt = style.measures().dt();
+        let text = Self::sample_width(style) + GAP * dt;
+        let width = self.sections.iter().flat_map(|section| {
+            std::iter::once(
+                label::legend_width(section.title, style, canvas)
+            ).chain(section.entries.iter().map(|entry| {
+                text + label::legend_width(&entry.label, style, canvas)
+            }))
+        }).fold(0., f64::max);
+        Point::new(
+            2. * MARGIN * dt + width,
+            2. * MARGIN * dt + self.rows() as f64 * Self::row_height(style),
+        )
+    }
+
+    /// Renders the legend into an image of the given format.
+    ///
+    /// The size of the image depends on the entries of the legend and the
+    /// length of their names.
+    pub fn render_image(&self, style: &Style, format: TileFormat) -> Vec<u8> {
+        let size = {
+            let surface = Surface::new_map_key(format, Point::new(1., 1.));
+            self.size(style, &Canvas::new(&surface))
+        };
+        let surface = Surface::new_map_key(
+            format,
+            Point::new(
+                size.x / format.canvas_bp(), size.y / format.canvas_bp()
+            )
+        );
+        self.render(size, style, &mut Canvas::new(&surface));
+        surface.finalize()
+    }
+
+    /// Renders the legend onto a canvas of the given size.
+    fn render(&self, size: Point, style: &Style, canvas: &mut Canvas) {
+        canvas.sketch()
+            .apply(style.background_color())
+            .apply([
+                PathEl::MoveTo(Point::new(0., 0.)),
+                PathEl::LineTo(Point::new(size.x, 0.)),
+                PathEl::LineTo(Point::new(size.x, size.y)),
+                PathEl::LineTo(Point::new(0., size.y)),
+                PathEl::ClosePath,
+            ])
+            .fill();
+
+        let dt = style.measures().dt();
+        let row = Self::row_height(style);
+        let sample = Self::sample_width(style);
+        let left = MARGIN * dt;
+        let text = left + sample + GAP * dt;
+        let mut y = MARGIN * dt + 0.5 * row;
+        for section in &self.sections {
+            label::render_legend(
+                section.title, Point::new(left, y), style, canvas
+            );
+            y += row;
+            for entry in &section.entries {
+                entry.sample.render(left, sample, y, style, canvas);
+                label::render_legend(
+                    &entry.label, Point::new(text, y), style, canvas
+                );
+                y += row;
+            }
+        }
+    }
+}
+
+impl Default for Legend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+//------------ Section -------------------------------------------------------
+
+/// A section of the legend.
+struct Section {
+    /// The title of the section.
+    title: &'static str,
+
+    /// The entries of the section.
+    entries: Vec<Entry>,
+}
+
+impl Section {
+    fn new(
+        title: &'static str, entries: impl Iterator<Item = Entry>
+    ) -> Self {
+        Section { title, entries: entries.collect() }
+    }
+}
+
+
+//------------ Entry ---------------------------------------------------------
+
+/// A single entry of the legend.
+struct Entry {
+    /// The name shown next to the sample.
+    label: String,
+
+    /// The sample to draw.
+    sample: Sample,
+}
+
+impl Entry {
+    fn track(label: String, class: Railway) -> Self {
+        Entry {
+            label: label.to_lowercase(),
+            sample: Sample::Track(TrackClass::new(class)),
+        }
+    }
+}
+
+
+//------------ Sample --------------------------------------------------------
+
+/// What is drawn for an entry.
+enum Sample {
+    /// A piece of track of the given class.
+    Track(TrackClass),
+
+    /// The built-in marker of the given name.
+    Marker(&'static str),
+}
+
+impl Sample {
+    /// Renders the sample.
+    ///
+    /// The sample starts at `left` and is `width` long. It is vertically
+    /// centered on `y`.
+    fn render(
+        &self, left: f64, width: f64, y: f64,
+        style: &Style, canvas: &mut Canvas,
+    ) {
+        match *self {
+            Sample::Track(ref class) => {
+                track::render_legend(
+                    class, Point::new(left, y), Point::new(left + width, y),
+                    style, canvas
+                )
+            }
+            Sample::Marker(name) => {
+                // Markers hang down from their point, so we move it up by
+                // half their height.
+                marker::render_legend(
+                    name, &Legend::base_class(),
+                    Point::new(
+                        left + 0.5 * width,
+                        y - 0.5 * style.measures().sh()
+                    ),
+                    style, canvas
+                )
+            }
+        }
+    }
+}
+
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::map::{LayerId, ScriptId};
+    use super::super::style::StyleSettings;
+
+    /// Returns width and height of a PNG image.
+    fn png_size(data: &[u8]) -> (u32, u32) {
+        assert_eq!(&data[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&data[12..16], b"IHDR");
+        (
+            u32::from_be_bytes(data[16..20].try_into().unwrap()),
+            u32::from_be_bytes(data[20..24].try_into().unwrap()),
+        )
+    }
+
+    #[test]
+    fn legend_size() {
+        let layer_id = LayerId::El(ScriptId::Original);
+        let tile_id = Legend::tile_id(
+            tile::LayerId::Railway(layer_id), TileFormat::Png
+        );
+        let style = Style::new(
+            layer_id, &tile_id, &StyleSettings::default()
+        );
+        let legend = Legend::new();
+        let (width, height) = png_size(
+            &legend.render_image(&style, TileFormat::Png)
+        );
+
+        // The image is large enough for the longest name next to its
+        // sample and for all rows.
+        let surface = Surface::new_map_key(
+            TileFormat::Png, Point::new(1., 1.)
+        );
+        let canvas = Canvas::new(&surface);
+        let dt = style.measures().dt();
+        let longest = legend.sections.iter().flat_map(|section| {
+            section.entries.iter()
+        }).map(|entry| {
+            label::legend_width(&entry.label, &style, &canvas)
+        }).fold(0., f64::max);
+        assert!(longest > 0.);
+        assert!(
+            f64::from(width) + 1. >= 2. * MARGIN * dt
+                + Legend::sample_width(&style) + GAP * dt + longest
+        );
+        assert!(
+            f64::from(height) + 1. >= 2. * MARGIN * dt
+                + legend.rows() as f64 * Legend::row_height(&style)
+        );
+    }
+}
@@ -1,13 +1,21 @@
 //! Binding it all together.
 
+use std::collections::{BTreeMap, HashSet};
 use std::str::FromStr;
 use femtomap::render::Canvas;
-use kurbo::{Point, Rect};
+use crate::config::{MapConfig, ScaleBarConfig};
+use kurbo::{PathEl, Point, Rect};
 use crate::tile;
-use crate::tile::{Surface, TileId, TileIdError};
+use crate::tile::{Surface, TileFormat, TileId, TileIdError};
 use super::colors::ColorSet;
-use super::feature::{FeatureSet, StageSet, Store};
-use super::style::{Style, StyleId};
+use super::decoration;
+use serde::Serialize;
+use super::feature::{
+    AnyFeature, AnyShape, FeatureDump, FeatureInfo, FeatureSet, StageSet,
+    Store,
+};
+use super::legend::Legend;
+use super::style::{Style, StyleId, StyleSettings};
 
 
 //------------ Map -----------------------------------------------------------
@@ -17,14 +25,117 @@ pub struct Map {
     /// All the features to render.
     features: Store,
 
-    /// The color set for rendering.
-    colors: ColorSet,
+    /// The settings for creating styles.
+    settings: StyleSettings,
+
+    /// Where to draw a scale bar if at all.
+    scale_bar: Option<ScaleBarConfig>,
+
+    /// The version of the source data.
+    version: u64,
 }
 
 impl Map {
     /// Creates a new map.
-    pub fn new(features: Store) -> Self {
-        Self { features, colors: Default::default() }
+    ///
+    /// The `version` identifies the source data the features were loaded
+    /// from. See [`MapConfig::version`] for how to get it.
+    pub fn new(features: Store, config: &MapConfig, version: u64) -> Self {
+        Self {
+            features,
+            settings: StyleSettings {
+                colors: ColorSet::with_operators(&config.operators),
+                measures: config.measures,
+                track_detail: config.track_detail,
+                label_collisions: config.label_collisions,
+                min_line_width: config.min_line_width,
+            },
+            scale_bar: config.scale_bar,
+            version,
+        }
+    }
+
+    /// Returns the version of the source data.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns the number of features in each of the feature sets.
+    pub fn feature_counts(&self) -> [(&'static str, usize); 4] {
+        self.feature_sets().map(|(name, set)| (name, set.len()))
+    }
+
+    /// Returns all feature sets with their names.
+    pub fn feature_sets(&self) -> [(&'static str, &FeatureSet); 4] {
+        [
+            ("railway", &self.features.railway),
+            ("line_labels", &self.features.line_labels),
+            ("tt_labels", &self.features.tt_labels),
+            ("borders", &self.features.borders),
+        ]
+    }
+
+    /// Returns all features loaded from the given region.
+    ///
+    /// The features of all feature sets are included.
+    pub fn region_features<'a>(
+        &'a self, region: &'a str
+    ) -> impl Iterator<Item = &'a AnyFeature> + 'a {
+        self.feature_sets().into_iter().flat_map(|(_, set)| {
+            set.iter()
+        }).filter(move |feature| {
+            feature.id().map(|id| id.region() == region).unwrap_or(false)
+        })
+    }
+
+    /// Returns a style for measuring features.
+    ///
+    /// This style can be used with [`AnyFeature::length`]. It shows all
+    /// years and categories.
+    pub fn measure_style(&self) -> Style {
+        let tile_id = Legend::tile_id(
+            tile::LayerId::Railway(LayerId::El(ScriptId::Original)),
+            TileFormat::Png
+        );
+        Style::new(
+            LayerId::El(ScriptId::Original), &tile_id, &self.settings
+        )
+    }
+
+    /// Returns the extent of all loaded regions.
+    pub fn regions(&self) -> Vec<RegionInfo> {
+        self.features.regions.iter().map(|(name, extent)| {
+            RegionInfo {
+                name: name.clone(),
+                count: extent.count,
+                bounds: extent.bounds.map(|bounds| {
+                    // Storage y grows southward.
+                    let (west, north) = tile::lonlat(
+                        Point::new(bounds.x0, bounds.y0)
+                    );
+                    let (east, south) = tile::lonlat(
+                        Point::new(bounds.x1, bounds.y1)
+                    );
+                    [west, south, east, north]
+                }),
+            }
+        }).collect()
+    }
+
+    /// Returns a description of all features without rendering them.
+    pub fn dump(&self) -> MapDump {
+        let dump_set = |set: &FeatureSet| {
+            set.iter().map(|feature| feature.dump()).collect()
+        };
+        MapDump {
+            schema: MapDump::SCHEMA,
+            version: self.version,
+            counts: self.feature_counts().into_iter().collect(),
+            railway: dump_set(&self.features.railway),
+            line_labels: dump_set(&self.features.line_labels),
+            tt_labels: dump_set(&self.features.tt_labels),
+            borders: dump_set(&self.features.borders),
+        }
     }
 
     /// Renders a map tile.
@@ -32,23 +143,50 @@ impl Map {
         &self, tile_id: TileId, surface: &Surface
     ) -> Result<(), TileIdError> {
         let layer_id = LayerId::try_from(tile_id.layer)?;
-        let style = Style::new(layer_id, &tile_id, &self.colors);
+        let style = Style::new(layer_id, &tile_id, &self.settings);
         let mut canvas = Canvas::new(surface);
-        let size = tile_id.format.size();
+        let size = tile_id.canvas_size();
         canvas.set_clip(Rect::new(0., 0., size, size));
-        let shapes = layer_id.features(&self.features).shape(
-            style.store_scale(),
-            Self::feature_bounds(tile_id, &style).into(),
-            &style, &canvas,
-        );
+        if matches!(tile_id.background, tile::Background::Paper) {
+            canvas.sketch()
+                .apply(style.background_color())
+                .apply([
+                    PathEl::MoveTo(Point::new(0., 0.)),
+                    PathEl::LineTo(Point::new(size, 0.)),
+                    PathEl::LineTo(Point::new(size, size)),
+                    PathEl::LineTo(Point::new(0., size)),
+                    PathEl::ClosePath,
+                ])
+                .fill();
+        }
 
-        for group in shapes.layer_groups() {
-            let stages = group.iter().fold(StageSet::empty(), |set, shape| {
-                set.add_set(shape.shape().stages())
-            });
-            for stage in stages.iter() {
-                group.iter().for_each(|shape| {
-                    shape.shape().render(stage, &style, &mut canvas)
+        // Additional label sets are drawn after the layer’s own features
+        // so they end up on top of everything else.
+        let mut sets = vec![layer_id.features(&self.features)];
+        if layer_id.is_base() {
+            if tile_id.labels.line {
+                sets.push(&self.features.line_labels);
+            }
+            if tile_id.labels.tt {
+                sets.push(&self.features.tt_labels);
+            }
+        }
+        for &set in &sets {
+            self.render_set(set, tile_id, &style, &mut canvas);
+        }
+
+        if let Some(scale_bar) = self.scale_bar.as_ref() {
+            decoration::render(scale_bar, &tile_id, &style, &mut canvas);
+        }
+
+        // The bounds overlay goes on top of everything.
+        if style.show_bounds() {
+            for set in sets {
+                set.locate(
+                    style.store_scale(),
+                    Self::feature_bounds(tile_id, &style).into()
+                ).for_each(|feature| {
+                    feature.render_bounds(&style, &mut canvas)
                 });
             }
         }
@@ -56,8 +194,136 @@ impl Map {
         Ok(())
     }
 
+    /// Renders the legend for a layer.
+    ///
+    /// The size of the image depends on the entries in the legend and the
+    /// length of their names.
+    pub fn render_legend(
+        &self, layer: tile::LayerId, format: TileFormat
+    ) -> Result<Vec<u8>, TileIdError> {
+        let layer_id = LayerId::try_from(layer)?;
+        let tile_id = Legend::tile_id(layer, format);
+        let style = Style::new(layer_id, &tile_id, &self.settings);
+        Ok(Legend::new().render_image(&style, format))
+    }
+
+    /// Renders the features of a single feature set.
+    fn render_set(
+        &self, features: &FeatureSet, tile_id: TileId, style: &Style,
+        canvas: &mut Canvas,
+    ) {
+        let mut bounds = Self::feature_bounds(tile_id, style);
+        if style.label_collisions() {
+            // Labels reaching into the tile can collide with labels
+            // further out, so select those, too.
+            let margin = 0.5 * (bounds.width() - 1. / tile_id.n());
+            bounds = bounds.inflate(margin, margin);
+        }
+        let shapes = features.shape(
+            style.store_scale(), bounds.into(), style, canvas,
+        );
+
+        let groups = shapes.layer_groups().collect::<Vec<_>>();
+
+        // If enabled, determine which labels to suppress because they
+        // overlap other labels. Labels in later groups are drawn on top and
+        // have priority. Within a group, the label that comes first wins.
+        //
+        // A label is suppressed if it overlaps any label of higher
+        // priority, even if that label is suppressed itself. This way,
+        // the decision only depends on the labels around it and all tiles
+        // showing a label come to the same conclusion.
+        let mut suppressed = HashSet::new();
+        if style.label_collisions() {
+            let boxes = groups.iter().enumerate().rev().flat_map(
+                |(group_idx, group)| {
+                    group.iter().enumerate().filter_map(
+                        move |(shape_idx, shape)| {
+                            Some((
+                                (group_idx, shape_idx),
+                                shape.shape().collision_box()?
+                            ))
+                        }
+                    )
+                }
+            ).collect::<Vec<_>>();
+            for (idx, (key, bounds)) in boxes.iter().enumerate() {
+                if boxes[..idx].iter().any(|(_, other)| {
+                    !other.intersect(*bounds).is_zero_area()
+                }) {
+                    suppressed.insert(*key);
+                }
+            }
+        }
+
+        for (group_idx, group) in groups.iter().enumerate() {
+            Self::render_group(
+                group.iter().enumerate().filter(|(shape_idx, _)| {
+                    !suppressed.contains(&(group_idx, *shape_idx))
+                }).map(|(_, shape)| shape.shape()).collect(),
+                style, canvas
+            )
+        }
+    }
+
+    /// Renders the shapes of a group stage by stage.
+    ///
+    /// Within each stage, shapes are rendered in order of their z-bias. The
+    /// sort is stable, so shapes with the same bias keep their order.
+    fn render_group(
+        mut shapes: Vec<&AnyShape>, style: &Style, canvas: &mut Canvas
+    ) {
+        shapes.sort_by_key(|shape| shape.z_bias());
+        let stages = shapes.iter().fold(
+            StageSet::empty(), |set, shape| set.add_set(shape.stages())
+        );
+        for stage in stages.iter() {
+            shapes.iter().for_each(|shape| {
+                shape.render(stage, style, canvas)
+            });
+        }
+    }
+
+    /// Returns whether there are any features in a tile.
+    ///
+    /// This only checks whether features are stored for the area of the
+    /// tile, not whether any of them are actually drawn.
+    pub fn has_features(&self, tile_id: TileId) -> Result<bool, TileIdError> {
+        let layer_id = LayerId::try_from(tile_id.layer)?;
+        let style = Style::new(layer_id, &tile_id, &self.settings);
+        Ok(layer_id.features(&self.features).locate(
+            style.store_scale(), Self::feature_bounds(tile_id, &style).into()
+        ).next().is_some())
+    }
+
+    /// Returns information about the features near a point.
+    ///
+    /// The point is given in storage coordinates and is expected to be
+    /// within the tile given by `tile_id` which determines layer, zoom, and
+    /// year. All features within `radius` pixels of the point are returned,
+    /// the topmost features first.
+    pub fn pick(
+        &self, tile_id: TileId, point: Point, radius: f64,
+    ) -> Result<Vec<FeatureInfo>, TileIdError> {
+        let layer_id = LayerId::try_from(tile_id.layer)?;
+        let style = Style::new(layer_id, &tile_id, &self.settings);
+        let canvas_radius = radius * style.canvas_px();
+        let radius = radius / (f64::from(tile_id.size) * tile_id.n());
+        let bounds = Rect::new(
+            point.x - radius, point.y - radius,
+            point.x + radius, point.y + radius,
+        );
+        let mut res = layer_id.features(&self.features).locate(
+            style.store_scale(), bounds.into()
+        ).filter_map(|feature| {
+            feature.pick(point, canvas_radius, &style)
+        }).collect::<Vec<_>>();
+        res.sort_by(|left, right| right.0.cmp(&left.0));
+        Ok(res.into_iter().map(|(_, info)| info).collect())
+    }
+
     fn feature_bounds(id: TileId, style: &Style) -> Rect {
-        let size = id.format.size();
+        let size = id.canvas_size();
         let scale = size * id.n();
         let feature_size = Point::new(size / scale, size / scale);
         let nw = id.nw();
@@ -78,6 +344,64 @@ impl Map {
 }
 
 
+
+//------------ MapDump -------------------------------------------------------
+
+/// A description of all features of a map.
+///
+/// This is what `railmap dump` prints as JSON. The `schema` field is
+/// increased whenever the format changes incompatibly.
+#[derive(Clone, Debug, Serialize)]
+pub struct MapDump {
+    /// The version of the dump format.
+    pub schema: u32,
+
+    /// The version of the source data.
+    pub version: u64,
+
+    /// The number of features in each feature set.
+    pub counts: BTreeMap<&'static str, usize>,
+
+    /// The railway features.
+    pub railway: Vec<FeatureDump>,
+
+    /// The line labels.
+    pub line_labels: Vec<FeatureDump>,
+
+    /// The timetable labels.
+    pub tt_labels: Vec<FeatureDump>,
+
+    /// The borders.
+    pub borders: Vec<FeatureDump>,
+}
+
+impl MapDump {
+    /// The current version of the dump format.
+    pub const SCHEMA: u32 = 1;
+}
+
+//------------ RegionInfo ----------------------------------------------------
+
+/// The extent of a loaded region.
+///
+/// This is what the `/regions` endpoint of the server returns.
+#[derive(Clone, Debug, Serialize)]
+pub struct RegionInfo {
+    /// The name of the region.
+    pub name: String,
+
+    /// The number of features loaded for the region.
+    pub count: usize,
+
+    /// The bounds of the region’s features.
+    ///
+    /// These are given as `[west, south, east, north]` in degrees. The
+    /// field is missing if the region has no features.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bounds: Option<[f64; 4]>,
+}
+
+
 //------------ LayerId -------------------------------------------------------
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
@@ -88,6 +412,15 @@ pub enum LayerId {
     /// Electrification line number map.
     ElNum,
 
+    /// Electrification base map with colorblind-safe colors.
+    ElAcc(ScriptId),
+
+    /// Electrification base map for a dark background.
+    ElDark(ScriptId),
+
+    /// Operator base map.
+    Op(ScriptId),
+
     /// Passenger base map.
     Pax(ScriptId),
 
@@ -104,6 +437,9 @@ impl LayerId {
 
         match self {
             El(_) | ElNum | Border => StyleId::El,
+            ElAcc(_) => StyleId::Accessible,
+            ElDark(_) => StyleId::Dark,
+            Op(_) => StyleId::Operator,
             Pax(_) | PaxNum => StyleId::Pax
         }
     }
@@ -112,7 +448,9 @@ impl LayerId {
         use self::LayerId::*;
 
         match self {
-            El(_) | Pax(_) => &store.railway,
+            El(_) | ElAcc(_) | ElDark(_) | Op(_) | Pax(_) => {
+                &store.railway
+            }
             ElNum => &store.line_labels,
             PaxNum => &store.tt_labels,
             Border => &store.borders,
@@ -121,10 +459,25 @@ impl LayerId {
 
     pub fn latin_text(self) -> bool {
         match self {
-            LayerId::El(id) | LayerId::Pax(id) => id.latin_text(),
+            LayerId::El(id) | LayerId::ElAcc(id) | LayerId::ElDark(id)
+                | LayerId::Op(id) | LayerId::Pax(id)
+            => {
+                id.latin_text()
+            }
             _ => false
         }
     }
+
+    /// Returns whether this is a base layer showing the railway features.
+    ///
+    /// Only base layers can have label sets added.
+    pub fn is_base(self) -> bool {
+        matches!(
+            self,
+            LayerId::El(_) | LayerId::ElAcc(_) | LayerId::ElDark(_)
+                | LayerId::Op(_) | LayerId::Pax(_)
+        )
+    }
 }
 
 impl TryFrom<tile::LayerId> for LayerId {
@@ -145,6 +498,12 @@ impl FromStr for LayerId {
             "el" => Ok(LayerId::El(ScriptId::Original)),
             "el-lat" => Ok(LayerId::El(ScriptId::Latin)),
             "el-num" => Ok(LayerId::ElNum),
+            "el-acc" => Ok(LayerId::ElAcc(ScriptId::Original)),
+            "el-acc-lat" => Ok(LayerId::ElAcc(ScriptId::Latin)),
+            "el-dark" => Ok(LayerId::ElDark(ScriptId::Original)),
+            "el-dark-lat" => Ok(LayerId::ElDark(ScriptId::Latin)),
+            "op" => Ok(LayerId::Op(ScriptId::Original)),
+            "op-lat" => Ok(LayerId::Op(ScriptId::Latin)),
             "pax" => Ok(LayerId::Pax(ScriptId::Original)),
             "pax-lat" => Ok(LayerId::Pax(ScriptId::Latin)),
             "pax-num" => Ok(LayerId::PaxNum),
@@ -155,6 +514,44 @@ impl FromStr for LayerId {
 }
 
 
+//------------ LabelSets -----------------------------------------------------
+
+/// The label sets to add to a base layer.
+///
+/// By default, no label sets are added and line numbers and timetable
+/// numbers are only available through their own layers.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct LabelSets {
+    /// Add the line number labels.
+    pub line: bool,
+
+    /// Add the timetable number labels.
+    pub tt: bool,
+}
+
+impl FromStr for LabelSets {
+    type Err = TileIdError;
+
+    /// Parses a comma separated list of `line` and `tt`.
+    ///
+    /// The value `none` or an empty string results in no label sets.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut res = LabelSets::default();
+        if s.is_empty() || s == "none" {
+            return Ok(res)
+        }
+        for item in s.split(',') {
+            match item {
+                "line" => res.line = true,
+                "tt" => res.tt = true,
+                _ => return Err(TileIdError)
+            }
+        }
+        Ok(res)
+    }
+}
+
+
 //------------ ScriptId ------------------------------------------------------
 
 /// Which script should we prefer for labels?
@@ -173,3 +570,56 @@ impl ScriptId {
     }
 }
 
+
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use crate::railway::feature::{Shape, Stage};
+    use super::*;
+
+    /// A shape noting its name when rendered in the base stage.
+    struct Named<'a> {
+        name: &'static str,
+        log: &'a RefCell<Vec<&'static str>>,
+    }
+
+    impl<'a> Shape<'a> for Named<'a> {
+        fn render(&self, stage: Stage, _style: &Style, _canvas: &mut Canvas) {
+            if matches!(stage, Stage::Base) {
+                self.log.borrow_mut().push(self.name)
+            }
+        }
+
+        fn stages(&self) -> StageSet {
+            StageSet::empty().add(Stage::Base)
+        }
+    }
+
+    #[test]
+    fn render_group_z_bias() {
+        let layer = tile::LayerId::Railway(LayerId::El(ScriptId::Original));
+        let tile_id = Legend::tile_id(layer, TileFormat::Png);
+        let style = Style::new(
+            LayerId::El(ScriptId::Original), &tile_id,
+            &StyleSettings::default(),
+        );
+        let surface = Surface::new_map_key(
+            TileFormat::Png, Point::new(1., 1.)
+        );
+        let mut canvas = Canvas::new(&surface);
+
+        let log = RefCell::new(Vec::new());
+        let biased = AnyShape::from(
+            Named { name: "biased", log: &log }
+        ).with_z_bias(1);
+        let first = AnyShape::from(Named { name: "first", log: &log });
+        let second = AnyShape::from(Named { name: "second", log: &log });
+        Map::render_group(
+            vec![&biased, &first, &second], &style, &mut canvas
+        );
+        assert_eq!(*log.borrow(), ["first", "second", "biased"]);
+    }
+}
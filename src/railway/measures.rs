@@ -5,6 +5,7 @@
 //! detail and zoom levels of the map.
 
 use std::ops;
+use crate::config::MeasuresConfig;
 use super::class::Railway;
 
 
@@ -152,6 +153,100 @@ impl Measures {
     }
 }
 
+/// # Configuration
+impl Measures {
+    /// Returns the measures with the overides from the config applied.
+    pub fn with_config(mut self, config: &MeasuresConfig) -> Self {
+        let values = [
+            config.dt,
+            config.main_track,
+            config.main_double,
+            config.main_skip,
+            config.light_track,
+            config.light_double,
+            config.light_skip,
+            config.guide_width,
+            config.border_width,
+            config.seg,
+            config.station_width,
+            config.station_height,
+        ];
+        for (idx, value) in values.into_iter().enumerate() {
+            if let Some(value) = value {
+                self.0[idx] = value
+            }
+        }
+        self
+    }
+
+    /// Returns the measures between these and `other`.
+    ///
+    /// Each measure is interpolated linearly. A `fraction` of zero results
+    /// in these measures, one in `other`.
+    pub fn interpolate(mut self, other: Self, fraction: f64) -> Self {
+        self.0.iter_mut().zip(other.0).for_each(|(value, other)| {
+            *value += (other - *value) * fraction
+        });
+        self
+    }
+
+    /// Returns warnings about odd results of applying the config.
+    ///
+    /// The config is applied to all the standard measures and the result
+    /// checked whether the measures still relate to each other in a way
+    /// that produces a sensible map. Only relations that hold for the
+    /// standard measures are checked since some detail levels deliberately
+    /// deviate from them, e.g., double track in proof mode.
+    pub fn config_warnings(config: &MeasuresConfig) -> Vec<String> {
+        const SETS: &[(&str, Measures)] = &[
+            ("detail levels 0 and 1", BASE_D0),
+            ("detail level 2", BASE_D2),
+            ("detail level 3", BASE_D3),
+            ("detail level 4", BASE_D4),
+            ("detail level 5", BASE_D5),
+            ("proof detail level 5", BASE_D6),
+        ];
+
+        let checks: [(fn(Measures) -> bool, &str); 6] = [
+            (
+                |m| m.main_track() < m.dt(),
+                "main track is at least as wide as dt"
+            ),
+            (
+                |m| m.light_track() <= m.main_track(),
+                "light track is wider than main track"
+            ),
+            (
+                |m| m.main_double() >= m.main_track(),
+                "main double is narrower than main track"
+            ),
+            (
+                |m| m.light_double() >= m.light_track(),
+                "light double is narrower than light track"
+            ),
+            (
+                |m| m.guide_width() < m.light_track(),
+                "guide width is at least as wide as light track"
+            ),
+            (
+                |m| m.seg() >= 2. * m.dt(),
+                "seg is shorter than two dt"
+            ),
+        ];
+
+        let mut res = Vec::new();
+        for &(name, measures) in SETS {
+            let configured = measures.with_config(config);
+            for (check, msg) in checks {
+                if check(measures) && !check(configured) {
+                    res.push(format!("{}: {}", name, msg))
+                }
+            }
+        }
+        res
+    }
+}
+
 /// # Other measures
 impl Measures {
     /// Returns the import map units array.
@@ -391,3 +486,40 @@ pub const BASE_D6: Measures = Measures([
     5.4,    // badge font
 ]);
 
+
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn interpolate() {
+        assert_eq!(BASE_D2.interpolate(BASE_D3, 0.).0, BASE_D2.0);
+        assert!(
+            BASE_D2.interpolate(BASE_D3, 1.).0.iter().zip(BASE_D3.0).all(
+                |(left, right)| (left - right).abs() < 1e-9
+            )
+        );
+        let half = BASE_D2.interpolate(BASE_D3, 0.5);
+        assert!((half.main_track() - 1.25).abs() < 1e-9);
+        assert!((half.main_double() - 2.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn default_config_warnings() {
+        assert!(
+            Measures::config_warnings(&MeasuresConfig::default()).is_empty()
+        );
+    }
+
+    #[test]
+    fn config_warnings() {
+        let config = MeasuresConfig {
+            main_double: Some(0.5),
+            .. Default::default()
+        };
+        assert!(!Measures::config_warnings(&config).is_empty());
+    }
+}
@@ -1,10 +1,12 @@
 
-pub use self::map::{LayerId, Map};
+pub use self::map::{LabelSets, LayerId, Map, MapDump};
 
 pub mod class;
 pub mod colors;
+pub mod decoration;
 pub mod import;
 pub mod feature;
+pub mod legend;
 pub mod map;
 pub mod measures;
 pub mod style;
@@ -3,9 +3,13 @@
 use femtomap::path::{MapDistance, Transform};
 use femtomap::render::Color;
 use kurbo::{TranslateScale, Vec2};
-use crate::tile::TileId;
+use crate::config::{
+    CasingConfig, LabelScaleConfig, MeasuresConfig, TrackDetailConfig
+};
+use crate::tile::{TileFormat, TileId};
 use super::{class, measures};
 use super::colors::{Colors, ColorSet};
+use super::feature::{Category, CategorySet};
 use super::map::LayerId;
 use super::measures::Measures;
 
@@ -29,6 +33,39 @@ impl Zoom {
     ) -> Self {
         Zoom { store_scale, detail, mag, measures, }
     }
+
+    /// Returns the zoom for a fractional zoom level.
+    ///
+    /// The zoom level is `zoom` plus `fraction` which must be between zero
+    /// and one. The magnification and measures are interpolated between
+    /// the two bracketing zoom levels while the store scale is that of the
+    /// lower one. Returns the zoom, the fractional detail level, and the
+    /// store scale of the upper zoom level so that the features of both
+    /// levels can be selected.
+    fn get(table: &[Zoom], zoom: u8, fraction: f64) -> (Self, f64, f64) {
+        let lower = table[usize::from(zoom)];
+        let upper = match table.get(usize::from(zoom) + 1) {
+            Some(upper) if fraction > 0. => *upper,
+            _ => {
+                return (lower, f64::from(lower.detail), lower.store_scale)
+            }
+        };
+        let between = |lower: f64, upper: f64| {
+            lower + (upper - lower) * fraction
+        };
+        (
+            Zoom {
+                store_scale: lower.store_scale,
+                detail: lower.detail,
+                mag: between(lower.mag, upper.mag),
+                measures: lower.measures.interpolate(
+                    upper.measures, fraction
+                ),
+            },
+            between(f64::from(lower.detail), f64::from(upper.detail)),
+            upper.store_scale,
+        )
+    }
 }
 
 const ZOOM: &[Zoom] = &[
@@ -76,6 +113,65 @@ const PROOF_ZOOM: &[Zoom] = &[
     Zoom::new(5.5, 5, 2.1, measures::BASE_D6),
 ];
 
+/// The largest difference between the store scales of adjacent zoom levels.
+///
+/// Features are inserted into the store with their detail range extended
+/// downwards by this much, so that looking up the lower store scale of a
+/// fractional zoom level also finds the features of the upper one. The
+/// exact range is checked via [`Style::shows_detail`].
+pub const MAX_STORE_SCALE_STEP: f64 = 1.0;
+
+/// Returns the range of store scales used for rendering a zoom level.
+///
+/// Several zoom levels share a store scale and proof mode uses different
+/// store scales for some zoom levels. The range covers both the regular
+/// and the proof mode zoom levels. Since it can’t tell zoom levels with
+/// the same store scale apart, it can only be used to narrow down the
+/// features to look at. Whether a feature is shown at a zoom level needs
+/// to be checked via [`Style::shows_zoom`].
+pub fn zoom_store_scales(zoom: u8) -> (f64, f64) {
+    let regular = ZOOM[usize::from(zoom).min(ZOOM.len() - 1)].store_scale;
+    let proof = PROOF_ZOOM[
+        usize::from(zoom).min(PROOF_ZOOM.len() - 1)
+    ].store_scale;
+    (regular.min(proof), regular.max(proof))
+}
+
+/// Returns the largest size of a _dt_ in storage coordinates.
+///
+/// Only zoom levels of at least the given detail level of both the regular
+/// and the proof mode zoom levels are considered. Since the magnification
+/// is interpolated for fractional zoom levels, the larger magnification of
+/// a level and the next one is used.
+pub fn max_storage_dt(min_detail: u8, measures: &MeasuresConfig) -> f64 {
+    max_storage_size(min_detail, measures, Measures::dt)
+}
+
+/// Returns the largest size of a distance in storage coordinates.
+///
+/// The distance is derived from the measures of a zoom level by `size`.
+/// Zoom levels are considered as for [`max_storage_dt`].
+pub fn max_storage_size(
+    min_detail: u8, measures: &MeasuresConfig,
+    size: impl Fn(Measures) -> f64,
+) -> f64 {
+    let format = TileFormat::Png;
+    let size = &size;
+    [ZOOM, PROOF_ZOOM].into_iter().flat_map(|table| {
+        table.iter().enumerate().filter(|(_, zoom)| {
+            zoom.detail >= min_detail
+        }).map(move |(idx, zoom)| {
+            let mag = match table.get(idx + 1) {
+                Some(next) => zoom.mag.max(next.mag),
+                None => zoom.mag,
+            };
+            size(zoom.measures.with_config(measures))
+                * mag * format.canvas_bp()
+                / (format.size() * f64::from(1u32 << idx))
+        })
+    }).fold(0., f64::max)
+}
+
 /// Size correction for feature bounds.
 ///
 /// This value will be multiplied with detail level, then length and height of
@@ -84,6 +180,11 @@ const PROOF_ZOOM: &[Zoom] = &[
 /// Increase if features are missing.
 const BOUNDS_CORRECTION: f64 = 0.3;
 
+/// The length of the equator in metres.
+///
+/// This is the size of the full range of storage coordinates.
+const EQUATOR_LENGTH: f64 = 40_075_016.686;
+
 
 //============ Style =========================================================
 
@@ -96,6 +197,15 @@ pub enum StyleId {
 
     /// Passenger map.
     Pax,
+
+    /// Electrification map with colorblind-safe colors.
+    Accessible,
+
+    /// Electrification map for a dark background.
+    Dark,
+
+    /// Operator map.
+    Operator,
 }
 
 impl StyleId {
@@ -103,19 +213,94 @@ impl StyleId {
         match self {
             StyleId::El => colors.el,
             StyleId::Pax  => colors.pax,
+            StyleId::Accessible => colors.accessible,
+            StyleId::Dark => colors.dark,
+            StyleId::Operator => colors.operator,
+        }
+    }
+}
+
+
+//------------ TrackLevel ----------------------------------------------------
+
+/// How track is rendered.
+///
+/// Which level is used for which detail level is determined by the
+/// [`TrackDetailConfig`].
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum TrackLevel {
+    /// Simplified lines of a single width without decorations.
+    Simple,
+
+    /// Lines with class-dependent widths and pax dashes.
+    Medium,
+
+    /// As medium but with bridges.
+    Bridges,
+
+    /// Separate tracks with all decorations.
+    Full,
+}
+
+impl TrackLevel {
+    fn new(detail: u8, config: &TrackDetailConfig) -> Self {
+        if detail >= config.full {
+            TrackLevel::Full
+        }
+        else if detail >= config.bridges {
+            TrackLevel::Bridges
+        }
+        else if detail >= config.medium {
+            TrackLevel::Medium
+        }
+        else {
+            TrackLevel::Simple
         }
     }
 }
 
 
+//------------ StyleSettings -------------------------------------------------
+
+/// The settings of a map that all its styles are created from.
+#[derive(Clone, Debug, Default)]
+pub struct StyleSettings {
+    /// The color set for rendering.
+    pub colors: ColorSet,
+
+    /// The overides for the measures.
+    pub measures: MeasuresConfig,
+
+    /// The detail levels where track rendering changes.
+    pub track_detail: TrackDetailConfig,
+
+    /// Suppress overlapping labels?
+    pub label_collisions: bool,
+
+    /// The minimum width of track lines in device pixels.
+    pub min_line_width: f64,
+}
+
+
 //------------ Style --------------------------------------------------------- 
 
 pub struct Style {
     /// The scale value for the feature store.
+    ///
+    /// For fractional zoom levels, this is the store scale of the lower
+    /// zoom level.
     store_scale: f64,
 
+    /// The store scale of the upper zoom level of a fractional zoom level.
+    ///
+    /// Features are shown if their detail range includes either scale. For
+    /// whole zoom levels, this is the same as `store_scale`.
+    store_upper: f64,
+
     /// The detail level.
-    detail: u8,
+    ///
+    /// This is only fractional if a fractional zoom level was requested.
+    detail: f64,
 
     /// Is this a pax-only map?
     pax_only: bool,
@@ -125,6 +310,12 @@ pub struct Style {
     /// These are already scaled into canvas co-ordinates.
     measures: Measures,
 
+    /// The configured widths of track casings.
+    casing: CasingConfig,
+
+    /// The configured scaling of labels by class.
+    label_scale: LabelScaleConfig,
+
     /// The map unit array for use with Femtomap transformation.
     map_units: [f64; 13],
 
@@ -134,6 +325,38 @@ pub struct Style {
     /// Are we using latin text only?
     latin_text: bool,
 
+    /// Should overlapping labels be suppressed?
+    label_collisions: bool,
+
+    /// The year to show the map for.
+    ///
+    /// If this is `None`, all features are shown.
+    year: Option<u16>,
+
+    /// The feature categories to show.
+    categories: CategorySet,
+
+    /// The railway classes to show.
+    classes: class::ClassFilter,
+
+    /// Draw the bounds overlay?
+    show_bounds: bool,
+
+    /// How track is rendered at this detail level.
+    track_level: TrackLevel,
+
+    /// The zoom levels whose features are shown.
+    ///
+    /// This is the zoom level corrected for the tile size and, for
+    /// fractional zoom levels, the next level, too.
+    zoom_levels: (u8, u8),
+
+    /// The minimum width of track lines in canvas units.
+    min_line_width: f64,
+
+    /// The size of a pixel in canvas units.
+    canvas_px: f64,
+
     /// The transformation from storage to canvas coordinates.
     ///
     /// Storage coordinates are Spherical Mercator with a range of `0. .. 1.`
@@ -149,26 +372,54 @@ pub struct Style {
 }
 
 impl Style {
-    pub fn new(layer_id: LayerId, tile_id: &TileId, colors: &ColorSet) -> Self {
-        let zoom = if tile_id.proof {
-            PROOF_ZOOM[usize::from(tile_id.zoom)]
-        }
-        else {
-            ZOOM[usize::from(tile_id.zoom)]
-        };
-        let measures = zoom.measures * tile_id.format.canvas_bp() * zoom.mag;
+    pub fn new(
+        layer_id: LayerId, tile_id: &TileId, settings: &StyleSettings,
+    ) -> Self {
+        let (zoom, detail, store_upper) = Zoom::get(
+            if tile_id.proof { PROOF_ZOOM } else { ZOOM },
+            tile_id.style_zoom(), tile_id.zoom_fraction(),
+        );
+        let casing = settings.measures.casing;
+        let label_scale = settings.measures.label_scale;
+        let measures = zoom.measures.with_config(&settings.measures)
+            * tile_id.format.canvas_bp() * zoom.mag;
         let equator_scale = tile_id.scale();
         let style_id = layer_id.style_id();
         let latin_text = layer_id.latin_text();
 
         Self {
             store_scale: zoom.store_scale,
-            detail: zoom.detail,
+            store_upper,
+            detail,
             pax_only: matches!(style_id, StyleId::Pax),
+            casing,
+            label_scale,
             map_units: measures.map_units(),
             measures,
-            colors: style_id.colors(colors),
+            colors: style_id.colors(
+                &settings.colors
+            ).with_year(tile_id.year),
             latin_text,
+            label_collisions: settings.label_collisions,
+            year: tile_id.year,
+            categories: tile_id.categories,
+            classes: tile_id.classes,
+            show_bounds: tile_id.bounds,
+            track_level: TrackLevel::new(
+                zoom.detail, &settings.track_detail
+            ),
+            zoom_levels: (
+                tile_id.style_zoom(),
+                if tile_id.zoom_fraction() > 0. {
+                    tile_id.style_zoom().saturating_add(1)
+                }
+                else {
+                    tile_id.style_zoom()
+                }
+            ),
+            min_line_width: settings.min_line_width * tile_id.canvas_size()
+                / f64::from(tile_id.size),
+            canvas_px: tile_id.canvas_size() / f64::from(tile_id.size),
             transform: TranslateScale::new(
                 Vec2::new(
                     -tile_id.nw().x * equator_scale,
@@ -184,10 +435,61 @@ impl Style {
         self.store_scale
     }
 
-    pub fn detail(&self) -> u8 {
+    /// Returns whether a feature with the given detail range is shown.
+    ///
+    /// The range is given in store scale. For fractional zoom levels,
+    /// features of both bracketing zoom levels are shown.
+    pub fn shows_detail(&self, (low, high): (f64, f64)) -> bool {
+        low <= self.store_upper && high >= self.store_scale
+    }
+
+    /// Returns whether a feature limited to some zoom levels is shown.
+    ///
+    /// Either end of the range is `None` if the feature isn’t limited on
+    /// that side. For fractional zoom levels, features of both bracketing
+    /// zoom levels are shown.
+    pub fn shows_zoom(
+        &self, (min, max): (Option<u8>, Option<u8>)
+    ) -> bool {
+        min.map(|min| min <= self.zoom_levels.1).unwrap_or(true)
+            && max.map(|max| max >= self.zoom_levels.0).unwrap_or(true)
+    }
+
+    /// Returns the detail level.
+    ///
+    /// For fractional zoom levels, the detail level is interpolated, too.
+    /// Features should compare it against whole detail levels so that they
+    /// change their appearance only once the level is reached.
+    pub fn detail(&self) -> f64 {
         self.detail
     }
 
+    /// Returns how track is rendered.
+    pub fn track_level(&self) -> TrackLevel {
+        self.track_level
+    }
+
+    /// Returns the factor to widen a track line of the given width by.
+    ///
+    /// Lines thinner than the minimum line width are widened to it. Their
+    /// casings should be widened by the same factor so they stay wider
+    /// than the track.
+    pub fn line_width_factor(&self, width: f64) -> f64 {
+        if width <= 0. || width >= self.min_line_width {
+            1.
+        }
+        else {
+            self.min_line_width / width
+        }
+    }
+
+    /// Returns the number of canvas units per metre at a latitude.
+    ///
+    /// The latitude is given in degrees.
+    pub fn canvas_per_metre(&self, lat: f64) -> f64 {
+        self.equator_scale / (EQUATOR_LENGTH * lat.to_radians().cos())
+    }
+
     pub fn pax_only(&self) -> bool {
         self.pax_only
     }
@@ -196,41 +498,159 @@ impl Style {
         self.measures
     }
 
+    /// Returns the factor to apply to the skip beside a track casing.
+    ///
+    /// This is the class’s override if it has one or the configured factor
+    /// otherwise.
+    pub fn casing_skip_factor(&self, class: &class::Railway) -> f64 {
+        class.casing_skip().unwrap_or(self.casing.skip)
+    }
+
+    /// Returns the width of the casing of a track feature.
+    pub fn track_casing_width(&self, double: bool) -> f64 {
+        if double {
+            self.casing.double * self.measures.dt()
+        }
+        else {
+            self.casing.single * self.measures.dt()
+        }
+    }
+
+    /// Returns the factor to scale the size of a label of the given class.
+    pub fn label_size_factor(&self, class: &class::Railway) -> f64 {
+        if Self::is_major_label(class) {
+            self.label_scale.major
+        }
+        else if Self::is_minor_label(class) {
+            self.label_scale.minor
+        }
+        else {
+            1.
+        }
+    }
+
+    /// Returns whether a label of the given class should default to bold.
+    pub fn label_bold(&self, class: &class::Railway) -> bool {
+        self.label_scale.major_bold && Self::is_major_label(class)
+    }
+
+    fn is_major_label(class: &class::Railway) -> bool {
+        matches!(
+            class.category(), class::Category::First | class::Category::Second
+        ) && matches!(class.pax(), class::Pax::Full)
+    }
+
+    fn is_minor_label(class: &class::Railway) -> bool {
+        matches!(
+            class.category(),
+            class::Category::Tram | class::Category::Private
+                | class::Category::Siding
+        ) || matches!(class.pax(), class::Pax::None)
+    }
+
     pub fn latin_text(&self) -> bool {
         self.latin_text
     }
 
+    pub fn label_collisions(&self) -> bool {
+        self.label_collisions
+    }
+
+    pub fn year(&self) -> Option<u16> {
+        self.year
+    }
+
+    /// Returns whether a feature of the given class is shown for the year.
+    pub fn shows_year(&self, class: &class::Railway) -> bool {
+        match self.year {
+            Some(year) => class.exists_in(year),
+            None => true,
+        }
+    }
+
+    /// Returns whether the bounds of features should be drawn on top.
+    pub fn show_bounds(&self) -> bool {
+        self.show_bounds
+    }
+
+    /// Returns whether features of the given class are shown.
+    ///
+    /// The class is `None` for features that don’t have one.
+    pub fn shows_class(&self, class: Option<&class::Railway>) -> bool {
+        self.classes.matches(class)
+    }
+
+    /// Returns whether features of the given category are shown.
+    pub fn shows_category(&self, category: Category) -> bool {
+        self.categories.contains(category)
+    }
+
     pub fn canvas_bp(&self) -> f64 {
         self.measures.bp()
     }
 
+    /// Returns the size of a pixel of a raster tile in canvas units.
+    pub fn canvas_px(&self) -> f64 {
+        self.canvas_px
+    }
+
     pub fn track_color(&self, class: &class::Railway) -> Color {
-        self.colors.track_color(class)
+        class.color().unwrap_or_else(|| self.colors.track_color(class))
     }
 
+    /// Returns the color of cat markings if they should be drawn.
+    ///
+    /// A color override of the class only recolors existing markings.
     pub fn cat_color(&self, class: &class::Railway) -> Option<Color> {
-        self.colors.cat_color(class)
+        self.colors.cat_color(class).map(|color| {
+            class.color().unwrap_or(color)
+        })
     }
 
+    /// Returns the color of third rail markings if they should be drawn.
+    ///
+    /// A color override of the class only recolors existing markings.
     pub fn rail_color(&self, class: &class::Railway) -> Option<Color> {
-        self.colors.rail_color(class)
+        self.colors.rail_color(class).map(|color| {
+            class.color().unwrap_or(color)
+        })
     }
 
     pub fn label_color(&self, class: &class::Railway) -> Color {
-        self.colors.label_color(class)
+        class.color().unwrap_or_else(|| self.colors.label_color(class))
     }
 
     pub fn primary_marker_color(&self, class: &class::Railway) -> Color {
-        self.colors.primary_marker_color(class)
+        class.color().unwrap_or_else(|| {
+            self.colors.primary_marker_color(class)
+        })
     }
 
     pub fn casing_color(&self) -> Color {
         self.colors.casing_color()
     }
 
+    pub fn track_casing_color(&self) -> Color {
+        self.colors.track_casing_color()
+    }
+
+    pub fn background_color(&self) -> Color {
+        self.colors.background_color()
+    }
+
+    pub fn platform_color(&self) -> Color {
+        self.colors.platform_color()
+    }
+
+    /// Returns the correction to add to tile bounds when selecting features.
+    ///
+    /// Label features are stored at their anchor only, so the correction
+    /// grows with the largest label size factor to keep enlarged labels
+    /// from being cut off at tile edges.
     pub fn bounds_correction(&self) -> f64 {
         BOUNDS_CORRECTION
-            * if self.detail == 0 { 1. } else { self.detail as f64 }
+            * if self.detail < 1. { 1. } else { self.detail }
+            * self.label_scale.major.max(self.label_scale.minor).max(1.)
     }
 }
 
@@ -252,3 +672,103 @@ impl Transform for Style {
     }
 }
 
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tile;
+    use crate::tile::TileFormat;
+    use super::super::legend::Legend;
+    use super::super::map::ScriptId;
+
+    fn el_style() -> Style {
+        let layer_id = LayerId::El(ScriptId::Original);
+        let tile_id = Legend::tile_id(
+            tile::LayerId::Railway(layer_id), TileFormat::Png
+        );
+        Style::new(layer_id, &tile_id, &StyleSettings::default())
+    }
+
+    #[test]
+    fn zoom_get() {
+        let (zoom, detail, upper) = Zoom::get(ZOOM, 8, 0.);
+        assert_eq!(zoom.store_scale, 1.5);
+        assert_eq!(upper, 1.5);
+        assert_eq!(detail, 1.);
+        assert_eq!(zoom.mag, 1.3);
+
+        let (zoom, detail, upper) = Zoom::get(ZOOM, 8, 0.5);
+        assert_eq!(zoom.store_scale, 1.5);
+        assert_eq!(upper, 2.0);
+        assert_eq!(detail, 1.5);
+        assert_eq!(zoom.detail, 1);
+        assert!((zoom.mag - 1.15).abs() < 1e-9);
+
+        // There is no zoom level above the last one.
+        let (zoom, detail, upper) = Zoom::get(ZOOM, 19, 0.5);
+        assert_eq!(zoom.store_scale, 5.5);
+        assert_eq!(upper, 5.5);
+        assert_eq!(detail, 5.);
+    }
+
+    #[test]
+    fn max_store_scale_step() {
+        for table in [ZOOM, PROOF_ZOOM] {
+            assert!(table.windows(2).all(|pair| {
+                pair[1].store_scale - pair[0].store_scale
+                    <= MAX_STORE_SCALE_STEP
+            }));
+        }
+    }
+
+    #[test]
+    fn zoom_store_scales_cover_proof_mode() {
+        // Zoom levels 0 to 5 share a store scale.
+        assert_eq!(zoom_store_scales(3), (0., 0.));
+        assert_eq!(zoom_store_scales(10), (2.5, 3.0));
+        assert_eq!(zoom_store_scales(19), (5.5, 5.5));
+    }
+
+    #[test]
+    fn max_storage_dt_matches_style() {
+        let measures = MeasuresConfig::default();
+        let dt = max_storage_dt(0, &measures);
+        let style = el_style();
+        assert!(style.measures().dt() / style.equator_scale <= dt);
+        assert!(max_storage_dt(4, &measures) < dt);
+    }
+
+    #[test]
+    fn shows_zoom() {
+        // The legend is rendered at zoom level 13.
+        let style = el_style();
+        assert!(style.shows_zoom((None, None)));
+        assert!(style.shows_zoom((Some(13), Some(13))));
+        assert!(!style.shows_zoom((Some(14), None)));
+        assert!(!style.shows_zoom((None, Some(12))));
+    }
+
+    #[test]
+    fn color_override_keeps_missing_markings() {
+        let style = el_style();
+        let mut class = class::Railway::default();
+        class.set_color(Color::rgb(0.8, 0., 0.));
+        class.set_cat(class::ElectricCat {
+            status: class::ElectricStatus::None,
+            voltage: None,
+            system: None,
+        });
+        assert!(style.cat_color(&class).is_none());
+        assert!(style.rail_color(&class).is_none());
+
+        class.set_cat(class::ElectricCat {
+            status: class::ElectricStatus::Open,
+            voltage: None,
+            system: None,
+        });
+        assert!(style.cat_color(&class).is_some());
+    }
+}
+
@@ -1,20 +1,92 @@
 use std::io;
+use std::collections::HashMap;
 use std::convert::Infallible;
+use std::io::Write as _;
+use std::fmt::Write;
 use std::net::SocketAddr;
 use std::num::NonZeroUsize;
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use arc_swap::ArcSwap;
-use http_body_util::Full;
-use hyper::{Request, Response};
+use flate2::Compression;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use http_body_util::{BodyExt, Full};
+use hyper::{Request, Response, StatusCode};
+use hyper::header::{
+    ACCEPT, ACCEPT_ENCODING, ACCESS_CONTROL_ALLOW_ORIGIN, CACHE_CONTROL,
+    CONTENT_ENCODING, CONTENT_TYPE, ETAG, HeaderMap, HeaderValue,
+    IF_NONE_MATCH, ORIGIN, VARY,
+};
 use hyper::body::{Bytes, Incoming};
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
 use hyper_util::rt::TokioIo;
 use lru::LruCache;
 use tokio::net::TcpListener;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 use crate::railway;
-use crate::tile::TileId;
+use crate::tile;
+use crate::tile::{
+    Background, DEFAULT_TILE_SIZE, LayerId, TileFormat, TileId, WebpQuality
+};
+
+
+//------------ ServerConfig --------------------------------------------------
+
+/// The configuration of the tile server.
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    /// Enable proof mode.
+    pub proof: bool,
+
+    /// The size of raster tiles in pixels.
+    pub tile_size: u32,
+
+    /// The max-age in seconds given to clients for caching tiles.
+    pub max_age: u32,
+
+    /// The quality of WebP tiles.
+    pub webp_quality: WebpQuality,
+
+    /// The quality of JPEG tiles.
+    ///
+    /// If this is present, PNG tiles with an opaque background are served
+    /// as JPEG with this quality instead.
+    pub jpeg_quality: Option<u8>,
+
+    /// The number of tiles along each side of a metatile.
+    ///
+    /// If a raster tile needs to be rendered, all missing tiles of its
+    /// metatile are rendered in parallel and cached. A value of 1 only
+    /// renders the requested tile.
+    pub metatile: u32,
+
+    /// The background of tiles unless requested otherwise.
+    pub background: Background,
+
+    /// The origins allowed to access the server from a browser.
+    ///
+    /// If empty, no CORS headers are sent. An entry of `*` allows all
+    /// origins.
+    pub cors_origins: Vec<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            proof: false,
+            tile_size: DEFAULT_TILE_SIZE,
+            max_age: 0,
+            webp_quality: WebpQuality::default(),
+            jpeg_quality: None,
+            metatile: 1,
+            background: Background::Transparent,
+            cors_origins: Vec::new(),
+        }
+    }
+}
 
 
 //------------ Server --------------------------------------------------------
@@ -22,13 +94,30 @@ use crate::tile::TileId;
 pub struct Server {
     railway: ArcSwap<railway::Map>,
     cache: Arc<Mutex<LruCache<TileId, Bytes>>>,
+
+    /// The rendered legends.
+    ///
+    /// The key is the layer and the version of the map the legend was
+    /// rendered for.
+    legends: Mutex<HashMap<(LayerId, u64), Bytes>>,
+
+    /// The metatiles currently being rendered.
+    ///
+    /// The key is the first tile of the metatile. The sender of the
+    /// channel is dropped once rendering has finished and the tiles are in
+    /// the cache.
+    rendering: Mutex<HashMap<TileId, watch::Receiver<()>>>,
+
     rx: Option<mpsc::Receiver<ServerCommand>>,
-    proof: bool,
+    config: ServerConfig,
+    metrics: Metrics,
 }
 
 
 impl Server {
-    pub fn new(railway: railway::Map, proof: bool) -> (Self, ServerControl) {
+    pub fn new(
+        railway: railway::Map, config: ServerConfig,
+    ) -> (Self, ServerControl) {
         let (tx, rx) = mpsc::channel(10);
         (
             Server {
@@ -36,8 +125,11 @@ impl Server {
                 cache: Arc::new(Mutex::new(
                     LruCache::new(NonZeroUsize::new(10_000).unwrap())
                 )),
+                legends: Default::default(),
+                rendering: Default::default(),
                 rx: Some(rx),
-                proof,
+                config,
+                metrics: Metrics::new(),
             },
             ServerControl { tx },
         )
@@ -55,13 +147,15 @@ impl Server {
             let stream = TokioIo::new(stream);
             let this = this.clone();
             tokio::task::spawn(async move {
-                http1::Builder::new().serve_connection(
+                if let Err(err) = http1::Builder::new().serve_connection(
                     stream,
                     service_fn(|r| {
                         let this = this.clone();
-                        async move { this.process(r).await }
+                        async move { this.respond(r).await }
                     })
-                ).await
+                ).await {
+                    log::debug!("Connection failed: {}", err);
+                }
             });
         }
     }
@@ -74,6 +168,9 @@ impl Server {
                 ServerCommand::UpdateRailway(map) => {
                     self.railway.store(map.into());
                     self.cache.lock().unwrap().clear();
+                    self.legends.lock().unwrap().clear();
+                    self.metrics.reloaded();
+                    log::info!("Switched to the reloaded map.");
                 }
             }
         }
@@ -81,8 +178,74 @@ impl Server {
 }
 
 impl Server {
+    /// Processes a request and adds CORS and compression to the response.
+    async fn respond(
+        self: Arc<Self>, request: Request<Incoming>
+    ) -> Result<Response<Full<Bytes>>, Infallible> {
+        let origin = self.allowed_origin(request.headers());
+        let encoding = Encoding::negotiate(request.headers());
+        let mut response = self.process(request).await?;
+        if let Some(origin) = origin {
+            let headers = response.headers_mut();
+            if origin != "*" {
+                headers.append(VARY, HeaderValue::from_static("Origin"));
+            }
+            headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        }
+        Ok(Self::compress(response, encoding).await)
+    }
+
+    /// Returns the value for the Access-Control-Allow-Origin header.
+    ///
+    /// Returns `None` if the header should not be sent.
+    fn allowed_origin(&self, headers: &HeaderMap) -> Option<HeaderValue> {
+        let origins = &self.config.cors_origins;
+        if origins.iter().any(|item| item == "*") {
+            return Some(HeaderValue::from_static("*"))
+        }
+        let origin = headers.get(ORIGIN)?;
+        let value = origin.to_str().ok()?;
+        if origins.iter().any(|item| item == value) {
+            Some(origin.clone())
+        }
+        else {
+            None
+        }
+    }
+
+    /// Compresses the body of a response if possible and worthwhile.
+    ///
+    /// Images are already compressed and are left alone as are empty
+    /// bodies.
+    async fn compress(
+        response: Response<Full<Bytes>>, encoding: Option<Encoding>
+    ) -> Response<Full<Bytes>> {
+        let compressible = response.headers().get(CONTENT_TYPE).map(|value| {
+            !value.as_bytes().starts_with(b"image/")
+        }).unwrap_or(false);
+        if !compressible || response.headers().contains_key(CONTENT_ENCODING) {
+            return response
+        }
+        let (mut parts, body) = response.into_parts();
+        let body = match body.collect().await {
+            Ok(body) => body.to_bytes(),
+            Err(err) => match err { },
+        };
+        parts.headers.append(
+            VARY, HeaderValue::from_static("Accept-Encoding")
+        );
+        let encoding = match encoding {
+            Some(encoding) if !body.is_empty() => encoding,
+            _ => return Response::from_parts(parts, Full::new(body)),
+        };
+        parts.headers.insert(
+            CONTENT_ENCODING, HeaderValue::from_static(encoding.as_str())
+        );
+        Response::from_parts(parts, Full::new(encoding.encode(&body).into()))
+    }
+
     async fn process(
-        &self, request: Request<Incoming>
+        self: Arc<Self>, request: Request<Incoming>
     ) -> Result<Response<Full<Bytes>>, Infallible> {
         let path = request.uri().path();
 
@@ -117,6 +280,34 @@ impl Server {
                     .unwrap()
                 )
             }
+            "/pick" => {
+                return Ok(self.pick(request.uri().query().unwrap_or("")))
+            }
+            "/regions" => {
+                return Ok(Response::builder()
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(Full::new(Bytes::from(
+                        serde_json::to_vec(
+                            &self.railway.load().regions()
+                        ).unwrap()
+                    )))
+                    .unwrap()
+                )
+            }
+            "/legend.png" => {
+                return Ok(self.legend(request.uri().query().unwrap_or("")))
+            }
+            "/metrics" => {
+                return Ok(Response::builder()
+                    .header(
+                        CONTENT_TYPE, "text/plain;version=0.0.4;charset=utf-8"
+                    )
+                    .body(Full::new(Bytes::from(
+                        self.metrics.render(&self.railway.load())
+                    )))
+                    .unwrap()
+                )
+            }
             _ => { }
         }
 
@@ -170,31 +361,352 @@ impl Server {
         }
         */
 
-        let tile = match TileId::from_path(
-            &request.uri().path()[1..], self.proof,
+        // Tiles requested without an extension are delivered as WebP if
+        // the client accepts it and PNG otherwise.
+        let default_format = if accepts_webp(request.headers()) {
+            TileFormat::Webp
+        }
+        else {
+            TileFormat::Png
+        };
+        let mut tile = match TileId::from_path(
+            &request.uri().path()[1..],
+            self.config.proof, self.config.tile_size, default_format,
         ) {
             Ok(tile) => tile,
             Err(_) => {
                 return Ok(not_found())
             }
         };
+        tile.background = self.config.background;
+        if let Some(query) = request.uri().query() {
+            if tile.apply_query(query).is_err() {
+                return Ok(bad_request())
+            }
+        }
+        tile.format = tile.format.for_background(
+            tile.background, self.config.jpeg_quality
+        );
+        let railway = self.railway.load_full();
+        let etag = tile.etag(railway.version(), self.config.webp_quality);
+        let cache_control = format!("max-age={}", self.config.max_age);
+        if etag_matches(request.headers(), &etag) {
+            return Ok(Response::builder()
+                .status(StatusCode::NOT_MODIFIED)
+                .header(ETAG, etag)
+                .header(CACHE_CONTROL, cache_control)
+                .header(VARY, "Accept")
+                .body(Full::default())
+                .unwrap()
+            )
+        }
         let cached = self.cache.lock().unwrap().get(&tile).map(Clone::clone); 
         let body = match cached {
             Some(bytes) => bytes.into(),
             None => {
-                let bytes: Bytes = tile.render(&self.railway.load()).into();
-                self.cache.lock().unwrap().put(tile.clone(), bytes.clone());
-                bytes.into()
+                match self.clone().render_tile(railway, tile).await {
+                    Ok(bytes) => bytes.into(),
+                    Err(_) => return Ok(internal_error()),
+                }
             }
         };
         Ok(Response::builder()
-            .header("Content-Type", tile.content_type())
+            .header(CONTENT_TYPE, tile.content_type())
+            .header(ETAG, etag)
+            .header(CACHE_CONTROL, cache_control)
+            .header(VARY, "Accept")
             .body(body)
             .unwrap()
         )
     }
 }
 
+impl Server {
+    /// Renders a tile, places it in the cache, and returns its content.
+    ///
+    /// Rendering happens on a blocking thread so it doesn’t hold up the
+    /// runtime. If metatiles are enabled and this is a raster tile, all
+    /// tiles of the metatile missing from the cache are rendered, too. A
+    /// request for a tile of a metatile that is already being rendered
+    /// waits for that rendering and takes its tile from the cache.
+    ///
+    /// Returns an error if rendering panicked.
+    async fn render_tile(
+        self: Arc<Self>, railway: Arc<railway::Map>, tile: TileId
+    ) -> Result<Bytes, Failed> {
+        if self.config.metatile <= 1 || !tile.format.is_raster() {
+            return tokio::task::spawn_blocking(move || {
+                let bytes = self.render_single(&railway, tile);
+                self.cache.lock().unwrap().put(tile, bytes.clone());
+                bytes
+            }).await.map_err(|err| {
+                log::error!("Rendering tile {:?} failed: {}", tile, err);
+                Failed
+            })
+        }
+
+        // The metatile contains the tile, so there always is a first one.
+        let key = tile.metatile(self.config.metatile).next().unwrap();
+        loop {
+            let (guard, mut receiver) = {
+                let mut rendering = self.rendering.lock().unwrap();
+                match rendering.get(&key) {
+                    Some(receiver) => (None, receiver.clone()),
+                    None => {
+                        let (sender, receiver) = watch::channel(());
+                        rendering.insert(key, receiver.clone());
+                        (
+                            Some(RenderingGuard {
+                                server: self.clone(), key,
+                                _sender: sender,
+                            }),
+                            receiver
+                        )
+                    }
+                }
+            };
+            if let Some(guard) = guard {
+                // The guard moves into the blocking task so the metatile
+                // is released once rendering ends, even if this future is
+                // dropped or rendering panics.
+                let railway = railway.clone();
+                return tokio::task::spawn_blocking(move || {
+                    guard.server.render_metatile(&railway, tile)
+                }).await.map_err(|err| {
+                    log::error!("Rendering tile {:?} failed: {}", tile, err);
+                    Failed
+                })
+            }
+
+            // The sender is dropped when rendering is done which makes
+            // `changed` return an error. If the tile isn’t in the cache
+            // then, rendering failed and the entry is gone, so we try
+            // rendering ourselves on the next round.
+            let _ = receiver.changed().await;
+            let cached = self.cache.lock().unwrap().get(&tile).cloned();
+            if let Some(bytes) = cached {
+                return Ok(bytes)
+            }
+        }
+    }
+
+    /// Renders all missing tiles of a metatile and places them in the cache.
+    ///
+    /// Returns the content of `tile` which is always rendered.
+    fn render_metatile(&self, railway: &railway::Map, tile: TileId) -> Bytes {
+        let tiles = {
+            let cache = self.cache.lock().unwrap();
+            tile.metatile(self.config.metatile).filter(|item| {
+                *item == tile || !cache.contains(item)
+            }).collect::<Vec<_>>()
+        };
+        let threads = std::thread::available_parallelism().map(|count| {
+            count.get()
+        }).unwrap_or(1).min(tiles.len());
+
+        // The features are only read during rendering, so the sub-tiles can
+        // simply be rendered on their own threads each picking the next
+        // tile from the list until it is exhausted.
+        let next = AtomicUsize::new(0);
+        let rendered = std::thread::scope(|scope| {
+            let handles = (0..threads).map(|_| {
+                scope.spawn(|| {
+                    let mut res = Vec::new();
+                    while let Some(item) = tiles.get(
+                        next.fetch_add(1, Ordering::Relaxed)
+                    ) {
+                        res.push((*item, self.render_single(railway, *item)));
+                    }
+                    res
+                })
+            }).collect::<Vec<_>>();
+            handles.into_iter().flat_map(|handle| {
+                handle.join().unwrap()
+            }).collect::<Vec<_>>()
+        });
+
+        let mut res = Bytes::new();
+        let mut cache = self.cache.lock().unwrap();
+        for (item, bytes) in rendered {
+            if item == tile {
+                res = bytes.clone();
+            }
+            cache.put(item, bytes);
+        }
+        res
+    }
+
+    /// Renders a single tile and returns its content.
+    fn render_single(&self, railway: &railway::Map, tile: TileId) -> Bytes {
+        let start = Instant::now();
+        let bytes = tile.render(railway, self.config.webp_quality).into();
+        self.metrics.rendered(start);
+        bytes
+    }
+}
+
+/// Returns whether the Accept header in `headers` includes WebP.
+fn accepts_webp(headers: &HeaderMap) -> bool {
+    headers.get_all(ACCEPT).iter().any(|value| {
+        let value = match value.to_str() {
+            Ok(value) => value,
+            Err(_) => return false,
+        };
+        value.split(',').any(|item| {
+            let mut item = item.split(';');
+            if item.next().map(str::trim) != Some("image/webp") {
+                return false
+            }
+            !is_refused(item)
+        })
+    })
+}
+
+/// Returns whether a quality parameter marks an item as not acceptable.
+fn is_refused<'a>(mut params: impl Iterator<Item = &'a str>) -> bool {
+    // A quality of zero means “not acceptable.”
+    params.any(|param| {
+        matches!(
+            param.trim().strip_prefix("q="),
+            Some("0" | "0.0" | "0.00" | "0.000")
+        )
+    })
+}
+
+/// Returns whether an If-None-Match header in `headers` matches `etag`.
+///
+/// Since we only ever produce strong tags, weak comparison is used as
+/// required for If-None-Match.
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers.get_all(IF_NONE_MATCH).iter().any(|value| {
+        let value = match value.to_str() {
+            Ok(value) => value,
+            Err(_) => return false,
+        };
+        value.split(',').any(|item| {
+            let item = item.trim();
+            item == "*" || item.strip_prefix("W/").unwrap_or(item) == etag
+        })
+    })
+}
+
+impl Server {
+    /// The radius around the point to pick features from in pixels.
+    const PICK_RADIUS: f64 = 8.;
+
+    /// Processes a request for the features at a location.
+    ///
+    /// The query must contain `lon`, `lat`, and `z` and can contain `layer`
+    /// and `year`. The layer defaults to `el`.
+    fn pick(&self, query: &str) -> Response<Full<Bytes>> {
+        let mut lon = None;
+        let mut lat = None;
+        let mut zoom = None;
+        let mut layer = "el";
+        let mut year = None;
+        for item in query.split('&') {
+            let (key, value) = match item.split_once('=') {
+                Some(item) => item,
+                None => continue,
+            };
+            match key {
+                "lon" => lon = f64::from_str(value).ok(),
+                "lat" => lat = f64::from_str(value).ok(),
+                "z" => zoom = u8::from_str(value).ok(),
+                "layer" => layer = value,
+                "year" => {
+                    match u16::from_str(value) {
+                        Ok(value) => year = Some(value),
+                        Err(_) => return bad_request(),
+                    }
+                }
+                _ => { }
+            }
+        }
+        let (lon, lat, zoom) = match (lon, lat, zoom) {
+            (Some(lon), Some(lat), Some(zoom)) => (lon, lat, zoom),
+            _ => return bad_request(),
+        };
+        let layer = match LayerId::from_str(layer) {
+            Ok(layer) => layer,
+            Err(_) => return not_found(),
+        };
+        let point = tile::storage_point(lon, lat);
+        let mut tile = match TileId::containing(
+            layer, zoom, point, self.config.proof, self.config.tile_size
+        ) {
+            Ok(tile) => tile,
+            Err(_) => return bad_request(),
+        };
+        tile.year = year;
+        let infos = match self.railway.load().pick(
+            tile, point, Self::PICK_RADIUS
+        ) {
+            Ok(infos) => infos,
+            Err(_) => return not_found(),
+        };
+        Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(Full::new(Bytes::from(
+                serde_json::to_vec(&infos).unwrap()
+            )))
+            .unwrap()
+    }
+}
+
+impl Server {
+    /// Processes a request for the legend.
+    ///
+    /// The query can contain `layer` to select the layer whose style is
+    /// used. It defaults to `el`.
+    fn legend(&self, query: &str) -> Response<Full<Bytes>> {
+        let layer = query.split('&').find_map(|item| {
+            item.strip_prefix("layer=")
+        }).unwrap_or("el");
+        let layer = match LayerId::from_str(layer) {
+            Ok(layer) => layer,
+            Err(_) => return not_found(),
+        };
+        let railway = self.railway.load();
+        let key = (layer, railway.version());
+        let cached = self.legends.lock().unwrap().get(&key).cloned();
+        let body = match cached {
+            Some(body) => body,
+            None => {
+                let body = match railway.render_legend(
+                    layer, TileFormat::Png
+                ) {
+                    Ok(body) => Bytes::from(body),
+                    Err(_) => return not_found(),
+                };
+                self.legends.lock().unwrap().insert(key, body.clone());
+                body
+            }
+        };
+        Response::builder()
+            .header(CONTENT_TYPE, TileFormat::Png.content_type())
+            .header(CACHE_CONTROL, format!("max-age={}", self.config.max_age))
+            .body(Full::new(body))
+            .unwrap()
+    }
+}
+
+fn bad_request() -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(StatusCode::BAD_REQUEST)
+        .header("Content-Type", "text/plain;charset=utf-8")
+        .body(Full::new(Bytes::from("bad request")))
+        .unwrap()
+}
+
+fn internal_error() -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(StatusCode::INTERNAL_SERVER_ERROR)
+        .header("Content-Type", "text/plain;charset=utf-8")
+        .body(Full::new(Bytes::from("internal server error")))
+        .unwrap()
+}
+
 fn not_found() -> Response<Full<Bytes>> {
     Response::builder()
         .status(404)
@@ -206,6 +718,218 @@ fn not_found() -> Response<Full<Bytes>> {
 pub struct Failed;
 
 
+//------------ RenderingGuard ------------------------------------------------
+
+/// Marks a metatile as being rendered for as long as it lives.
+///
+/// When dropped, removes the metatile from the server’s rendering map and
+/// then drops the sender, which wakes up all requests waiting for the
+/// metatile.
+struct RenderingGuard {
+    server: Arc<Server>,
+    key: TileId,
+    _sender: watch::Sender<()>,
+}
+
+impl Drop for RenderingGuard {
+    fn drop(&mut self) {
+        // Don’t panic while unwinding from a panic during rendering.
+        if let Ok(mut rendering) = self.server.rendering.lock() {
+            rendering.remove(&self.key);
+        }
+    }
+}
+
+
+
+//------------ Encoding ------------------------------------------------------
+
+/// A content encoding for compressing responses.
+#[derive(Clone, Copy, Debug)]
+enum Encoding {
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    /// Picks the encoding to use from the Accept-Encoding headers.
+    ///
+    /// Gzip is preferred over deflate if the client accepts both.
+    fn negotiate(headers: &HeaderMap) -> Option<Self> {
+        let mut res = None;
+        for value in headers.get_all(ACCEPT_ENCODING) {
+            let value = match value.to_str() {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            for item in value.split(',') {
+                let mut item = item.split(';');
+                let encoding = match item.next().map(str::trim) {
+                    Some("gzip") => Encoding::Gzip,
+                    Some("deflate") => Encoding::Deflate,
+                    _ => continue,
+                };
+                if is_refused(item) {
+                    continue
+                }
+                if matches!(encoding, Encoding::Gzip) {
+                    return Some(encoding)
+                }
+                res = Some(encoding);
+            }
+        }
+        res
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    /// Compresses the data.
+    fn encode(self, data: &[u8]) -> Vec<u8> {
+        // Writing into a vec can’t fail.
+        match self {
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(
+                    Vec::new(), Compression::default()
+                );
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+            Encoding::Deflate => {
+                let mut encoder = ZlibEncoder::new(
+                    Vec::new(), Compression::default()
+                );
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+        }
+    }
+}
+
+//------------ Metrics -------------------------------------------------------
+
+/// Operational statistics of the server.
+///
+/// These are provided in Prometheus text format via `/metrics`.
+struct Metrics {
+    /// The number of tiles rendered.
+    ///
+    /// This doesn’t include tiles served from the cache.
+    rendered: AtomicU64,
+
+    /// The cumulative counts of the render duration histogram.
+    ///
+    /// Each value is the number of renders that took at most the time given
+    /// by the same index in `RENDER_BUCKETS`.
+    buckets: [AtomicU64; Metrics::RENDER_BUCKETS.len()],
+
+    /// The sum of all render durations in microseconds.
+    duration: AtomicU64,
+
+    /// The time of the last (re)load of the map as a Unix timestamp.
+    loaded: AtomicU64,
+}
+
+impl Metrics {
+    /// The upper bounds of the render duration histogram in seconds.
+    const RENDER_BUCKETS: [f64; 10] = [
+        0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1., 2.5, 5.
+    ];
+
+    fn new() -> Self {
+        let res = Metrics {
+            rendered: Default::default(),
+            buckets: Default::default(),
+            duration: Default::default(),
+            loaded: Default::default(),
+        };
+        res.reloaded();
+        res
+    }
+
+    /// Records that the map has been replaced.
+    fn reloaded(&self) {
+        self.loaded.store(
+            SystemTime::now().duration_since(UNIX_EPOCH).map(|time| {
+                time.as_secs()
+            }).unwrap_or(0),
+            Ordering::Relaxed
+        );
+    }
+
+    /// Records a tile render that started at `start`.
+    fn rendered(&self, start: Instant) {
+        let duration = start.elapsed();
+        self.rendered.fetch_add(1, Ordering::Relaxed);
+        self.duration.fetch_add(
+            u64::try_from(duration.as_micros()).unwrap_or(u64::MAX),
+            Ordering::Relaxed
+        );
+        let secs = duration.as_secs_f64();
+        for (bound, count) in Self::RENDER_BUCKETS.iter().zip(&self.buckets) {
+            if secs <= *bound {
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Produces the metrics in Prometheus text format.
+    fn render(&self, railway: &railway::Map) -> String {
+        let mut res = String::new();
+        let rendered = self.rendered.load(Ordering::Relaxed);
+
+        // Writing to a string never fails, so we can ignore the results.
+        let _ = writeln!(res,
+            "# HELP railmap_tiles_rendered_total \
+             Number of tiles rendered.\n\
+             # TYPE railmap_tiles_rendered_total counter\n\
+             railmap_tiles_rendered_total {}",
+            rendered
+        );
+        let _ = writeln!(res,
+            "# HELP railmap_render_duration_seconds \
+             Time spent rendering a tile.\n\
+             # TYPE railmap_render_duration_seconds histogram"
+        );
+        for (bound, count) in Self::RENDER_BUCKETS.iter().zip(&self.buckets) {
+            let _ = writeln!(res,
+                "railmap_render_duration_seconds_bucket{{le=\"{}\"}} {}",
+                bound, count.load(Ordering::Relaxed)
+            );
+        }
+        let _ = writeln!(res,
+            "railmap_render_duration_seconds_bucket{{le=\"+Inf\"}} {}\n\
+             railmap_render_duration_seconds_sum {}\n\
+             railmap_render_duration_seconds_count {}",
+            rendered,
+            self.duration.load(Ordering::Relaxed) as f64 / 1_000_000.,
+            rendered,
+        );
+        let _ = writeln!(res,
+            "# HELP railmap_features Number of features loaded.\n\
+             # TYPE railmap_features gauge"
+        );
+        for (set, count) in railway.feature_counts() {
+            let _ = writeln!(res,
+                "railmap_features{{set=\"{}\"}} {}", set, count
+            );
+        }
+        let _ = writeln!(res,
+            "# HELP railmap_last_reload_timestamp_seconds \
+             Time the map was last loaded.\n\
+             # TYPE railmap_last_reload_timestamp_seconds gauge\n\
+             railmap_last_reload_timestamp_seconds {}",
+            self.loaded.load(Ordering::Relaxed)
+        );
+        res
+    }
+}
+
+
 //------------ ServerControl -------------------------------------------------
 
 #[derive(Clone)]
@@ -1,8 +1,12 @@
 #![allow(dead_code)]
 use std::{fmt, ops};
+use std::hash::{Hash, Hasher};
 use std::str::FromStr;
 use kurbo::Point;
 use crate::railway;
+use crate::railway::class::ClassFilter;
+use crate::railway::feature::CategorySet;
+use crate::config::StableHasher;
 
 
 //------------ Configurable Constants ----------------------------------------
@@ -12,6 +16,49 @@ use crate::railway;
 /// This **must** be less than 32 or stuff will break.
 const MAX_ZOOM: u8 = 20;
 
+/// The default size of a raster tile in pixels.
+///
+/// The styles are defined for this tile size.
+pub const DEFAULT_TILE_SIZE: u32 = 512;
+
+/// The smallest size of a raster tile in pixels.
+///
+/// All tile sizes must be this value multiplied by a power of two.
+pub const MIN_TILE_SIZE: u32 = 256;
+
+/// The largest size of a raster tile in pixels.
+pub const MAX_TILE_SIZE: u32 = 4096;
+
+/// The largest number of tiles along each side of a metatile.
+pub const MAX_METATILE: u32 = 16;
+
+/// Checks whether a tile size is acceptable.
+pub fn check_tile_size(size: u32) -> Result<(), TileSizeError> {
+    if size < MIN_TILE_SIZE
+        || size > MAX_TILE_SIZE
+        || !(size / MIN_TILE_SIZE).is_power_of_two()
+        || size % MIN_TILE_SIZE != 0
+    {
+        Err(TileSizeError(size))
+    }
+    else {
+        Ok(())
+    }
+}
+
+/// Checks whether a metatile factor is acceptable.
+///
+/// The factor must be a power of two no larger than `MAX_METATILE`. A
+/// factor of 1 disables metatiles.
+pub fn check_metatile(factor: u32) -> Result<(), MetatileError> {
+    if factor > MAX_METATILE || !factor.is_power_of_two() {
+        Err(MetatileError(factor))
+    }
+    else {
+        Ok(())
+    }
+}
+
 
 //------------ TileId --------------------------------------------------------
 
@@ -23,6 +70,36 @@ pub struct TileId {
     pub y: u32,
     pub format: TileFormat,
     pub proof: bool,
+
+    /// The size of a raster tile in pixels.
+    pub size: u32,
+
+    /// The year to render the map for.
+    pub year: Option<u16>,
+
+    /// The feature categories to render.
+    pub categories: CategorySet,
+
+    /// The railway classes to render.
+    pub classes: ClassFilter,
+
+    /// The label sets to add to a base layer.
+    pub labels: railway::LabelSets,
+
+    /// Draw the bounds of all features on top?
+    ///
+    /// This is only available in proof mode.
+    pub bounds: bool,
+
+    /// The background of the tile.
+    pub background: Background,
+
+    /// The fraction of a zoom level to add to the zoom for rendering.
+    ///
+    /// This is given in hundredths and is less than 100. It is used to
+    /// render tiles for the zoom levels in between while a client is
+    /// zooming.
+    pub fraction: u8,
 }
 
 impl TileId {
@@ -33,7 +110,11 @@ impl TileId {
     /// ```text
     /// {layer}/{zoom}/{x}/{y}.{fmt}
     /// ```
-    pub fn from_path(path: &str, proof: bool) -> Result<Self, TileIdError> {
+    ///
+    /// The extension can be missing in which case `default_format` is used.
+    pub fn from_path(
+        path: &str, proof: bool, size: u32, default_format: TileFormat,
+    ) -> Result<Self, TileIdError> {
         let mut path = path.split('/');
 
         let layer = path.next().ok_or(TileIdError)?;
@@ -61,15 +142,116 @@ impl TileId {
             return Err(TileIdError);
         }
 
-        let format = TileFormat::from_str(
-            next.next().ok_or(TileIdError)?
-        )?;
+        let format = match next.next() {
+            Some(format) => TileFormat::from_str(format)?,
+            None => default_format,
+        };
 
         if next.next().is_some() || path.next().is_some() {
             return Err(TileIdError)
         }
 
-        Ok(TileId { layer, zoom, x, y, format, proof, })
+        Ok(TileId {
+            layer, zoom, x, y, format, proof, size,
+            year: None,
+            categories: CategorySet::all(),
+            classes: ClassFilter::default(),
+            labels: Default::default(),
+            bounds: false,
+            background: Background::default(),
+            fraction: 0,
+        })
+    }
+
+    /// Applies the query part of a URI.
+    ///
+    /// The `year` parameter selects the year to render the map for. The
+    /// `show` parameter is a comma separated list of the feature
+    /// categories to render. The `class` parameter is a comma separated
+    /// list of class symbols limiting the features rendered and the
+    /// `unclassed` parameter, `show` or `hide`, whether features without a
+    /// class are rendered. The `labels` parameter lists the label sets
+    /// to add to a base layer. The `bg` parameter selects the background.
+    /// The `dz` parameter gives the fraction of a zoom level to add for
+    /// rendering as a value of at least 0 and less than 1. In proof mode,
+    /// the `bounds` parameter enables the bounds overlay. Unknown
+    /// parameters are ignored.
+    pub fn apply_query(&mut self, query: &str) -> Result<(), TileIdError> {
+        for item in query.split('&') {
+            if let Some(year) = item.strip_prefix("year=") {
+                self.year = Some(
+                    u16::from_str(year).map_err(|_| TileIdError)?
+                );
+            }
+            else if let Some(show) = item.strip_prefix("show=") {
+                self.categories = CategorySet::from_str(
+                    show
+                ).map_err(|_| TileIdError)?;
+            }
+            else if let Some(classes) = item.strip_prefix("class=") {
+                let unclassed = self.classes.unclassed();
+                self.classes = ClassFilter::from_str(
+                    classes
+                ).map_err(|_| TileIdError)?;
+                self.classes.set_unclassed(unclassed);
+            }
+            else if let Some(unclassed) = item.strip_prefix("unclassed=") {
+                self.classes.set_unclassed(match unclassed {
+                    "show" => true,
+                    "hide" => false,
+                    _ => return Err(TileIdError)
+                });
+            }
+            else if let Some(labels) = item.strip_prefix("labels=") {
+                self.labels = railway::LabelSets::from_str(labels)?;
+            }
+            else if let Some(background) = item.strip_prefix("bg=") {
+                self.background = Background::from_str(background)?;
+            }
+            else if let Some(fraction) = item.strip_prefix("dz=") {
+                let fraction = f64::from_str(
+                    fraction
+                ).map_err(|_| TileIdError)?;
+                if !(0. ..1.).contains(&fraction) {
+                    return Err(TileIdError)
+                }
+                self.fraction = ((fraction * 100.).round() as u8).min(99);
+            }
+            else if self.proof && (item == "bounds" || item == "bounds=1") {
+                self.bounds = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the tile of the given layer and zoom containing a point.
+    ///
+    /// The point is given in storage coordinates, i.e., Spherical Mercator
+    /// with a range of `0. .. 1.` Returns an error if the point is outside
+    /// of this range or the zoom level is too large.
+    pub fn containing(
+        layer: LayerId, zoom: u8, point: Point, proof: bool, size: u32,
+    ) -> Result<Self, TileIdError> {
+        if zoom > MAX_ZOOM
+            || !(0. ..1.).contains(&point.x) || !(0. ..1.).contains(&point.y)
+        {
+            return Err(TileIdError)
+        }
+        let n = f64::from(Self::coord_end(zoom));
+        Ok(TileId {
+            layer, zoom,
+            x: (point.x * n) as u32,
+            y: (point.y * n) as u32,
+            format: TileFormat::Png,
+            proof, size,
+            year: None,
+            categories: CategorySet::all(),
+            classes: ClassFilter::default(),
+            labels: Default::default(),
+            bounds: false,
+            background: Background::default(),
+            fraction: 0,
+        })
     }
 
     /// The upper bound for a coordinate in a zoom level.
@@ -91,14 +273,60 @@ impl TileId {
     }
 
     pub fn scale(&self) -> f64 {
-        self.format.size() * self.n()
+        self.canvas_size() * self.n()
     }
 
-    pub fn content_type(&self) -> &'static str {
-        match self.format {
-            TileFormat::Png => "image/png",
-            TileFormat::Svg => "image/svg+xml",
+    /// Returns the size of the tile in canvas units.
+    ///
+    /// For SVG tiles, the size is in points and scales with the tile size.
+    pub fn canvas_size(&self) -> f64 {
+        self.format.size() * f64::from(self.size)
+            / f64::from(DEFAULT_TILE_SIZE)
+    }
+
+    /// Returns the zoom level to use for selecting the style.
+    ///
+    /// A larger tile covers the same area as multiple smaller tiles at a
+    /// higher zoom level and needs to be rendered in the same way. Since the
+    /// styles are defined for the default tile size, the zoom is corrected
+    /// by the ratio of the tile size to the default size.
+    pub fn style_zoom(&self) -> u8 {
+        let mut zoom = i32::from(self.zoom);
+        let mut size = self.size;
+        while size > DEFAULT_TILE_SIZE {
+            zoom += 1;
+            size /= 2;
         }
+        while size < DEFAULT_TILE_SIZE {
+            zoom -= 1;
+            size *= 2;
+        }
+        zoom.clamp(0, i32::from(MAX_ZOOM)) as u8
+    }
+
+    /// Returns the fraction of a zoom level to add for rendering.
+    pub fn zoom_fraction(&self) -> f64 {
+        f64::from(self.fraction) / 100.
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        self.format.content_type()
+    }
+
+    /// Returns all tiles of the metatile this tile is part of.
+    ///
+    /// A metatile is a block of `factor` by `factor` tiles aligned to
+    /// multiples of `factor`. At low zoom levels, it is cut down to the
+    /// tiles that actually exist. The tile itself is included.
+    pub fn metatile(self, factor: u32) -> impl Iterator<Item = TileId> {
+        let end = Self::coord_end(self.zoom);
+        let x0 = self.x - self.x % factor;
+        let y0 = self.y - self.y % factor;
+        let x1 = end.min(x0 + factor);
+        let y1 = end.min(y0 + factor);
+        (y0..y1).flat_map(move |y| {
+            (x0..x1).map(move |x| TileId { x, y, ..self })
+        })
     }
 
     /*
@@ -112,8 +340,22 @@ impl TileId {
     }
     */
 
-    pub fn render(self, map: &railway::Map) -> Vec<u8> {
-        let surface = Surface::new(self.format);
+    /// Returns the entity tag for the tile rendered from a map version.
+    ///
+    /// The tag is the same for the same tile of the same map version
+    /// encoded the same way.
+    pub fn etag(&self, version: u64, webp: WebpQuality) -> String {
+        let mut hasher = StableHasher::default();
+        hasher.write_u64(version);
+        self.hash(&mut hasher);
+        if matches!(self.format, TileFormat::Webp) {
+            webp.hash(&mut hasher);
+        }
+        format!("\"{:016x}\"", hasher.finish())
+    }
+
+    pub fn render(self, map: &railway::Map, webp: WebpQuality) -> Vec<u8> {
+        let surface = Surface::new(self.format, self.size, webp);
         let _ = map.render(self, &surface);
         surface.finalize()
     }
@@ -126,6 +368,31 @@ impl fmt::Display for TileId {
 }
 
 
+//------------ Helper Functions ----------------------------------------------
+
+/// Converts longitude and latitude in degrees into storage coordinates.
+///
+/// Storage coordinates are Spherical Mercator with a range of `0. .. 1.`
+/// for both x and y with the origin in the north-west.
+pub fn storage_point(lon: f64, lat: f64) -> Point {
+    let lat = lat.to_radians();
+    Point::new(
+        (lon + 180.) / 360.,
+        (1. - (lat.tan() + 1. / lat.cos()).ln() / std::f64::consts::PI) / 2.
+    )
+}
+
+/// Converts storage coordinates into longitude and latitude in degrees.
+///
+/// This is the inverse of [`storage_point`].
+pub fn lonlat(point: Point) -> (f64, f64) {
+    (
+        point.x * 360. - 180.,
+        (std::f64::consts::PI * (1. - 2. * point.y)).sinh().atan().to_degrees()
+    )
+}
+
+
 //------------ LayerId -------------------------------------------------------
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
@@ -148,27 +415,72 @@ impl FromStr for LayerId {
 pub enum TileFormat {
     Png,
     Svg,
+    Webp,
+
+    /// JPEG with the given quality between 1 and 100.
+    ///
+    /// JPEG has no alpha channel, so it is only used for tiles with an
+    /// opaque background in place of PNG. It can’t be requested directly.
+    Jpeg(u8),
 }
 
 impl TileFormat {
+    /// Returns whether the format is a raster format.
+    pub fn is_raster(self) -> bool {
+        matches!(
+            self, TileFormat::Png | TileFormat::Webp | TileFormat::Jpeg(_)
+        )
+    }
+
+    /// Returns the format to use for a tile with the given background.
+    ///
+    /// If `jpeg` is given, PNG tiles with an opaque background are
+    /// replaced by JPEG tiles of this quality. All other tiles keep their
+    /// format.
+    pub fn for_background(
+        self, background: Background, jpeg: Option<u8>
+    ) -> Self {
+        match (self, background, jpeg) {
+            (TileFormat::Png, Background::Paper, Some(quality)) => {
+                TileFormat::Jpeg(quality)
+            }
+            _ => self
+        }
+    }
+
+    /// Returns the size of a tile of the default size in canvas units.
     pub fn size(self) -> f64 {
         match self {
-            TileFormat::Png => 512.,
+            TileFormat::Png | TileFormat::Webp | TileFormat::Jpeg(_) => 512.,
             TileFormat::Svg => 192.,
         }
     }
     
     pub fn canvas_bp(self) -> f64 {
         match self {
-            TileFormat::Png => 192./72.,
+            TileFormat::Png | TileFormat::Webp | TileFormat::Jpeg(_) => {
+                192./72.
+            }
             TileFormat::Svg => 1.,
         }
     }
 
+    /// Returns the file name extension for the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            TileFormat::Png => "png",
+            TileFormat::Svg => "svg",
+            TileFormat::Webp => "webp",
+            TileFormat::Jpeg(_) => "jpg",
+        }
+    }
+
     pub fn content_type(self) -> &'static str {
         match self {
             TileFormat::Png => "image/png",
             TileFormat::Svg => "image/svg+xml",
+            TileFormat::Webp => "image/webp",
+            TileFormat::Jpeg(_) => "image/jpeg",
         }
     }
 }
@@ -180,6 +492,7 @@ impl FromStr for TileFormat {
         match s {
             "png" => Ok(TileFormat::Png),
             "svg" => Ok(TileFormat::Svg),
+            "webp" => Ok(TileFormat::Webp),
             _ => Err(TileIdError),
         }
     }
@@ -190,32 +503,115 @@ impl fmt::Display for TileFormat {
         f.write_str(match *self {
             TileFormat::Png => "png",
             TileFormat::Svg => "svg",
+            TileFormat::Webp => "webp",
+            TileFormat::Jpeg(_) => "jpg",
         })
     }
 }
 
 
+//------------ WebpQuality ---------------------------------------------------
+
+/// The quality of WebP tiles.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum WebpQuality {
+    /// Use lossless encoding.
+    #[default]
+    Lossless,
+
+    /// Use lossy encoding with the given quality between 0 and 99.
+    Lossy(u8),
+}
+
+impl WebpQuality {
+    /// Creates the quality from the value given in the config.
+    ///
+    /// A value of 100 or more results in lossless encoding.
+    pub fn from_config(quality: u8) -> Self {
+        if quality >= 100 {
+            WebpQuality::Lossless
+        }
+        else {
+            WebpQuality::Lossy(quality)
+        }
+    }
+}
+
+
+//------------ Background ----------------------------------------------------
+
+/// The background of a tile.
+#[derive(
+    Clone, Copy, Debug, Default, Eq, Hash, PartialEq, serde::Deserialize
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Background {
+    /// Everything not covered by features is transparent.
+    ///
+    /// This is what overlays need. Raster tiles keep their alpha channel.
+    #[default]
+    Transparent,
+
+    /// The tile is filled with the background color of the layer first.
+    ///
+    /// This is for using the map on its own.
+    Paper,
+}
+
+impl FromStr for Background {
+    type Err = TileIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "transparent" => Ok(Background::Transparent),
+            "paper" => Ok(Background::Paper),
+            _ => Err(TileIdError)
+        }
+    }
+}
+
+
 //------------ Surface -------------------------------------------------------
 
 pub enum Surface {
     Png(cairo::ImageSurface),
-    Svg(cairo::SvgSurface)
+    Svg(cairo::SvgSurface),
+    Webp(cairo::ImageSurface, WebpQuality),
+    Jpeg(cairo::ImageSurface, u8),
 }
 
 impl Surface {
-    fn new(format: TileFormat) -> Self {
+    fn new(format: TileFormat, size: u32, webp: WebpQuality) -> Self {
         match format {
             TileFormat::Png => {
                 Surface::Png(cairo::ImageSurface::create(
-                    cairo::Format::ARgb32, 512, 512
+                    cairo::Format::ARgb32, size as i32, size as i32
                 ).unwrap())
             }
+            TileFormat::Webp => {
+                Surface::Webp(
+                    cairo::ImageSurface::create(
+                        cairo::Format::ARgb32, size as i32, size as i32
+                    ).unwrap(),
+                    webp
+                )
+            }
+            TileFormat::Jpeg(quality) => {
+                Surface::Jpeg(
+                    cairo::ImageSurface::create(
+                        cairo::Format::Rgb24, size as i32, size as i32
+                    ).unwrap(),
+                    quality
+                )
+            }
             TileFormat::Svg => {
                 // We are assuming 192 dpi resolution at 512 px for now.
                 // (That’s .375 pt for each pixel, which means 192 pt for
                 // 512 px. I think.)
+                let size = format.size() * f64::from(size)
+                    / f64::from(DEFAULT_TILE_SIZE);
                 Surface::Svg(cairo::SvgSurface::for_stream(
-                    192., 192., Vec::new()
+                    size, size, Vec::new()
                 ).unwrap())
             }
         }
@@ -232,6 +628,22 @@ impl Surface {
                     cairo::Format::ARgb32, size.x as i32, size.y as i32,
                 ).unwrap())
             }
+            TileFormat::Webp => {
+                Surface::Webp(
+                    cairo::ImageSurface::create(
+                        cairo::Format::ARgb32, size.x as i32, size.y as i32,
+                    ).unwrap(),
+                    WebpQuality::default()
+                )
+            }
+            TileFormat::Jpeg(quality) => {
+                Surface::Jpeg(
+                    cairo::ImageSurface::create(
+                        cairo::Format::Rgb24, size.x as i32, size.y as i32,
+                    ).unwrap(),
+                    quality
+                )
+            }
             TileFormat::Svg => {
                 // We are assuming 192 dpi resolution at 512 px for now.
                 // (That’s .375 pt for each pixel, which means 192 pt for
@@ -271,8 +683,92 @@ impl Surface {
                 let stream = *(stream.downcast::<Vec<u8>>().unwrap());
                 stream
             }
+            Surface::Webp(surface, quality) => {
+                Self::encode_webp(surface, quality)
+            }
+            Surface::Jpeg(surface, quality) => {
+                Self::encode_jpeg(surface, quality)
+            }
         }
     }
+
+    /// Encodes an image surface as JPEG.
+    ///
+    /// The surface has no alpha channel, so the pixels can be used as is.
+    fn encode_jpeg(surface: cairo::ImageSurface, quality: u8) -> Vec<u8> {
+        surface.flush();
+        let width = surface.width() as u16;
+        let height = surface.height() as u16;
+        let stride = surface.stride() as usize;
+
+        // Cairo stores RGB24 as native-endian 32 bit words with the upper
+        // eight bits unused.
+        let mut rgb = Vec::with_capacity(
+            usize::from(width) * usize::from(height) * 3
+        );
+        surface.with_data(|data| {
+            for row in data.chunks(stride).take(usize::from(height)) {
+                for pixel in row.chunks_exact(4).take(usize::from(width)) {
+                    let pixel = u32::from_ne_bytes(
+                        pixel.try_into().unwrap()
+                    );
+                    rgb.extend_from_slice(&[
+                        (pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8
+                    ]);
+                }
+            }
+        }).unwrap();
+
+        let mut data = Vec::new();
+        jpeg_encoder::Encoder::new(&mut data, quality).encode(
+            &rgb, width, height, jpeg_encoder::ColorType::Rgb
+        ).unwrap();
+        data
+    }
+
+    /// Encodes an image surface as WebP.
+    fn encode_webp(
+        surface: cairo::ImageSurface, quality: WebpQuality
+    ) -> Vec<u8> {
+        surface.flush();
+        let width = surface.width() as u32;
+        let height = surface.height() as u32;
+        let stride = surface.stride() as usize;
+
+        // Cairo uses premultiplied ARGB stored in native-endian 32 bit
+        // words while WebP wants straight RGBA bytes. Fully transparent
+        // pixels stay transparent.
+        let mut rgba = Vec::with_capacity((width * height * 4) as usize);
+        surface.with_data(|data| {
+            for row in data.chunks(stride).take(height as usize) {
+                for pixel in row.chunks_exact(4).take(width as usize) {
+                    let pixel = u32::from_ne_bytes(
+                        pixel.try_into().unwrap()
+                    );
+                    let alpha = pixel >> 24;
+                    let channel = |shift: u32| {
+                        if alpha == 0 {
+                            return 0
+                        }
+                        let value = (pixel >> shift) & 0xff;
+                        ((value * 255 + alpha / 2) / alpha).min(255) as u8
+                    };
+                    rgba.extend_from_slice(&[
+                        channel(16), channel(8), channel(0), alpha as u8
+                    ]);
+                }
+            }
+        }).unwrap();
+
+        let encoder = webp::Encoder::from_rgba(&rgba, width, height);
+        let data = match quality {
+            WebpQuality::Lossless => encoder.encode_lossless(),
+            WebpQuality::Lossy(quality) => {
+                encoder.encode(f32::from(quality))
+            }
+        };
+        data.to_vec()
+    }
 }
 
 impl ops::Deref for Surface {
@@ -281,7 +777,9 @@ impl ops::Deref for Surface {
     fn deref(&self) -> &Self::Target {
         match *self {
             Surface::Png(ref surface) => surface,
-            Surface::Svg(ref surface) => surface
+            Surface::Svg(ref surface) => surface,
+            Surface::Webp(ref surface, _) => surface,
+            Surface::Jpeg(ref surface, _) => surface,
         }
     }
 }
@@ -291,3 +789,37 @@ impl ops::Deref for Surface {
 
 pub struct TileIdError;
 
+
+//------------ MetatileError -------------------------------------------------
+
+/// A metatile factor was not acceptable.
+#[derive(Clone, Copy, Debug)]
+pub struct MetatileError(u32);
+
+impl fmt::Display for MetatileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+            "invalid metatile factor {}: must be a power of two \
+             and at most {}",
+            self.0, MAX_METATILE
+        )
+    }
+}
+
+
+//------------ TileSizeError -------------------------------------------------
+
+/// A tile size was not acceptable.
+#[derive(Clone, Copy, Debug)]
+pub struct TileSizeError(u32);
+
+impl fmt::Display for TileSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+            "invalid tile size {}: must be {} multiplied by a power of two \
+             and at most {}",
+            self.0, MIN_TILE_SIZE, MAX_TILE_SIZE
+        )
+    }
+}
+
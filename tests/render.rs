@@ -0,0 +1,161 @@
+//! Render tests.
+//!
+//! These tests load the fixture maps in `tests/fixtures`, render a fixed
+//! tile of them, and compare the result with a reference image stored next
+//! to the fixture.
+//!
+//! A few tests check the loaded features of a fixture instead.
+//!
+//! If the rendering changes on purpose, the reference images can be
+//! updated by running the tests with the `RAILMAP_BLESS` environment
+//! variable set. Missing reference images are created the same way. Without
+//! the variable, a missing reference image fails the test.
+
+use std::{env, fs};
+use std::path::PathBuf;
+use std::str::FromStr;
+use femtomap::import::watch::WatchSet;
+use railmap::MapConfig;
+use railmap::railway::Map;
+use railmap::railway::import::load::LoadFeatures;
+use railmap::tile::{
+    self, Background, DEFAULT_TILE_SIZE, LayerId, TileId, WebpQuality
+};
+
+
+//------------ Configurable Constants ----------------------------------------
+
+/// The largest difference of a color channel for pixels to be equal.
+const CHANNEL_TOLERANCE: u8 = 8;
+
+/// The largest share of pixels that may differ from the reference.
+const PIXEL_TOLERANCE: f64 = 0.001;
+
+
+//------------ Tests ---------------------------------------------------------
+
+/// Double electrified track with a station.
+#[test]
+fn basic() {
+    assert_render("basic", "el", 14, 10.0085, 50.0007);
+}
+
+/// Two lines of different operators.
+#[test]
+fn operator() {
+    assert_render("operator", "op", 14, 10.0085, 50.0007);
+}
+
+/// Track carrying a second line takes the second line’s class.
+#[test]
+fn combined_class() {
+    let dump = load_map("combined").dump();
+    let info = &dump.railway[0].info;
+    assert_eq!(info.class.as_ref().unwrap().category, "first");
+    assert_eq!(info.combined.as_ref().unwrap().category, "second");
+}
+
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Renders the tile containing a point and compares it with the reference.
+///
+/// The reference image is `{layer}-{zoom}.png` in the fixture’s
+/// directory.
+fn assert_render(fixture: &str, layer: &str, zoom: u8, lon: f64, lat: f64) {
+    let dir = fixture_dir(fixture);
+    let map = load_map(fixture);
+    let mut tile = TileId::containing(
+        LayerId::from_str(layer).unwrap(), zoom,
+        tile::storage_point(lon, lat), false, DEFAULT_TILE_SIZE,
+    ).unwrap();
+    tile.background = Background::Paper;
+    let data = tile.render(&map, WebpQuality::default());
+
+    let reference = dir.join(format!("{}-{}.png", layer, zoom));
+    if env::var_os("RAILMAP_BLESS").is_some() {
+        fs::write(&reference, &data).unwrap();
+        return
+    }
+    if !reference.exists() {
+        panic!(
+            "missing reference image {}, run with RAILMAP_BLESS set to \
+             create it",
+            reference.display()
+        );
+    }
+    let expected = fs::read(&reference).unwrap();
+    if let Err(err) = compare(&data, &expected) {
+        let actual = dir.join(format!("{}-{}.actual.png", layer, zoom));
+        fs::write(&actual, &data).unwrap();
+        panic!(
+            "{} differs from {}: {}",
+            actual.display(), reference.display(), err
+        );
+    }
+}
+
+/// Returns the directory of a fixture.
+fn fixture_dir(fixture: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests").join("fixtures").join(fixture)
+}
+
+/// Loads the map of a fixture.
+fn load_map(fixture: &str) -> Map {
+    let config = MapConfig::load(
+        fixture_dir(fixture).join("map.toml")
+    ).unwrap();
+    let mut features = LoadFeatures::new();
+    features.set_measures(&config.measures);
+    let mut watch = WatchSet::default();
+    let mut names = config.regions.keys().collect::<Vec<_>>();
+    names.sort();
+    for name in names {
+        features.load_region(name, &config.regions[name], &mut watch);
+    }
+    match features.finalize() {
+        Ok(features) => Map::new(features, &config, 0),
+        Err(err) => panic!("failed to load fixture {}:\n{}", fixture, err),
+    }
+}
+
+/// Compares two PNG images.
+///
+/// Returns an error describing the difference if they don’t match within
+/// the tolerance.
+fn compare(actual: &[u8], expected: &[u8]) -> Result<(), String> {
+    let (actual_size, actual) = decode(actual);
+    let (expected_size, expected) = decode(expected);
+    if actual_size != expected_size {
+        return Err(format!(
+            "size is {}x{} instead of {}x{}",
+            actual_size.0, actual_size.1, expected_size.0, expected_size.1
+        ))
+    }
+    let differing = actual.chunks(4).zip(expected.chunks(4)).filter(
+        |(left, right)| {
+            left.iter().zip(right.iter()).any(|(left, right)| {
+                left.abs_diff(*right) > CHANNEL_TOLERANCE
+            })
+        }
+    ).count();
+    let share = differing as f64 / (actual.len() / 4) as f64;
+    if share > PIXEL_TOLERANCE {
+        return Err(format!(
+            "{} pixels ({:.2}%) differ", differing, share * 100.
+        ))
+    }
+    Ok(())
+}
+
+/// Decodes a PNG image into its size and ARGB pixel data.
+fn decode(data: &[u8]) -> ((i32, i32), Vec<u8>) {
+    let surface = cairo::ImageSurface::create_from_png(
+        &mut &*data
+    ).unwrap();
+    let mut pixels = Vec::new();
+    surface.with_data(|data| pixels.extend_from_slice(data)).unwrap();
+    ((surface.width(), surface.height()), pixels)
+}